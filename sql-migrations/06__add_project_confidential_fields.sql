@@ -0,0 +1,9 @@
+-- Confidential fields, readable according to ProjectFieldPolicy
+ALTER TABLE projects
+ADD COLUMN budget double precision DEFAULT 0;
+
+ALTER TABLE projects
+ADD COLUMN internal_description text DEFAULT '';
+
+INSERT INTO role_type (name, created_by)
+VALUES ('Auditor', 1);
@@ -0,0 +1,13 @@
+-- Project actions delegated by a project lead to another party, enforced by a generated
+-- Delegation.<delegation_id> policy
+CREATE TABLE delegations (
+    delegation_id SERIAL PRIMARY KEY,
+    project_id int references projects(id),
+    delegator_id int references parties(party_id),
+    delegate_id int references parties(party_id),
+    actions text,          -- comma separated action names
+    valid_from bigint,     -- unix timestamp (seconds)
+    valid_to bigint,       -- unix timestamp (seconds), excluded
+    revoked int DEFAULT 0,
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
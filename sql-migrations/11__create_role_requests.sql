@@ -0,0 +1,13 @@
+-- Self-service requests for a role type (project_id = 0) or for an assignment to a project
+-- through the party role of the role type, decided by an approver authorized by Cedar
+CREATE TABLE role_requests (
+    request_id SERIAL PRIMARY KEY,
+    party_id int references parties(party_id),
+    role_type_id int references role_type(role_type_id),
+    project_id int DEFAULT 0,
+    reason text,
+    status text DEFAULT 'pending', -- pending, approved or rejected
+    decided_by int DEFAULT 0,      -- party_id of the approver
+    decided_at bigint DEFAULT 0,   -- unix timestamp (seconds)
+    requested_at bigint            -- unix timestamp (seconds)
+);
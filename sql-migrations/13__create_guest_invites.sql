@@ -0,0 +1,11 @@
+-- Invite links of external collaborators: a guest token scoped to one project, the
+-- `Guest` principal of the policies
+CREATE TABLE guest_invites (
+    invite_id SERIAL PRIMARY KEY,
+    project_id int references projects(id),
+    invited_by int references parties(party_id),
+    label text,            -- e.g. the e-mail address of the guest
+    valid_to bigint,       -- unix timestamp (seconds), excluded
+    revoked int DEFAULT 0,
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
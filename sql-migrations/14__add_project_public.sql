@@ -0,0 +1,4 @@
+-- Published projects are readable by anybody, anonymous requests included (see PublicProjectPolicy)
+-- 0 or 1, booleans are not supported by the sqlx Any driver on SQLite
+ALTER TABLE projects
+ADD COLUMN public int DEFAULT 0;
@@ -0,0 +1,11 @@
+-- Role of the party on the project, independent of its global role type:
+-- Contributor, Reviewer, Viewer or Maintainer (see ProjectRolePolicy)
+ALTER TABLE assignments
+ADD COLUMN project_role text DEFAULT 'Contributor';
+
+UPDATE assignments
+SET project_role = 'Contributor';
+
+-- Project role requested along with an assignment
+ALTER TABLE role_requests
+ADD COLUMN project_role text DEFAULT 'Contributor';
@@ -0,0 +1,29 @@
+-- Organizations (tenants) hosted on the deployment, isolated from each other (see TenantPolicy)
+CREATE TABLE organizations (
+    tenant_id SERIAL PRIMARY KEY,
+    name text UNIQUE,
+    policies text DEFAULT '', -- Cedar policies of the tenant, layered over the global ones
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO organizations (tenant_id, name)
+VALUES (1, 'Default');
+
+-- Existing parties, projects and assignments belong to the default organization
+ALTER TABLE parties
+ADD COLUMN tenant_id int DEFAULT 1;
+
+ALTER TABLE projects
+ADD COLUMN tenant_id int DEFAULT 1;
+
+ALTER TABLE assignments
+ADD COLUMN tenant_id int DEFAULT 1;
+
+UPDATE parties
+SET tenant_id = 1;
+
+UPDATE projects
+SET tenant_id = 1;
+
+UPDATE assignments
+SET tenant_id = 1;
@@ -0,0 +1,26 @@
+-- Teams of parties, the Group::"Team<team_id>" entities parents of their members
+CREATE TABLE teams (
+    team_id SERIAL PRIMARY KEY,
+    tenant_id int references organizations(tenant_id),
+    name text,
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE team_members (
+    team_id int references teams(team_id),
+    party_id int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (team_id, party_id)
+);
+
+-- A team assigned to a project: its group is in the assignees group of the project, and in the
+-- group of its project role
+CREATE TABLE team_assignments (
+    team_id int references teams(team_id),
+    project_id int references projects(id),
+    project_role text DEFAULT 'Contributor',
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (team_id, project_id)
+);
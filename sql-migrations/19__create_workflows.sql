@@ -0,0 +1,32 @@
+-- Workflow of each project: the states of its tasks, the first one (position 0) being the
+-- state of the new tasks
+CREATE TABLE workflow_states (
+    project_id int references projects(id),
+    state text,
+    position int,
+    PRIMARY KEY (project_id, state)
+);
+
+-- Transitions allowed between the states, each one granted to the assignees of a project role
+-- by the generated Workflow.<transition_id> policy
+CREATE TABLE workflow_transitions (
+    transition_id SERIAL PRIMARY KEY,
+    project_id int references projects(id),
+    from_state text,
+    to_state text,
+    project_role text
+);
+
+-- Default workflow of the existing projects (see services/workflows.rs)
+INSERT INTO workflow_states (project_id, state, position) SELECT id, 'todo', 0 FROM projects;
+INSERT INTO workflow_states (project_id, state, position) SELECT id, 'in_progress', 1 FROM projects;
+INSERT INTO workflow_states (project_id, state, position) SELECT id, 'done', 2 FROM projects;
+
+INSERT INTO workflow_transitions (project_id, from_state, to_state, project_role)
+SELECT id, 'todo', 'in_progress', 'Contributor' FROM projects;
+INSERT INTO workflow_transitions (project_id, from_state, to_state, project_role)
+SELECT id, 'in_progress', 'todo', 'Contributor' FROM projects;
+INSERT INTO workflow_transitions (project_id, from_state, to_state, project_role)
+SELECT id, 'in_progress', 'done', 'Reviewer' FROM projects;
+INSERT INTO workflow_transitions (project_id, from_state, to_state, project_role)
+SELECT id, 'done', 'in_progress', 'Reviewer' FROM projects;
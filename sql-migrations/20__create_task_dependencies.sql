@@ -0,0 +1,14 @@
+-- Estimate of the tasks, in days, for the schedule
+ALTER TABLE tasks ADD COLUMN duration int DEFAULT 1;
+
+-- Blocking relationships between the tasks of a project, without cycles:
+-- finish_to_start, the successor starts once the predecessor is finished
+-- start_to_start, the successor starts once the predecessor is started
+CREATE TABLE task_dependencies (
+    predecessor_id int references tasks(task_id),
+    successor_id int references tasks(task_id),
+    kind text DEFAULT 'finish_to_start',
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (predecessor_id, successor_id)
+);
@@ -0,0 +1,15 @@
+-- Status changes of the tasks, for the burndown and cumulative-flow reports: the creation
+-- (from_state NULL) and every transition
+CREATE TABLE task_status_history (
+    history_id SERIAL PRIMARY KEY,
+    task_id int references tasks(task_id),
+    project_id int references projects(id),
+    from_state text,
+    to_state text,
+    changed_by int references parties(party_id),
+    changed_at bigint      -- unix timestamp (seconds)
+);
+
+-- The tasks created before the history are in their current status since ever
+INSERT INTO task_status_history (task_id, project_id, to_state, changed_at)
+SELECT task_id, project_id, status, 0 FROM tasks;
@@ -0,0 +1,24 @@
+-- Working calendar of an organization: the days of the week off, e.g. 'Sat,Sun', and the holidays
+ALTER TABLE organizations
+ADD COLUMN weekend text DEFAULT 'Sat,Sun';
+
+CREATE TABLE holidays (
+    holiday_id SERIAL PRIMARY KEY,
+    tenant_id int references organizations(tenant_id),
+    date bigint,           -- unix timestamp (seconds) of the day, midnight UTC
+    name text,
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (tenant_id, date)
+);
+
+-- Leave of a party, the days off from its organization calendar on top
+CREATE TABLE leave_entries (
+    leave_id SERIAL PRIMARY KEY,
+    party_id int references parties(party_id),
+    from_date bigint,      -- unix timestamp (seconds) of the first day, midnight UTC
+    to_date bigint,        -- unix timestamp (seconds) of the last day, midnight UTC
+    reason text DEFAULT '',
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
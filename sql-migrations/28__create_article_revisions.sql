@@ -0,0 +1,40 @@
+-- Articles become the wiki of the projects, the `Article` entities children of their `Project`.
+-- The articles of 02__create_articles.sql are kept, their current text as their first revision
+ALTER TABLE articles
+ADD COLUMN project_id int references projects(id);
+
+ALTER TABLE articles
+ADD COLUMN revision int DEFAULT 1; -- number of the current revision
+
+ALTER TABLE articles
+ADD COLUMN created_by int references parties(party_id);
+
+ALTER TABLE articles
+ADD COLUMN created_at timestamp DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE articles
+ALTER COLUMN content SET DEFAULT ''; -- Markdown of the current revision
+
+-- unix timestamp (seconds), a draft when NULL
+ALTER TABLE articles
+ALTER COLUMN published_on TYPE bigint USING EXTRACT(EPOCH FROM published_on)::bigint;
+
+UPDATE articles SET title = COALESCE(title, ''), content = COALESCE(content, '');
+
+-- Every edit of an article, restorable
+CREATE TABLE article_revisions (
+    revision_id SERIAL PRIMARY KEY,
+    article_id int references articles(id),
+    revision int,
+    title text,
+    content text,
+    restored_from int,       -- revision restored, none for an edit
+    edited_by int references parties(party_id),
+    edited_at bigint,        -- unix timestamp (seconds)
+    UNIQUE (article_id, revision)
+);
+
+INSERT INTO article_revisions (article_id, revision, title, content, edited_by, edited_at)
+SELECT id, 1, title, content, published_by,
+    COALESCE(published_on, EXTRACT(EPOCH FROM CURRENT_TIMESTAMP)::bigint)
+FROM articles;
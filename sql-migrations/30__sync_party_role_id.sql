@@ -0,0 +1,4 @@
+-- Roles granted on approval take their party_role_id from the sequence, which the explicit ids of
+-- the seeded roles left behind
+SELECT setval(pg_get_serial_sequence('party_role', 'party_role_id'), COALESCE(MAX(party_role_id), 1))
+FROM party_role;
@@ -0,0 +1,3 @@
+-- Deleted tasks are kept for their status history, the reports still count them until then
+ALTER TABLE tasks
+ADD COLUMN deleted_at bigint; -- unix timestamp (seconds), NULL while the task exists
@@ -0,0 +1,11 @@
+-- Archived projects and projects under legal hold are read-only (see ReadOnlyProjectPolicy)
+ALTER TABLE projects
+ADD COLUMN status text DEFAULT 'active';
+
+-- 0 or 1, booleans are not supported by the sqlx Any driver on SQLite
+ALTER TABLE projects
+ADD COLUMN legal_hold int DEFAULT 0;
+
+UPDATE projects
+SET status = 'active',
+    legal_hold = 0;
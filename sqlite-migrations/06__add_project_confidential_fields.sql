@@ -0,0 +1,9 @@
+-- Confidential fields, readable according to ProjectFieldPolicy
+ALTER TABLE projects
+ADD COLUMN budget double precision DEFAULT 0;
+
+ALTER TABLE projects
+ADD COLUMN internal_description text DEFAULT '';
+
+INSERT INTO role_type (role_type_id, name, created_by)
+VALUES (5, 'Auditor', 1);
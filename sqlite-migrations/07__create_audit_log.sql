@@ -0,0 +1,9 @@
+CREATE TABLE audit_log (
+    audit_id INTEGER PRIMARY KEY,
+    party_id int references parties(party_id),
+    action text,
+    resource text,            -- Cedar entity uid, e.g. Project::"1"
+    policies text DEFAULT '', -- comma separated ids of the determining policies
+    detail text DEFAULT '',
+    logged_at bigint          -- unix timestamp (seconds)
+);
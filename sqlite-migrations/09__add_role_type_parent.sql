@@ -0,0 +1,12 @@
+-- Role inheritance: a role type is a member of its parent role type, emitted as the
+-- parent of the Cedar Role entity (a project lead is also a developer)
+ALTER TABLE role_type
+ADD COLUMN parent_role_type_id int references role_type(role_type_id);
+
+UPDATE role_type
+SET parent_role_type_id = (SELECT role_type_id FROM role_type WHERE name = 'Developer')
+WHERE name = 'Project Lead';
+
+UPDATE role_type
+SET parent_role_type_id = (SELECT role_type_id FROM role_type WHERE name = 'Auditor')
+WHERE name = 'Administrator';
@@ -0,0 +1,11 @@
+-- Validity period of a role assignment, unix timestamps (seconds): a membership counts from
+-- valid_from (included, none when NULL) until valid_to (excluded, none when NULL)
+ALTER TABLE party_role
+ADD COLUMN valid_from bigint;
+
+ALTER TABLE party_role
+ADD COLUMN valid_to bigint;
+
+-- Set by the sweep once the expiration of the membership has been reported
+ALTER TABLE party_role
+ADD COLUMN expired int DEFAULT 0;
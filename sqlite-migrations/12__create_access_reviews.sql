@@ -0,0 +1,21 @@
+-- Access review campaigns: per project, a snapshot of the parties with access and the
+-- policy granting it, confirmed or revoked by the project owner
+CREATE TABLE access_reviews (
+    review_id INTEGER PRIMARY KEY,
+    campaign text,                -- e.g. 2026-Q4
+    project_id int references projects(id),
+    reviewer_id int references parties(party_id),
+    status text DEFAULT 'open',   -- open or completed
+    created_at bigint,            -- unix timestamp (seconds)
+    completed_at bigint DEFAULT 0 -- unix timestamp (seconds)
+);
+
+CREATE TABLE access_review_items (
+    item_id INTEGER PRIMARY KEY,
+    review_id int references access_reviews(review_id),
+    party_id int references parties(party_id),
+    grant_kind text,                -- ownership, assignment, delegation or role
+    policy text,                    -- id of the policy granting ViewProject
+    decision text DEFAULT 'pending', -- pending, confirmed or revoked
+    decided_at bigint DEFAULT 0      -- unix timestamp (seconds)
+);
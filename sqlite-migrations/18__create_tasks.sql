@@ -0,0 +1,14 @@
+-- Tasks of a project, the `Task` entities children of their `Project`
+CREATE TABLE tasks (
+    task_id INTEGER PRIMARY KEY,
+    project_id int references projects(id),
+    title text,
+    description text DEFAULT '',
+    status text DEFAULT 'todo',  -- "todo", "in_progress" or "done"
+    assignee int references party_role(party_role_id),
+    due_date bigint,             -- unix timestamp (seconds), none when NULL
+    created_by int references parties(party_id),
+    updated_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP,
+    updated_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,12 @@
+-- Milestones of a project, the `Milestone` entities children of their `Project`
+CREATE TABLE milestones (
+    milestone_id INTEGER PRIMARY KEY,
+    project_id int references projects(id),
+    name text,
+    description text DEFAULT '',
+    due_date bigint,       -- unix timestamp (seconds), none when NULL
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE tasks ADD COLUMN milestone_id int references milestones(milestone_id);
@@ -0,0 +1,22 @@
+-- Sprints of a project: planned, then active (one at a time per project), then closed
+CREATE TABLE sprints (
+    sprint_id INTEGER PRIMARY KEY,
+    project_id int references projects(id),
+    name text,
+    goal text DEFAULT '',
+    start_date bigint,     -- unix timestamp (seconds)
+    end_date bigint,       -- unix timestamp (seconds)
+    status text DEFAULT 'planned',
+    completed_estimate int, -- days of the tasks done on close, none until closed
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP,
+    closed_at timestamp
+);
+
+-- Tasks committed to a sprint, an unfinished task carried over is committed to the next one too
+CREATE TABLE sprint_tasks (
+    sprint_id int references sprints(sprint_id),
+    task_id int references tasks(task_id),
+    completed int DEFAULT 0, -- done on close
+    PRIMARY KEY (sprint_id, task_id)
+);
@@ -0,0 +1,24 @@
+-- Time logged by a party on a project, against one of its tasks or the project itself
+CREATE TABLE time_entries (
+    entry_id INTEGER PRIMARY KEY,
+    project_id int references projects(id),
+    task_id int references tasks(task_id), -- none when logged against the project
+    party_id int references parties(party_id),
+    date bigint,           -- unix timestamp (seconds) of the day, midnight UTC
+    minutes int,
+    note text DEFAULT '',
+    billable int DEFAULT 1,
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP,
+    updated_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Approved timesheets: the entries of the party on the project for the week are locked
+CREATE TABLE timesheet_approvals (
+    approval_id INTEGER PRIMARY KEY,
+    project_id int references projects(id),
+    party_id int references parties(party_id),
+    week_start bigint,     -- unix timestamp (seconds) of the Monday, midnight UTC
+    approved_by int references parties(party_id),
+    approved_at timestamp DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (project_id, party_id, week_start)
+);
@@ -0,0 +1,14 @@
+-- Currency of the budget, confidential as the budget
+ALTER TABLE projects
+ADD COLUMN budget_currency text DEFAULT 'USD';
+
+-- Hourly cost rates of an organization, per role type or per party, the party rate first
+CREATE TABLE cost_rates (
+    rate_id INTEGER PRIMARY KEY,
+    tenant_id int references organizations(tenant_id),
+    role_type_id int references role_type(role_type_id), -- none for a party rate
+    party_id int references parties(party_id),           -- none for a role type rate
+    hourly_rate double precision,
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,10 @@
+-- Share of the time of the party on the project, over an optional date range, for capacity
+-- planning only: access follows the assignment whatever its allocation
+ALTER TABLE assignments
+ADD COLUMN allocation int DEFAULT 100; -- percent
+
+ALTER TABLE assignments
+ADD COLUMN allocated_from bigint;      -- unix timestamp (seconds) of the first day, none when open
+
+ALTER TABLE assignments
+ADD COLUMN allocated_to bigint;        -- unix timestamp (seconds) of the last day, none when open
@@ -0,0 +1,32 @@
+-- Articles become the wiki of the projects, the `Article` entities children of their `Project`.
+-- 02__create_articles.sql created no articles table on SQLite, the existing articles of the
+-- other databases are kept as their first revision
+
+CREATE TABLE articles (
+    id INTEGER PRIMARY KEY,
+    project_id int references projects(id),
+    title text,
+    content text DEFAULT '', -- Markdown of the current revision
+    revision int DEFAULT 1,  -- number of the current revision
+    published_by int references parties(party_id),
+    published_on bigint,     -- unix timestamp (seconds), a draft when NULL
+    created_by int references parties(party_id),
+    created_at timestamp DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Every edit of an article, restorable
+CREATE TABLE article_revisions (
+    revision_id INTEGER PRIMARY KEY,
+    article_id int references articles(id),
+    revision int,
+    title text,
+    content text,
+    restored_from int,       -- revision restored, none for an edit
+    edited_by int references parties(party_id),
+    edited_at bigint,        -- unix timestamp (seconds)
+    UNIQUE (article_id, revision)
+);
+
+INSERT INTO article_revisions (article_id, revision, title, content, edited_by, edited_at)
+SELECT id, 1, title, COALESCE(content, ''), published_by, COALESCE(published_on, strftime('%s', 'now'))
+FROM articles;
@@ -0,0 +1,5 @@
+-- The revision of an article readers see, later edits stay drafts until published
+ALTER TABLE articles
+ADD COLUMN published_revision int; -- NULL for a draft
+
+UPDATE articles SET published_revision = revision WHERE published_on IS NOT NULL;
@@ -0,0 +1,8 @@
+-- party_role_id is no alias of the rowid on SQLite, the roles granted on approval are numbered
+-- after the existing ones
+CREATE TRIGGER party_role_id AFTER INSERT ON party_role
+WHEN NEW.party_role_id IS NULL
+BEGIN
+    UPDATE party_role SET party_role_id = (SELECT MAX(party_role_id) + 1 FROM party_role)
+    WHERE rowid = NEW.rowid;
+END;
@@ -10,7 +10,7 @@ use actix_web::{
 use dotenv::dotenv;
 use sqlx::{self, any::AnyPoolOptions};
 use sqlx::{Any, Pool};
-use std::{io, sync::Arc};
+use std::{io, sync::Arc, time::Duration};
 
 use actix_web_httpauth::{
     extractors::{
@@ -23,9 +23,36 @@ use actix_web_httpauth::{
 use derive_more::From;
 
 mod services;
-use services::Permission;
-use services::{status, TokenService};
-use services::{create_project, get_project, list_projects};
+use services::{Permission, RequestContext, RoleHierarchy};
+use services::{status, TokenClaims, TokenService};
+use services::{create_project, get_project, list_projects, update_project};
+use services::{create_delegation, list_audit_entries, list_delegations, revoke_delegation};
+use services::request_break_glass;
+use services::{active_invite, create_guest_invite, list_guest_invites, revoke_guest_invite};
+use services::{create_access_review_campaign, decide_access_review_item, export_access_review, get_access_review};
+use services::{approve_role_request, create_role_request, list_role_requests, reject_role_request};
+use services::{create_task, delete_task, get_task, list_tasks, transition_task, update_task};
+use services::{get_workflow, update_workflow};
+use services::{create_dependency, delete_dependency, get_schedule, list_dependencies};
+use services::{create_milestone, delete_milestone, list_milestones, milestone_rollup, update_milestone};
+use services::{close_sprint, commit_sprint_tasks, create_sprint, get_velocity, list_sprints, start_sprint};
+use services::{cumulative_flow_report, milestone_burndown, sprint_burndown};
+use services::{
+    approve_timesheet, create_time_entry, delete_time_entry, get_project_time, get_timesheet, list_time_entries,
+    update_time_entry,
+};
+use services::{delete_cost_rate, get_budget, list_cost_rates, set_cost_rate};
+use services::{
+    create_holiday, create_leave, delete_holiday, delete_leave, get_calendar, import_holidays, list_leave,
+    update_weekend,
+};
+use services::{
+    create_article, delete_article, get_article, list_article_revisions, list_articles, publish_article,
+    restore_article_revision, update_article,
+};
+use services::{get_capacity, list_assignments, update_allocation};
+use services::{add_team_member, assign_team, create_team, list_teams, remove_team_member, unassign_team};
+use services::{active_roles, party_tenant, sweep_expired_party_roles, SWEEP_INTERVAL_SECONDS};
 
 mod repository;
 
@@ -35,32 +62,72 @@ pub struct AppState {
     permission: Permission,
 }
 
+/// Optional authentication: a request without token is evaluated as `User::"anonymous"`,
+/// a request with an invalid token is rejected.
 async fn validator(
     req: ServiceRequest,
-    credentials: BearerAuth,
+    credentials: Option<BearerAuth>,
 ) -> std::result::Result<ServiceRequest, (Error, ServiceRequest)> {
+    let credentials = match credentials {
+        Some(credentials) => credentials,
+        None => {
+            let anonymous = TokenClaims::anonymous();
+            let context = RequestContext::from_request(&req, &anonymous);
+            req.extensions_mut().insert(context);
+            req.extensions_mut().insert(anonymous);
+            return Ok(req);
+        }
+    };
+
     let token_service = TokenService::default();
     let token = credentials.token();
     let token_claims = token_service.verify(token).await;
 
     match token_claims {
-        Ok(value) => {
+        Ok(mut value) => {
+            if let Some(state) = req.app_data::<Data<Arc<AppState>>>() {
+                if let Some(project_id) = value.guest_project {
+                    // an invite link stops working once revoked
+                    match active_invite(&state.db, value.id, project_id).await {
+                        Ok(true) => {}
+                        Ok(false) => return Err((authentication_error(&req), req)),
+                        Err(err) => return Err((err.into(), req)),
+                    }
+                } else {
+                    // the token is bound to the organization of the party
+                    match party_tenant(&state.db, value.id).await {
+                        Ok(Some(tenant_id)) if tenant_id == value.tenant_id => {}
+                        Ok(_) => return Err((authentication_error(&req), req)),
+                        Err(err) => return Err((err.into(), req)),
+                    }
+                    // roles of the token are only honored while the party holds them
+                    match active_roles(&state.db, value.id).await {
+                        Ok(roles) => value.roles.retain(|role| roles.contains(role)),
+                        Err(err) => return Err((err.into(), req)),
+                    }
+                }
+            }
+            let context = RequestContext::from_request(&req, &value);
+            req.extensions_mut().insert(context);
             req.extensions_mut().insert(value);
             Ok(req)
         }
         Err(err) => {
-            dbg!(&err);
-            println!("{:?}", &err);
-            let config = req
-                .app_data::<bearer::Config>()
-                .cloned()
-                .unwrap_or_default()
-                .scope("");
-            Err((AuthenticationError::from(config).into(), req))
+            log::debug!("{:?}", err);
+            Err((authentication_error(&req), req))
         }
     }
 }
 
+fn authentication_error(req: &ServiceRequest) -> Error {
+    let config = req
+        .app_data::<bearer::Config>()
+        .cloned()
+        .unwrap_or_default()
+        .scope("");
+    AuthenticationError::from(config).into()
+}
+
 pub type Result<T> = std::result::Result<T, MainError>;
 
 #[allow(dead_code)]
@@ -106,6 +173,7 @@ impl error::ResponseError for MainError {
 #[actix_web::main]
 async fn main() -> Result<()> {
     dotenv().ok();
+    env_logger::init();
     // Configure the connection options
 
     sqlx::any::install_default_drivers();
@@ -120,11 +188,30 @@ async fn main() -> Result<()> {
             .unwrap()
     };
 
+    let roles = RoleHierarchy::load(&pool).await?;
+
+    let sweep_pool = pool.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval =
+            actix_web::rt::time::interval(Duration::from_secs(SWEEP_INTERVAL_SECONDS));
+        loop {
+            interval.tick().await;
+            match sweep_expired_party_roles(&sweep_pool).await {
+                Ok(expired) => {
+                    for grant in expired {
+                        log::info!("expired role assignment {:?}", grant);
+                    }
+                }
+                Err(err) => log::error!("sweeping expired role assignments: {:?}", err),
+            }
+        }
+    });
+
     let a = HttpServer::new(move || {
-        let bearer_middleware = HttpAuthentication::bearer(validator);
+        let bearer_middleware = HttpAuthentication::with_fn(validator);
         let app_state = Arc::new(AppState {
             db: pool.clone(),
-            permission: Permission::default(),
+            permission: Permission::default().with_role_hierarchy(roles.clone()),
         });
         App::new()
             .app_data(Data::new(app_state))
@@ -134,14 +221,89 @@ async fn main() -> Result<()> {
             .service(
                 web::scope("")
                     .wrap(bearer_middleware.clone())
-                    .service(list_projects),
-            )
-            .service(
-                web::scope("")
-                    .wrap(bearer_middleware.clone())
-                    .service(get_project),
+                    .service(list_projects)
+                    .service(get_project)
+                    .service(update_project)
+                    .service(create_delegation)
+                    .service(list_delegations)
+                    .service(revoke_delegation)
+                    .service(create_guest_invite)
+                    .service(list_guest_invites)
+                    .service(revoke_guest_invite)
+                    .service(list_audit_entries)
+                    .service(request_break_glass)
+                    .service(create_role_request)
+                    .service(list_role_requests)
+                    .service(approve_role_request)
+                    .service(reject_role_request)
+                    .service(create_access_review_campaign)
+                    .service(get_access_review)
+                    .service(decide_access_review_item)
+                    .service(export_access_review)
+                    .service(create_team)
+                    .service(list_teams)
+                    .service(add_team_member)
+                    .service(remove_team_member)
+                    .service(assign_team)
+                    .service(unassign_team)
+                    .service(list_tasks)
+                    .service(create_task)
+                    .service(get_task)
+                    .service(update_task)
+                    .service(delete_task)
+                    .service(transition_task)
+                    .service(get_workflow)
+                    .service(update_workflow)
+                    .service(list_dependencies)
+                    .service(create_dependency)
+                    .service(delete_dependency)
+                    .service(get_schedule)
+                    .service(milestone_rollup)
+                    .service(list_milestones)
+                    .service(create_milestone)
+                    .service(update_milestone)
+                    .service(delete_milestone)
+                    .service(list_sprints)
+                    .service(create_sprint)
+                    .service(commit_sprint_tasks)
+                    .service(start_sprint)
+                    .service(close_sprint)
+                    .service(get_velocity)
+                    .service(sprint_burndown)
+                    .service(milestone_burndown)
+                    .service(cumulative_flow_report)
+                    .service(list_time_entries)
+                    .service(create_time_entry)
+                    .service(update_time_entry)
+                    .service(delete_time_entry)
+                    .service(get_timesheet)
+                    .service(get_project_time)
+                    .service(approve_timesheet)
+                    .service(list_cost_rates)
+                    .service(set_cost_rate)
+                    .service(delete_cost_rate)
+                    .service(get_budget)
+                    .service(list_assignments)
+                    .service(update_allocation)
+                    .service(get_capacity)
+                    .service(get_calendar)
+                    .service(update_weekend)
+                    .service(create_holiday)
+                    .service(delete_holiday)
+                    .service(import_holidays)
+                    .service(list_leave)
+                    .service(create_leave)
+                    .service(delete_leave)
+                    .service(list_articles)
+                    .service(create_article)
+                    .service(get_article)
+                    .service(update_article)
+                    .service(list_article_revisions)
+                    .service(restore_article_revision)
+                    .service(publish_article)
+                    .service(delete_article)
+                    .service(create_project),
             )
-            .service(create_project)
         // .service(
         //     web::scope("")
         //         .wrap(bearer_middleware)
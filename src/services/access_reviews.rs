@@ -0,0 +1,694 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    get, post,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Transaction};
+
+#[derive(Deserialize)]
+struct CreateCampaignBody {
+    campaign: String,
+}
+
+#[derive(Deserialize)]
+struct DecideItemBody {
+    /// "confirm" or "revoke"
+    decision: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct AccessReview {
+    review_id: i32,
+    campaign: String,
+    project_id: i32,
+    /// party_id of the project owner at the time of the snapshot
+    reviewer_id: i32,
+    status: String,
+    /// unix timestamp (seconds)
+    created_at: i64,
+    /// unix timestamp (seconds), 0 while open
+    completed_at: i64,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct AccessReviewItem {
+    item_id: i32,
+    review_id: i32,
+    party_id: i32,
+    grant_kind: String,
+    policy: String,
+    decision: String,
+    /// unix timestamp (seconds), 0 while pending
+    decided_at: i64,
+}
+
+#[derive(Serialize)]
+struct AccessReviewDetail {
+    #[serde(flatten)]
+    review: AccessReview,
+    items: Vec<AccessReviewItem>,
+}
+
+/// Grants an access review may revoke, the snapshot lists these only.
+const REVOCABLE_KINDS: [&str; 4] = ["assignment", "delegation", "team", "share"];
+
+/// How the access is granted, from the determining policy.
+fn grant_kind(policy: &str) -> &'static str {
+    match policy {
+        "ProjectLeadPolicy.Project" => "ownership",
+        "DeveloperPolicy" => "assignment",
+        _ if policy.starts_with("ProjectRolePolicy.") => "assignment",
+        _ if policy.starts_with("Delegation.") => "delegation",
+        _ => "role",
+    }
+}
+
+/// The grants of a party through a policy: the access of the assignee policies is granted
+/// by the assignment of the party, by the assignment of its teams, or both.
+fn grant_kinds(policy: &str, party_id: i32, resource: &ProjectResource) -> Vec<&'static str> {
+    match grant_kind(policy) {
+        "assignment" => {
+            let mut kinds = vec![];
+            if resource.assignees.contains(&party_id) {
+                kinds.push("assignment");
+            }
+            if resource.team_members.iter().any(|(_, member)| *member == party_id) {
+                kinds.push("team");
+            }
+            if kinds.is_empty() {
+                kinds.push("assignment");
+            }
+            kinds
+        }
+        kind => vec![kind],
+    }
+}
+
+async fn fetch_review(state: &AppState, review_id: i32) -> Result<AccessReview> {
+    let review = sqlx::query_as::<_, AccessReview>(
+        "SELECT review_id, campaign, project_id, reviewer_id, status, created_at, completed_at
+        FROM access_reviews
+        WHERE review_id = $1",
+    )
+    .bind(review_id)
+    .fetch_one(&state.db)
+    .await?;
+    Ok(review)
+}
+
+async fn fetch_items(state: &AppState, review_id: i32) -> Result<Vec<AccessReviewItem>> {
+    let items = sqlx::query_as::<_, AccessReviewItem>(
+        "SELECT item_id, review_id, party_id, grant_kind, policy, decision, decided_at
+        FROM access_review_items
+        WHERE review_id = $1
+        ORDER BY item_id",
+    )
+    .bind(review_id)
+    .fetch_all(&state.db)
+    .await?;
+    Ok(items)
+}
+
+/// Whoever may audit the project, the reviewer (its owner) included. Guests and anonymous
+/// principals never do, their ids are no party ids.
+async fn may_read(
+    state: &AppState,
+    token_claims: &TokenClaims,
+    context: &RequestContext,
+    review: &AccessReview,
+) -> Result<bool> {
+    if token_claims.is_guest() || token_claims.anonymous {
+        return Ok(false);
+    }
+    let resource = project_resource(&state.db, review.project_id).await?;
+    Ok(state
+        .permission
+        .is_authorized(token_claims, Action::AuditProject, &resource, context)?)
+}
+
+/// A grant of the project to review: the party, how the access is granted and by which policy.
+struct Grant {
+    party_id: i32,
+    grant_kind: &'static str,
+    policy: String,
+}
+
+/// Snapshots the parties allowed to view the project and the policies granting it, for the
+/// owner to review, and the guests the project is shared with, listed under the party that
+/// shared it. Ownership and roles are not granted by the project, they cannot be revoked by
+/// its owner and are left out.
+async fn snapshot(state: &AppState, resource: &ProjectResource) -> Result<Vec<Grant>> {
+    let now = Utc::now();
+    let mut grants = vec![];
+
+    let parties: Vec<(i32,)> = sqlx::query_as(
+        "SELECT DISTINCT party_role.party_id FROM party_role
+        JOIN parties ON parties.party_id = party_role.party_id
+        WHERE parties.tenant_id = $1
+        ORDER BY party_role.party_id",
+    )
+    .bind(resource.tenant)
+    .fetch_all(&state.db)
+    .await?;
+    for (party_id,) in parties {
+        let token_claims = TokenClaims {
+            id: party_id,
+            roles: active_roles(&state.db, party_id).await?,
+            tenant_id: resource.tenant,
+            ..Default::default()
+        };
+        let context = RequestContext::new(&token_claims, None, now);
+        let permission = delegations::permission_for(state, &token_claims).await?;
+        let authorization = permission.authorize(&token_claims, Action::ViewProject, resource, &context)?;
+        if !authorization.allowed {
+            continue;
+        }
+        for policy in &authorization.policy_ids {
+            for grant_kind in grant_kinds(policy, party_id, resource) {
+                if REVOCABLE_KINDS.contains(&grant_kind) {
+                    grants.push(Grant {
+                        party_id,
+                        grant_kind,
+                        policy: policy.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    let invites: Vec<(i32, i32)> = sqlx::query_as(
+        "SELECT invite_id, invited_by FROM guest_invites
+        WHERE project_id = $1 AND revoked = 0 AND valid_to > $2
+        ORDER BY invite_id",
+    )
+    .bind(resource.id)
+    .bind(now.timestamp())
+    .fetch_all(&state.db)
+    .await?;
+    for (invite_id, invited_by) in invites {
+        grants.push(Grant {
+            party_id: invited_by,
+            grant_kind: "share",
+            policy: format!("GuestInvite.{}", invite_id),
+        });
+    }
+    Ok(grants)
+}
+
+/// Writes the review of the project and its grants, the project owner reviews them.
+async fn save_review(
+    tx: &mut Transaction<'_, Any>,
+    campaign: &str,
+    resource: &ProjectResource,
+    grants: &[Grant],
+) -> Result<i32> {
+    let (review_id,): (i32,) = sqlx::query_as(
+        "INSERT INTO access_reviews (campaign, project_id, reviewer_id, created_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING review_id",
+    )
+    .bind(campaign)
+    .bind(resource.id)
+    .bind(resource.owner)
+    .bind(Utc::now().timestamp())
+    .fetch_one(&mut **tx)
+    .await?;
+
+    for grant in grants {
+        sqlx::query(
+            "INSERT INTO access_review_items (review_id, party_id, grant_kind, policy)
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(review_id)
+        .bind(grant.party_id)
+        .bind(grant.grant_kind)
+        .bind(&grant.policy)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(review_id)
+}
+
+/// Starts a campaign: one review per project the caller may audit, assigned to the project owner.
+#[post("/api/access-reviews")]
+async fn create_access_review_campaign(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    body: Json<CreateCampaignBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let campaign = body.into_inner().campaign.trim().to_string();
+            if campaign.is_empty() {
+                return Err(ProjectError::InvalidInput("a campaign name is required".to_string()));
+            }
+            if campaign.contains([',', '\n']) {
+                return Err(ProjectError::InvalidInput("the campaign name is exported as CSV".to_string()));
+            }
+
+            let projects: Vec<(i32,)> = sqlx::query_as("SELECT id FROM projects WHERE tenant_id = $1 ORDER BY id")
+                .bind(tk.tenant_id)
+                .fetch_all(&state.db)
+                .await?;
+            let mut resources = vec![];
+            for (project_id,) in projects {
+                let resource = project_resource(&state.db, project_id).await?;
+                if state
+                    .permission
+                    .is_authorized(&tk, Action::AuditProject, &resource, &context)?
+                {
+                    resources.push(resource);
+                }
+            }
+            // no partial campaign: every project needs an owner to review it
+            if let Some(resource) = resources.iter().find(|resource| resource.owner == 0) {
+                return Err(ProjectError::InvalidInput(format!("project {} has no owner to review it", resource.id)));
+            }
+
+            let mut snapshots = vec![];
+            for resource in resources {
+                let grants = snapshot(&state, &resource).await?;
+                snapshots.push((resource, grants));
+            }
+            // the campaign is written at once, or not at all
+            let mut tx = state.db.begin().await?;
+            let mut review_ids = vec![];
+            for (resource, grants) in &snapshots {
+                review_ids.push(save_review(&mut tx, &campaign, resource, grants).await?);
+            }
+            tx.commit().await?;
+
+            let mut reviews = vec![];
+            for ((resource, _), review_id) in snapshots.iter().zip(review_ids) {
+                let review = fetch_review(&state, review_id).await?;
+                let detail = format!("access review {} of campaign {}", review.review_id, campaign);
+                audit::record(&state.db, tk.id, "CreateAccessReview", &resource.uid(), &[], &detail).await?;
+                reviews.push(review);
+            }
+
+            let json = serde_json::to_string(&reviews)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[get("/api/access-reviews/{id}")]
+async fn get_access_review(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let review_id = path.into_inner().parse::<i32>()?;
+
+            let review = fetch_review(&state, review_id).await?;
+            if !may_read(&state, &tk, &context, &review).await? {
+                return Err(ProjectError::AuthFailed);
+            }
+            let items = fetch_items(&state, review_id).await?;
+
+            let json = serde_json::to_string(&AccessReviewDetail { review, items })?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Confirms or revokes a grant, the revocation is applied right away. The review is
+/// completed once every grant is decided.
+///
+/// Decided by the reviewer while it may audit the project, revocations also need the right
+/// to assign the project.
+#[post("/api/access-reviews/{id}/items/{item_id}")]
+async fn decide_access_review_item(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<DecideItemBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            if tk.is_guest() || tk.anonymous {
+                return Err(ProjectError::AuthFailed);
+            }
+            let (review_id, item_id) = path.into_inner();
+            let review_id = review_id.parse::<i32>()?;
+            let item_id = item_id.parse::<i32>()?;
+
+            let review = fetch_review(&state, review_id).await?;
+            if review.reviewer_id != tk.id {
+                return Err(ProjectError::AuthFailed);
+            }
+            let resource = project_resource(&state.db, review.project_id).await?;
+            if !state
+                .permission
+                .is_authorized(&tk, Action::AuditProject, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+            let item = fetch_items(&state, review_id)
+                .await?
+                .into_iter()
+                .find(|item| item.item_id == item_id)
+                .ok_or_else(|| ProjectError::InvalidInput(format!("unknown item {}", item_id)))?;
+            if item.decision != "pending" {
+                return Err(ProjectError::InvalidInput(format!("item {} is already {}", item_id, item.decision)));
+            }
+
+            let decision = match body.into_inner().decision.as_str() {
+                "confirm" => "confirmed",
+                "revoke" => {
+                    if !state
+                        .permission
+                        .is_authorized(&tk, Action::AssignPartyrole, &resource, &context)?
+                    {
+                        return Err(ProjectError::AuthFailed);
+                    }
+                    revoke(&state, &review, &item).await?;
+                    "revoked"
+                }
+                other => return Err(ProjectError::InvalidInput(format!("unknown decision {}", other))),
+            };
+            let now = Utc::now().timestamp();
+            sqlx::query("UPDATE access_review_items SET decision = $1, decided_at = $2 WHERE item_id = $3")
+                .bind(decision)
+                .bind(now)
+                .bind(item_id)
+                .execute(&state.db)
+                .await?;
+            sqlx::query(
+                "UPDATE access_reviews SET status = 'completed', completed_at = $1
+                WHERE review_id = $2 AND NOT EXISTS
+                (SELECT 1 FROM access_review_items WHERE review_id = $2 AND decision = 'pending')",
+            )
+            .bind(now)
+            .bind(review_id)
+            .execute(&state.db)
+            .await?;
+
+            let detail = format!(
+                "access review {}: {} {} of party {} ({})",
+                review_id, decision, item.grant_kind, item.party_id, item.policy
+            );
+            audit::record(&state.db, tk.id, "ReviewAccess", &resource.uid(), &[], &detail).await?;
+
+            let review = fetch_review(&state, review_id).await?;
+            let items = fetch_items(&state, review_id).await?;
+            let json = serde_json::to_string(&AccessReviewDetail { review, items })?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Removes the grant of the item. A team grant unassigns the teams of the party from the
+/// project, for all their members.
+async fn revoke(state: &AppState, review: &AccessReview, item: &AccessReviewItem) -> Result<()> {
+    match item.grant_kind.as_str() {
+        "assignment" => {
+            sqlx::query(
+                "DELETE FROM assignments WHERE project_id = $1
+                AND party_role_id IN (SELECT party_role_id FROM party_role WHERE party_id = $2)",
+            )
+            .bind(review.project_id)
+            .bind(item.party_id)
+            .execute(&state.db)
+            .await?;
+        }
+        "delegation" => {
+            let delegation_id = item
+                .policy
+                .trim_start_matches("Delegation.")
+                .parse::<i32>()?;
+            sqlx::query("UPDATE delegations SET revoked = 1 WHERE delegation_id = $1 AND project_id = $2")
+                .bind(delegation_id)
+                .bind(review.project_id)
+                .execute(&state.db)
+                .await?;
+        }
+        "team" => {
+            sqlx::query(
+                "DELETE FROM team_assignments WHERE project_id = $1
+                AND team_id IN (SELECT team_id FROM team_members WHERE party_id = $2)",
+            )
+            .bind(review.project_id)
+            .bind(item.party_id)
+            .execute(&state.db)
+            .await?;
+        }
+        "share" => {
+            let invite_id = item
+                .policy
+                .trim_start_matches("GuestInvite.")
+                .parse::<i32>()?;
+            sqlx::query("UPDATE guest_invites SET revoked = 1 WHERE invite_id = $1 AND project_id = $2")
+                .bind(invite_id)
+                .bind(review.project_id)
+                .execute(&state.db)
+                .await?;
+        }
+        kind => {
+            return Err(ProjectError::InvalidInput(format!(
+                "a {} grant cannot be revoked by an access review",
+                kind
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// CSV export of a completed review.
+#[get("/api/access-reviews/{id}/export")]
+async fn export_access_review(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let review_id = path.into_inner().parse::<i32>()?;
+
+            let review = fetch_review(&state, review_id).await?;
+            if !may_read(&state, &tk, &context, &review).await? {
+                return Err(ProjectError::AuthFailed);
+            }
+            if review.status != "completed" {
+                return Err(ProjectError::InvalidInput(format!("access review {} is not completed", review_id)));
+            }
+
+            let mut csv = "campaign,project_id,reviewer_id,party_id,grant_kind,policy,decision,decided_at\n".to_string();
+            for item in fetch_items(&state, review_id).await? {
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{},{},{}\n",
+                    review.campaign,
+                    review.project_id,
+                    review.reviewer_id,
+                    item.party_id,
+                    item.grant_kind,
+                    item.policy,
+                    item.decision,
+                    item.decided_at
+                ));
+            }
+            Ok(csv)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use dotenv::dotenv;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn access_review_revocations_are_applied() {
+        let app = create_test_app().await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/guests")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({
+                "label": "jane@partner.example",
+                "valid_to": chrono::Utc::now() + chrono::Duration::days(7),
+            }))
+            .to_request();
+        let invite: Value = test::call_and_read_body_json(&app, req).await;
+        let guest = test::TestRequest::get()
+            .uri("/api/projects/2")
+            .insert_header(("Authorization", format!("Bearer {}", invite["token"].as_str().unwrap())));
+
+        let review_req = |token_claims: TokenClaims, uri: String| {
+            test::TestRequest::get().uri(&uri).insert_header(bearer(token_claims)).to_request()
+        };
+        let decide_req = |token_claims: TokenClaims, item_id: &Value, decision: &str| {
+            test::TestRequest::post()
+                .uri(&format!("/api/access-reviews/2/items/{}", item_id))
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({ "decision": decision }))
+                .to_request()
+        };
+
+        // administrators audit through the Auditor role
+        let req = test::TestRequest::post()
+            .uri("/api/access-reviews")
+            .insert_header(bearer(admin()))
+            .set_json(serde_json::json!({ "campaign": "2026-Q4" }))
+            .to_request();
+        let reviews: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        let projects: Vec<&Value> = reviews.iter().map(|review| &review["project_id"]).collect();
+        assert_eq!(projects, vec![1, 2]);
+        assert_eq!(reviews[1]["reviewer_id"], 3);
+
+        let review: Value = test::call_and_read_body_json(&app, review_req(projectlead(), "/api/access-reviews/2".to_string())).await;
+        let grants: Vec<(i64, &str)> = review["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| (item["party_id"].as_i64().unwrap(), item["grant_kind"].as_str().unwrap()))
+            .collect();
+        // the owner does not review its own access, the guest is listed under the owner who shared it
+        assert_eq!(grants, vec![(4, "assignment"), (3, "share")]);
+        let assignment = &review["items"][0]["item_id"];
+        let share = &review["items"][1]["item_id"];
+
+        let resp = test::call_service(&app, decide_req(developer(), assignment, "revoke")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let review: Value = test::call_and_read_body_json(&app, decide_req(projectlead(), assignment, "revoke")).await;
+        assert_eq!(review["status"], "open");
+        let resp = test::call_service(&app, get_project_req(developer(), 2)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let resp = test::call_service(&app, review_req(admin(), "/api/access-reviews/2/export".to_string())).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let review: Value = test::call_and_read_body_json(&app, decide_req(projectlead(), share, "revoke")).await;
+        assert_eq!(review["status"], "completed");
+        let resp = test::call_service(&app, guest.to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let csv = test::call_and_read_body(&app, review_req(admin(), "/api/access-reviews/2/export".to_string())).await;
+        let lines: Vec<&str> = std::str::from_utf8(&csv).unwrap().lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("2026-Q4,2,3,4,assignment,DeveloperPolicy,revoked,"));
+        assert!(lines[2].starts_with(&format!("2026-Q4,2,3,3,share,GuestInvite.{},revoked,", invite["invite_id"])));
+    }
+
+    #[actix_web::test]
+    async fn access_review_reports_team_access_apart() {
+        let app = create_test_app().await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/teams")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "name": "platform" }))
+            .to_request();
+        let team: Value = test::call_and_read_body_json(&app, req).await;
+        let team_id = team["team_id"].as_i64().unwrap();
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/teams/{}/members", team_id))
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "party_id": 4 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = test::TestRequest::post()
+            .uri("/api/projects/1/teams")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "team_id": team_id }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::post()
+            .uri("/api/access-reviews")
+            .insert_header(bearer(admin()))
+            .set_json(serde_json::json!({ "campaign": "2026-Q4" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // anonymous principals have id 0, like a project without owner
+        let req = test::TestRequest::get().uri("/api/access-reviews/1").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::get()
+            .uri("/api/access-reviews/1")
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let review: Value = test::call_and_read_body_json(&app, req).await;
+        let grants: Vec<(i64, &str)> = review["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| (item["party_id"].as_i64().unwrap(), item["grant_kind"].as_str().unwrap()))
+            .collect();
+        assert_eq!(grants, vec![(4, "team")]);
+
+        // revoking the team grant unassigns the team
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/access-reviews/1/items/{}", review["items"][0]["item_id"]))
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "decision": "revoke" }))
+            .to_request();
+        let review: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(review["status"], "completed");
+        let resp = test::call_service(&app, get_project_req(developer(), 1)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn access_review_needs_a_project_owner() {
+        dotenv().ok();
+        sqlx::any::install_default_drivers();
+        let now = chrono::Utc::now().timestamp();
+
+        let app_data = Arc::new(create_app_data().await.unwrap());
+        sqlx::query("UPDATE party_role SET valid_to = $1 WHERE party_role_id = 3")
+            .bind(now - 3600)
+            .execute(&app_data.db)
+            .await
+            .unwrap();
+        let app = create_test_app_with(app_data).await;
+
+        let admin = TokenClaims {
+            id: 2,
+            roles: vec!["Administrator".to_string()],
+            ..Default::default()
+        };
+        let req = test::TestRequest::post()
+            .uri("/api/access-reviews")
+            .insert_header(bearer(admin))
+            .set_json(serde_json::json!({ "campaign": "2026-Q4" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
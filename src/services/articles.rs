@@ -1,158 +1,616 @@
-use crate::{services::TokenClaims, AppState};
+use std::sync::Arc;
+
+use crate::AppState;
 use actix_web::{
-    get, post,
-    web::{Data, Json, ReqData},
-    HttpResponse, Responder,
+    delete, get, post, put,
+    web::{self, Data, Json, ReqData},
 };
-use actix_web_httpauth::extractors::basic::BasicAuth;
-use argonautica::{Hasher, Verifier};
-use chrono::NaiveDateTime;
-use hmac::{Hmac, Mac};
-use jwt::SignWithKey;
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
-use sqlx::{self, FromRow};
+use sqlx::{self, Any, FromRow, Pool, Transaction};
 
 #[derive(Deserialize)]
-struct CreateUserBody {
-    username: String,
-    password: String,
+struct ArticleBody {
+    title: String,
+    /// Markdown
+    #[serde(default)]
+    content: String,
 }
 
-#[derive(Serialize, FromRow)]
-struct UserNoPassword {
+/// A page of the wiki of a project, a draft until published. Its readers see the published
+/// revision, its editors the current one.
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+struct Article {
     id: i32,
-    username: String
+    project_id: i32,
+    title: String,
+    /// Markdown of the current revision
+    content: String,
+    /// Number of the current revision
+    revision: i32,
+    /// party_id of the party who published the article
+    published_by: Option<i32>,
+    /// unix timestamp (seconds), none for a draft
+    published_on: Option<i64>,
+    /// Number of the revision readers see, none for a draft
+    published_revision: Option<i32>,
 }
 
-#[derive(Serialize, FromRow)]
-struct AuthUser {
-    id: i32,
-    username: String,
-    password: String,
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+struct Revision {
+    revision: i32,
+    title: String,
+    content: String,
+    /// Revision restored, none for an edit
+    restored_from: Option<i32>,
+    /// party_id of the editor
+    edited_by: i32,
+    /// unix timestamp (seconds)
+    edited_at: i64,
 }
 
-#[derive(Deserialize)]
-struct CreateArticleBody {
-    title: String, 
-    content: String,
+const ARTICLE_COLUMNS: &str = "id, project_id, title, content, revision, published_by, published_on, published_revision";
+
+const REVISION_COLUMNS: &str = "revision, title, content, restored_from, edited_by, edited_at";
+
+fn validate(body: &ArticleBody) -> Result<()> {
+    if body.title.trim().is_empty() {
+        return Err(ProjectError::InvalidInput("a title is required".to_string()));
+    }
+    Ok(())
 }
 
-#[derive(Serialize, FromRow)]
-struct Article {
-    id: i32,
-    title: String, 
-    content: String,
-    published_by: i32,
-    published_on: Option<NaiveDateTime>
-}
-
-#[post("/user")]
-async fn create_user(state: Data<AppState>, body: Json<CreateUserBody>) -> impl Responder {
-    let user = body.into_inner();
-    
-    let hash_secret = std::env::var("HASH_SECRET").expect("HASH_SECRET must be set!");
-    let mut hasher = Hasher::default();
-    let hash = hasher
-        .with_password(user.password)
-        .with_secret_key(hash_secret)
-        .hash()
-        .unwrap();
-    //let hash = "hash";
-
-    match sqlx::query_as::<_, UserNoPassword>(
-        "INSERT INTO users (username, password)
-        VALUES ($1, $2)
-        RETURNING id, username"
+async fn article(db: &Pool<Any>, project_id: i32, article_id: i32) -> Result<Article> {
+    let article = sqlx::query_as::<_, Article>(&format!(
+        "SELECT {} FROM articles WHERE id = $1 AND project_id = $2",
+        ARTICLE_COLUMNS
+    ))
+    .bind(article_id)
+    .bind(project_id)
+    .fetch_one(db)
+    .await?;
+    Ok(article)
+}
+
+/// The article of the project with its Cedar `Article` entity.
+async fn article_resource(db: &Pool<Any>, project_id: i32, article_id: i32) -> Result<(Article, ArticleResource)> {
+    let project = project_resource(db, project_id).await?;
+    let article = article(db, project_id, article_id).await?;
+    let resource = ArticleResource {
+        id: article.id,
+        project,
+        published: article.published_on.is_some(),
+    };
+    Ok((article, resource))
+}
+
+/// The article as its readers see it, the title and content of its published revision.
+async fn published(db: &Pool<Any>, article: Article) -> Result<Article> {
+    match article.published_revision {
+        Some(revision) if revision != article.revision => {
+            let published = sqlx::query_as::<_, Revision>(&format!(
+                "SELECT {} FROM article_revisions WHERE article_id = $1 AND revision = $2",
+                REVISION_COLUMNS
+            ))
+            .bind(article.id)
+            .bind(revision)
+            .fetch_one(db)
+            .await?;
+            Ok(Article {
+                title: published.title,
+                content: published.content,
+                revision,
+                ..article
+            })
+        }
+        _ => Ok(article),
+    }
+}
+
+/// Stores the title and content as the next revision of the article. The revision number is
+/// taken by updating the article first, concurrent edits wait for the transaction to end.
+async fn save_revision(
+    tx: &mut Transaction<'_, Any>,
+    article_id: i32,
+    title: &str,
+    content: &str,
+    restored_from: Option<i32>,
+    party_id: i32,
+) -> Result<Article> {
+    let article = sqlx::query_as::<_, Article>(&format!(
+        "UPDATE articles SET title = $1, content = $2, revision = revision + 1
+        WHERE id = $3
+        RETURNING {}",
+        ARTICLE_COLUMNS
+    ))
+    .bind(title)
+    .bind(content)
+    .bind(article_id)
+    .fetch_one(&mut **tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO article_revisions (article_id, revision, title, content, restored_from, edited_by, edited_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
     )
-    .bind(user.username)
-    .bind(hash)
-    .fetch_one(&state.db)
-    .await {
-        Ok(user) => HttpResponse::Ok().json(user),
-        Err(error) => HttpResponse::InternalServerError().json(format!("{:?}", error))
+    .bind(article.id)
+    .bind(article.revision)
+    .bind(title)
+    .bind(content)
+    .bind(restored_from)
+    .bind(party_id)
+    .bind(Utc::now().timestamp())
+    .execute(&mut **tx)
+    .await?;
+    Ok(article)
+}
+
+/// The articles of the project the caller may view: the published ones, the drafts too for their
+/// editors. Readers get the published revisions.
+#[get("/api/projects/{id}/articles")]
+async fn list_articles(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let project = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            let articles = sqlx::query_as::<_, Article>(&format!(
+                "SELECT {} FROM articles WHERE project_id = $1 ORDER BY title, id",
+                ARTICLE_COLUMNS
+            ))
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+            let mut visible = vec![];
+            for article in articles {
+                let resource = ArticleResource {
+                    id: article.id,
+                    project: project.clone(),
+                    published: article.published_on.is_some(),
+                };
+                if !permission.is_authorized(&tk, Action::ViewArticle, &resource, &context)? {
+                    continue;
+                }
+                if permission.is_authorized(&tk, Action::EditArticle, &resource, &context)? {
+                    visible.push(article);
+                } else {
+                    visible.push(published(&state.db, article).await?);
+                }
+            }
+
+            let json = serde_json::to_string(&visible)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Adds a draft page to the wiki of the project, its first revision.
+#[post("/api/projects/{id}/articles")]
+async fn create_article(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<ArticleBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+            validate(&body)?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::CreateArticle, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let mut tx = state.db.begin().await?;
+            let (article_id,): (i32,) = sqlx::query_as(
+                "INSERT INTO articles (project_id, title, content, revision, created_by)
+                VALUES ($1, $2, $3, 0, $4)
+                RETURNING id",
+            )
+            .bind(id)
+            .bind(body.title.trim())
+            .bind(&body.content)
+            .bind(tk.id)
+            .fetch_one(&mut *tx)
+            .await?;
+            let article = save_revision(&mut tx, article_id, body.title.trim(), &body.content, None, tk.id).await?;
+            tx.commit().await?;
+
+            let detail = format!("article {} {}", article.id, article.title);
+            audit::record(&state.db, tk.id, "CreateArticle", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&article)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[get("/api/projects/{id}/articles/{article_id}")]
+async fn get_article(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, article_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let article_id = article_id.parse::<i32>()?;
+
+            let (article, resource) = article_resource(&state.db, id, article_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewArticle, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            let article = if permission.is_authorized(&tk, Action::EditArticle, &resource, &context)? {
+                article
+            } else {
+                published(&state.db, article).await?
+            };
+
+            let json = serde_json::to_string(&article)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Saves the title and content as a new revision, the readers of a published article seeing it
+/// once published again.
+#[put("/api/projects/{id}/articles/{article_id}")]
+async fn update_article(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<ArticleBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, article_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let article_id = article_id.parse::<i32>()?;
+            let body = body.into_inner();
+            validate(&body)?;
+
+            let (article, resource) = article_resource(&state.db, id, article_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::EditArticle, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let mut tx = state.db.begin().await?;
+            let article = save_revision(&mut tx, article.id, body.title.trim(), &body.content, None, tk.id).await?;
+            tx.commit().await?;
+
+            let detail = format!("article {} revision {}", article.id, article.revision);
+            audit::record(&state.db, tk.id, "EditArticle", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&article)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
     }
+}
+
+/// Every revision of the article, the latest first, for its editors.
+#[get("/api/projects/{id}/articles/{article_id}/revisions")]
+async fn list_article_revisions(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, article_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let article_id = article_id.parse::<i32>()?;
 
+            let (_, resource) = article_resource(&state.db, id, article_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::EditArticle, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let revisions = sqlx::query_as::<_, Revision>(&format!(
+                "SELECT {} FROM article_revisions WHERE article_id = $1 ORDER BY revision DESC",
+                REVISION_COLUMNS
+            ))
+            .bind(article_id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let json = serde_json::to_string(&revisions)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
 }
 
-#[get("/auth")]
-async fn basic_auth(state: Data<AppState>, credentials: BasicAuth) -> impl Responder {
-    let jwt_secret: Hmac<Sha256> = Hmac::new_from_slice(
-        std::env::var("JWT_SECRET")
-        .expect("JWT_SECRET must be set!")
-        .as_bytes()
-    ).unwrap();
+/// Restores a past revision as a new one, the revisions in between kept.
+#[post("/api/projects/{id}/articles/{article_id}/revisions/{revision}/restore")]
+async fn restore_article_revision(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, article_id, revision) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let article_id = article_id.parse::<i32>()?;
+            let revision = revision.parse::<i32>()?;
 
-    let username = credentials.user_id();
-    let password = credentials.password();
+            let (article, resource) = article_resource(&state.db, id, article_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::EditArticle, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
 
-    match password {
-        None => HttpResponse::Unauthorized().json("Must provide username and password"),
-        Some(pass) => {
-            match sqlx::query_as::<_, AuthUser>(
-                "SELECT id, username, password FROM users WHERE username = $1",
+            let restored = sqlx::query_as::<_, Revision>(&format!(
+                "SELECT {} FROM article_revisions WHERE article_id = $1 AND revision = $2",
+                REVISION_COLUMNS
+            ))
+            .bind(article_id)
+            .bind(revision)
+            .fetch_optional(&state.db)
+            .await?
+            .ok_or_else(|| ProjectError::InvalidInput(format!("unknown revision {}", revision)))?;
+            let mut tx = state.db.begin().await?;
+            let article = save_revision(
+                &mut tx,
+                article.id,
+                &restored.title,
+                &restored.content,
+                Some(restored.revision),
+                tk.id,
             )
-            .bind(username.to_string())
+            .await?;
+            tx.commit().await?;
+
+            let detail = format!(
+                "article {} revision {} restored as {}",
+                article.id, restored.revision, article.revision
+            );
+            audit::record(&state.db, tk.id, "RestoreArticle", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&article)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Publishes the current revision of the article (`publish`), or withdraws the article back to a
+/// draft (`unpublish`).
+#[post("/api/projects/{id}/articles/{article_id}/{decision}")]
+async fn publish_article(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, article_id, decision) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let article_id = article_id.parse::<i32>()?;
+            let publish = match decision.as_str() {
+                "publish" => true,
+                "unpublish" => false,
+                _ => return Err(ProjectError::InvalidInput(format!("unknown decision {}", decision))),
+            };
+
+            let (article, resource) = article_resource(&state.db, id, article_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::PublishArticle, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            let current = if publish { Some(article.revision) } else { None };
+            if article.published_revision == current {
+                return Err(ProjectError::InvalidInput(format!(
+                    "article {} is already {}",
+                    article.id,
+                    if publish { "published" } else { "a draft" }
+                )));
+            }
+
+            let (published_by, published_on) = if publish {
+                (Some(tk.id), Some(Utc::now().timestamp()))
+            } else {
+                (None, None)
+            };
+            let article = sqlx::query_as::<_, Article>(&format!(
+                "UPDATE articles SET published_by = $1, published_on = $2, published_revision = $3
+                WHERE id = $4
+                RETURNING {}",
+                ARTICLE_COLUMNS
+            ))
+            .bind(published_by)
+            .bind(published_on)
+            .bind(current)
+            .bind(article_id)
             .fetch_one(&state.db)
-            .await
-            {
-                Ok(user) => {
-                    let hash_secret = std::env::var("HASH_SECRET").expect("HASH_SECRET must be set!");
-                    let mut verifier = Verifier::default();
-                    let is_valid = verifier
-                        .with_hash(user.password)
-                        .with_password(pass)
-                        .with_secret_key(hash_secret)
-                        .verify()
-                        .unwrap();                    
-                    //let is_valid = true;
-
-                    if is_valid {
-                        let claims = TokenClaims { id: user.id, roles: vec![] };
-                        let token_str = claims.sign_with_key(&jwt_secret).unwrap();
-                        HttpResponse::Ok().json(token_str)
-                    } else {
-                        HttpResponse::Unauthorized().json("Incorrect username or password")
-                    }
-                },
-                Err(error) => HttpResponse::InternalServerError().json(format!("{:?}", error))
+            .await?;
+
+            let action = if publish { "PublishArticle" } else { "UnpublishArticle" };
+            let detail = format!("article {} revision {}", article.id, article.revision);
+            audit::record(&state.db, tk.id, action, &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&article)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
 
+/// Deletes the article with its revisions.
+#[delete("/api/projects/{id}/articles/{article_id}")]
+async fn delete_article(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, article_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let article_id = article_id.parse::<i32>()?;
+
+            let (article, resource) = article_resource(&state.db, id, article_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::DeleteArticle, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
             }
+
+            let mut tx = state.db.begin().await?;
+            sqlx::query("DELETE FROM article_revisions WHERE article_id = $1")
+                .bind(article_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM articles WHERE id = $1")
+                .bind(article_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            let detail = format!("article {} {}", article.id, article.title);
+            audit::record(&state.db, tk.id, "DeleteArticle", &resource.project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&article)?;
+            Ok(json)
         }
+        _ => Err(ProjectError::AuthFailed),
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn wiki_articles_are_revised_published_and_restored() {
+        let app = create_test_app().await;
+
+        let article_req = |token_claims: TokenClaims, content: &str| {
+            test::TestRequest::put()
+                .uri("/api/projects/2/articles/1")
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({ "title": "Onboarding", "content": content }))
+                .to_request()
+        };
+        let publish_req = |token_claims: TokenClaims| {
+            test::TestRequest::post()
+                .uri("/api/projects/2/articles/1/publish")
+                .insert_header(bearer(token_claims))
+                .to_request()
+        };
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/articles")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "title": "Onboarding", "content": "# Welcome" }))
+            .to_request();
+        let article: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(article["id"], 1);
+        assert_eq!(article["revision"], 1);
+        assert_eq!(article["published_on"], Value::Null);
+
+        // the developer contributes to project 2
+        let article: Value = test::call_and_read_body_json(&app, article_req(developer(), "# Welcome!\n\nSetup")).await;
+        assert_eq!(article["revision"], 2);
+        let resp = test::call_service(&app, publish_req(developer())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let article: Value = test::call_and_read_body_json(&app, publish_req(projectlead())).await;
+        assert_eq!(article["published_by"], 3);
+        let resp = test::call_service(&app, publish_req(projectlead())).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/articles/1/revisions/1/restore")
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let article: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(article["revision"], 3);
+        assert_eq!(article["content"], "# Welcome");
+        assert!(article["published_on"].is_i64());
+        assert_eq!(article["published_revision"], 2);
 
-// #[post("/article")]
-// async fn create_article(state: Data<AppState>, req_user: Option<ReqData<TokenClaims>>, body: Json<CreateArticleBody>) -> impl Responder {
-//     match req_user {
-//         Some(user) => {
-//             let article: CreateArticleBody = body.into_inner();
-
-//             // match sqlx::query_as::<_, Article>(
-//             //     "INSERT INTO articles (title, content, published_by)
-//             //     VALUES ($1, $2, $3)
-//             //     RETURNING id, title, content, published_by, published_on"
-//             // )
-//             // .bind(article.title)
-//             // .bind(article.content)
-//             // .bind(user.id)
-//             // .fetch_one(&state.db)
-//             // .await 
-//             // { 
-//             //     Ok(article) => HttpResponse::Ok().json(article),
-//             //     Err(error) => HttpResponse::InternalServerError().json(format!("{:?}", error)),
-//             // }
-//             Ok(Article {
-//                 id: 1,
-//                 title: "title".to_string(),
-//                 content: "content".to_string(),
-//                 published_by: 1,
-//                 published_on: Some(NaiveDateTime::default())
-
-//             })
-          
-//         },
-//         _ => HttpResponse::Unauthorized().json("Unable to verify identity"),
-//     }
-// }
+        // readers see the published revision until the restored one is published
+        let req = test::TestRequest::put()
+            .uri("/api/projects/2")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({
+                "name": "my other project",
+                "description": "that project",
+                "status": "active",
+                "public": true,
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let anonymous_req = || test::TestRequest::get().uri("/api/projects/2/articles/1").to_request();
+        let article: Value = test::call_and_read_body_json(&app, anonymous_req()).await;
+        assert_eq!(article["revision"], 2);
+        assert_eq!(article["content"], "# Welcome!\n\nSetup");
+        let req = test::TestRequest::get()
+            .uri("/api/projects/2/articles/1")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let article: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(article["revision"], 3);
+
+        let article: Value = test::call_and_read_body_json(&app, publish_req(projectlead())).await;
+        assert_eq!(article["published_revision"], 3);
+        let article: Value = test::call_and_read_body_json(&app, anonymous_req()).await;
+        assert_eq!(article["content"], "# Welcome");
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects/2/articles/1/revisions")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let revisions: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(revisions.len(), 3);
+        assert_eq!(revisions[0]["restored_from"], 1);
+        assert_eq!(revisions[1]["edited_by"], 4);
+
+        let resp = test::call_service(&app, update_project_req(projectlead(), 2, "archived")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = test::call_service(&app, article_req(projectlead(), "# Archived")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    get,
+    web::{self, Data, ReqData},
+};
+
+use crate::services::*;
+
+use super::projects::{project_resource, ProjectError, Result};
+
+use cedar_policy::EntityUid;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct AuditEntry {
+    audit_id: i32,
+    party_id: i32,
+    action: String,
+    resource: String,
+    policies: String,
+    detail: String,
+    logged_at: i64,
+}
+
+/// Appends an entry to the audit trail.
+pub(crate) async fn record(
+    db: &Pool<Any>,
+    party_id: i32,
+    action: &str,
+    resource: &EntityUid,
+    policies: &[String],
+    detail: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO audit_log (party_id, action, resource, policies, detail, logged_at)
+        VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(party_id)
+    .bind(action)
+    .bind(resource.to_string())
+    .bind(policies.join(","))
+    .bind(detail)
+    .bind(Utc::now().timestamp())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Raises an alert for the on-call team, e.g. on break-glass accesses. Alerts are logged
+/// under the `alert` target, for the log pipeline to route them to the on-call sink.
+pub(crate) fn alert(party_id: i32, action: &str, resource: &EntityUid, policies: &[String], detail: &str) {
+    log::warn!(
+        target: "alert",
+        "party {} {} {} (policies: {}) {}",
+        party_id,
+        action,
+        resource,
+        policies.join(","),
+        detail
+    );
+}
+
+/// Records the accesses granted by a policy annotated with `@audit` (delegations, ...),
+/// and raises an alert when the policy is also annotated with `@alert` (break-glass).
+pub(crate) async fn record_authorization<R: Resource>(
+    db: &Pool<Any>,
+    token_claims: &TokenClaims,
+    action: Action,
+    resource: &R,
+    authorization: &Authorization,
+) -> Result<()> {
+    if authorization.allowed && authorization.audit {
+        record(
+            db,
+            token_claims.id,
+            action.name(),
+            &resource.uid(),
+            &authorization.policy_ids,
+            "",
+        )
+        .await?;
+    }
+    if authorization.allowed && authorization.alert {
+        alert(
+            token_claims.id,
+            action.name(),
+            &resource.uid(),
+            &authorization.policy_ids,
+            "",
+        );
+    }
+    Ok(())
+}
+
+#[get("/api/projects/{id}/audit")]
+async fn list_audit_entries(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            if !state
+                .permission
+                .is_authorized(&tk, Action::AuditProject, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let entries = sqlx::query_as::<_, AuditEntry>(
+                "SELECT audit_id, party_id, action, resource, policies, detail, logged_at FROM audit_log
+                WHERE resource = $1
+                ORDER BY audit_id",
+            )
+            .bind(resource.uid().to_string())
+            .fetch_all(&state.db)
+            .await?;
+
+            let json = serde_json::to_string(&entries)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    post,
+    web::{Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::projects::{ProjectError, Result};
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Lifetime of a break-glass token, the elevation is not renewable.
+const BREAK_GLASS_TTL_MINUTES: i64 = 30;
+
+#[derive(Deserialize)]
+struct BreakGlassBody {
+    justification: String,
+}
+
+#[derive(Serialize)]
+struct BreakGlassToken {
+    token: String,
+    /// unix timestamp (seconds)
+    expires_at: i64,
+}
+
+/// Issues a short-lived token honored by `BreakGlassPolicy`, for emergency access during incidents.
+#[post("/api/break-glass")]
+async fn request_break_glass(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    body: Json<BreakGlassBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let justification = body.into_inner().justification.trim().to_string();
+            if justification.is_empty() {
+                return Err(ProjectError::InvalidInput("a justification is required".to_string()));
+            }
+
+            // an elevation cannot be extended with its own token
+            let resource = GroupResource::all_projects();
+            if tk.break_glass
+                || !state
+                    .permission
+                    .is_authorized(&tk, Action::RequestBreakGlass, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let expires_at = (Utc::now() + Duration::minutes(BREAK_GLASS_TTL_MINUTES)).timestamp();
+            let token = TokenService::generate_token(TokenClaims {
+                break_glass: true,
+                exp: Some(expires_at),
+                ..tk.clone()
+            })?;
+
+            let action = Action::RequestBreakGlass.name();
+            let policies = ["BreakGlassPolicy.Request".to_string()];
+            audit::record(&state.db, tk.id, action, &resource.uid(), &policies, &justification).await?;
+            audit::alert(tk.id, action, &resource.uid(), &policies, &justification);
+
+            let json = serde_json::to_string(&BreakGlassToken { token, expires_at })?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn admin_break_glass_is_justified_and_audited() {
+        let app = create_test_app().await;
+
+        let projectlead = TokenClaims {
+            id: 3,
+            roles: vec!["ProjectLead".to_string()],
+            ..Default::default()
+        };
+        let break_glass_req = |justification: &str| {
+            test::TestRequest::post()
+                .uri("/api/break-glass")
+                .insert_header(bearer(admin()))
+                .set_json(serde_json::json!({ "justification": justification }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, get_project_req(admin(), 1)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let resp = test::call_service(&app, break_glass_req("  ")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let elevation: Value = test::call_and_read_body_json(&app, break_glass_req("incident 42")).await;
+        let token = format!("Bearer {}", elevation["token"].as_str().unwrap());
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1")
+            .insert_header(("Authorization", token.clone()))
+            .to_request();
+        let project: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(project["budget"], 0.0);
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects")
+            .insert_header(("Authorization", token))
+            .to_request();
+        let projects: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(projects.len(), 2);
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1/audit")
+            .insert_header(bearer(projectlead))
+            .to_request();
+        let entries: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        let actions: Vec<&str> = entries.iter().map(|e| e["action"].as_str().unwrap()).collect();
+        assert_eq!(actions, vec!["ViewProject", "ViewProject"]);
+        assert_eq!(entries[0]["policies"], "BreakGlassPolicy");
+    }
+
+    #[actix_web::test]
+    async fn expired_break_glass_token_is_rejected() {
+        let app = create_test_app().await;
+
+        let token_claims = TokenClaims {
+            id: 2,
+            roles: vec!["Administrator".to_string()],
+            break_glass: true,
+            exp: Some(chrono::Utc::now().timestamp() - 1),
+            ..Default::default()
+        };
+        let resp = test::call_service(&app, get_project_req(token_claims, 1)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
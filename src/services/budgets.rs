@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+use super::workflows::workflow;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+/// Working hours in a day of estimate.
+const HOURS_PER_DAY: f64 = 8.0;
+
+#[derive(Deserialize)]
+struct CostRateBody {
+    /// Name of the role type, for a role type rate
+    #[serde(default)]
+    role_type: Option<String>,
+    /// party_id of the party, for a party rate
+    #[serde(default)]
+    party_id: Option<i32>,
+    hourly_rate: f64,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone, PartialEq)]
+struct CostRate {
+    rate_id: i32,
+    role_type: Option<String>,
+    party_id: Option<i32>,
+    hourly_rate: f64,
+}
+
+#[derive(FromRow)]
+struct TaskCost {
+    task_id: i32,
+    status: String,
+    /// days
+    duration: i32,
+    /// party_id of the assignee
+    assignee: Option<i32>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct BudgetReport {
+    budget: f64,
+    budget_currency: String,
+    /// Cost of the estimates of the tasks, at the rates of their assignees
+    planned: f64,
+    /// Cost of the time logged
+    actual: f64,
+    /// Actual cost and cost of the estimates left of the open tasks
+    forecast: f64,
+    over_budget: bool,
+    /// Hours planned or logged by parties without rate, or on unassigned tasks
+    unpriced_hours: f64,
+}
+
+const COST_RATE_COLUMNS: &str = "cost_rates.rate_id, role_type.name AS role_type, cost_rates.party_id, cost_rates.hourly_rate";
+
+async fn cost_rates(db: &Pool<Any>, tenant_id: i32) -> Result<Vec<CostRate>> {
+    let rates = sqlx::query_as::<_, CostRate>(&format!(
+        "SELECT {} FROM cost_rates
+        LEFT JOIN role_type ON role_type.role_type_id = cost_rates.role_type_id
+        WHERE cost_rates.tenant_id = $1
+        ORDER BY cost_rates.rate_id",
+        COST_RATE_COLUMNS
+    ))
+    .bind(tenant_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rates)
+}
+
+/// Hourly rate of a party: its own rate, the highest rate of its role types otherwise.
+fn party_rate(rates: &[CostRate], party_id: i32, role_types: &[String]) -> Option<f64> {
+    rates
+        .iter()
+        .find(|rate| rate.party_id == Some(party_id))
+        .or_else(|| {
+            rates
+                .iter()
+                .filter(|rate| rate.role_type.as_ref().is_some_and(|role_type| role_types.contains(role_type)))
+                .max_by(|a, b| a.hourly_rate.total_cmp(&b.hourly_rate))
+        })
+        .map(|rate| rate.hourly_rate)
+}
+
+/// Hourly rates of the parties, from the role types they hold now.
+async fn party_rates(db: &Pool<Any>, rates: &[CostRate], party_ids: &[i32]) -> Result<HashMap<i32, f64>> {
+    let now = Utc::now().timestamp();
+    let mut party_rates = HashMap::new();
+    for party_id in party_ids {
+        let role_types: Vec<(String,)> = sqlx::query_as(
+            "SELECT role_type.name FROM party_role
+            JOIN role_type ON role_type.role_type_id = party_role.role_type_id
+            WHERE party_role.party_id = $1
+            AND (party_role.valid_from IS NULL OR party_role.valid_from <= $2)
+            AND (party_role.valid_to IS NULL OR party_role.valid_to > $2)",
+        )
+        .bind(party_id)
+        .bind(now)
+        .fetch_all(db)
+        .await?;
+        let role_types: Vec<String> = role_types.into_iter().map(|(name,)| name).collect();
+        if let Some(rate) = party_rate(rates, *party_id, &role_types) {
+            party_rates.insert(*party_id, rate);
+        }
+    }
+    Ok(party_rates)
+}
+
+/// Planned, actual and forecast spend of the tasks and the (party_id, task_id, minutes) entries.
+fn spend(
+    tasks: &[TaskCost],
+    entries: &[(i32, Option<i32>, i32)],
+    rates: &HashMap<i32, f64>,
+    done_state: &str,
+) -> (f64, f64, f64, f64) {
+    let (mut planned, mut actual, mut remaining, mut unpriced_hours) = (0.0, 0.0, 0.0, 0.0);
+    for (party_id, _, minutes) in entries {
+        let hours = *minutes as f64 / 60.0;
+        match rates.get(party_id) {
+            Some(rate) => actual += hours * rate,
+            None => unpriced_hours += hours,
+        }
+    }
+    for task in tasks {
+        let hours = task.duration as f64 * HOURS_PER_DAY;
+        let logged: f64 = entries
+            .iter()
+            .filter(|(_, task_id, _)| *task_id == Some(task.task_id))
+            .map(|(_, _, minutes)| *minutes as f64 / 60.0)
+            .sum();
+        let left = if task.status == done_state { 0.0 } else { (hours - logged).max(0.0) };
+        match task.assignee.and_then(|assignee| rates.get(&assignee)) {
+            Some(rate) => {
+                planned += hours * rate;
+                remaining += left * rate;
+            }
+            None => unpriced_hours += hours,
+        }
+    }
+    (planned, actual, actual + remaining, unpriced_hours)
+}
+
+#[get("/api/cost-rates")]
+async fn list_cost_rates(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageCostRates, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let json = serde_json::to_string(&cost_rates(&state.db, tk.tenant_id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Sets the hourly rate of a role type or of a party of the organization of the caller.
+#[post("/api/cost-rates")]
+async fn set_cost_rate(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    body: Json<CostRateBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let body = body.into_inner();
+            if !body.hourly_rate.is_finite() || body.hourly_rate < 0.0 {
+                return Err(ProjectError::InvalidInput("an hourly rate is positive".to_string()));
+            }
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageCostRates, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let (role_type_id, party_id, subject) = match (&body.role_type, body.party_id) {
+                (Some(role_type), None) => {
+                    let role_type_id: Option<(i32,)> =
+                        sqlx::query_as("SELECT role_type_id FROM role_type WHERE name = $1")
+                            .bind(role_type)
+                            .fetch_optional(&state.db)
+                            .await?;
+                    match role_type_id {
+                        Some((role_type_id,)) => (Some(role_type_id), None, format!("role type {}", role_type)),
+                        None => return Err(ProjectError::InvalidInput(format!("unknown role type {}", role_type))),
+                    }
+                }
+                (None, Some(party_id)) => {
+                    if party_tenant(&state.db, party_id).await? != Some(tk.tenant_id) {
+                        return Err(ProjectError::InvalidInput(format!("unknown party {}", party_id)));
+                    }
+                    (None, Some(party_id), format!("party {}", party_id))
+                }
+                _ => {
+                    return Err(ProjectError::InvalidInput(
+                        "a rate is for a role type or for a party".to_string(),
+                    ))
+                }
+            };
+
+            sqlx::query(
+                "DELETE FROM cost_rates
+                WHERE tenant_id = $1 AND role_type_id IS NOT DISTINCT FROM $2 AND party_id IS NOT DISTINCT FROM $3",
+            )
+            .bind(tk.tenant_id)
+            .bind(role_type_id)
+            .bind(party_id)
+            .execute(&state.db)
+            .await?;
+            let (rate_id,): (i32,) = sqlx::query_as(
+                "INSERT INTO cost_rates (tenant_id, role_type_id, party_id, hourly_rate, created_by)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING rate_id",
+            )
+            .bind(tk.tenant_id)
+            .bind(role_type_id)
+            .bind(party_id)
+            .bind(body.hourly_rate)
+            .bind(tk.id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!("cost rate {} of {}: {}", rate_id, subject, body.hourly_rate);
+            audit::record(&state.db, tk.id, "SetCostRate", &resource.uid(), &[], &detail).await?;
+
+            let rate = cost_rates(&state.db, tk.tenant_id)
+                .await?
+                .into_iter()
+                .find(|rate| rate.rate_id == rate_id)
+                .ok_or(ProjectError::Unknown)?;
+            let json = serde_json::to_string(&rate)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/cost-rates/{rate_id}")]
+async fn delete_cost_rate(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let rate_id = path.into_inner().parse::<i32>()?;
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageCostRates, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let rate = cost_rates(&state.db, tk.tenant_id)
+                .await?
+                .into_iter()
+                .find(|rate| rate.rate_id == rate_id)
+                .ok_or(ProjectError::Sqlx(sqlx::Error::RowNotFound))?;
+            sqlx::query("DELETE FROM cost_rates WHERE rate_id = $1")
+                .bind(rate_id)
+                .execute(&state.db)
+                .await?;
+
+            let detail = format!("cost rate {}", rate_id);
+            audit::record(&state.db, tk.id, "DeleteCostRate", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&rate)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Planned, actual and forecast spend of the project against its budget, for its owner.
+///
+/// Costs are hours at the rate of the party: the time logged for the actual spend, the estimates
+/// of the tasks for the planned spend and, for the forecast, what is left of the estimates of the
+/// open tasks once the time logged on them is deducted.
+#[get("/api/projects/{id}/budget")]
+async fn get_budget(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewBudget, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let (budget, budget_currency): (f64, String) =
+                sqlx::query_as("SELECT budget, budget_currency FROM projects WHERE id = $1")
+                    .bind(id)
+                    .fetch_one(&state.db)
+                    .await?;
+            let tasks = sqlx::query_as::<_, TaskCost>(
+                "SELECT tasks.task_id, tasks.status, tasks.duration, party_role.party_id AS assignee FROM tasks
+                LEFT JOIN party_role ON party_role.party_role_id = tasks.assignee
+                WHERE tasks.project_id = $1 AND tasks.deleted_at IS NULL
+                ORDER BY tasks.task_id",
+            )
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+            let entries: Vec<(i32, Option<i32>, i32)> =
+                sqlx::query_as("SELECT party_id, task_id, minutes FROM time_entries WHERE project_id = $1")
+                    .bind(id)
+                    .fetch_all(&state.db)
+                    .await?;
+
+            let mut party_ids: Vec<i32> = tasks
+                .iter()
+                .filter_map(|task| task.assignee)
+                .chain(entries.iter().map(|(party_id, _, _)| *party_id))
+                .collect();
+            party_ids.sort();
+            party_ids.dedup();
+            let rates = cost_rates(&state.db, resource.tenant).await?;
+            let rates = party_rates(&state.db, &rates, &party_ids).await?;
+            let workflow = workflow(&state.db, id).await?;
+            let done_state = workflow.states.last().cloned().unwrap_or_default();
+
+            let (planned, actual, forecast, unpriced_hours) = spend(&tasks, &entries, &rates, &done_state);
+            let report = BudgetReport {
+                budget,
+                budget_currency,
+                planned,
+                actual,
+                forecast,
+                over_budget: forecast > budget,
+                unpriced_hours,
+            };
+
+            let json = serde_json::to_string(&report)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    fn rate(party_id: Option<i32>, role_type: Option<&str>, hourly_rate: f64) -> CostRate {
+        CostRate {
+            rate_id: 0,
+            role_type: role_type.map(str::to_string),
+            party_id,
+            hourly_rate,
+        }
+    }
+
+    #[actix_web::test]
+    async fn party_rates_come_first() {
+        let rates = vec![
+            rate(None, Some("Developer"), 50.0),
+            rate(None, Some("ProjectLead"), 80.0),
+            rate(Some(4), None, 60.0),
+        ];
+        let roles = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<String>>();
+        assert_eq!(party_rate(&rates, 4, &roles(&["Developer"])), Some(60.0));
+        assert_eq!(party_rate(&rates, 5, &roles(&["Developer", "ProjectLead"])), Some(80.0));
+        assert_eq!(party_rate(&rates, 6, &roles(&["Auditor"])), None);
+    }
+
+    #[actix_web::test]
+    async fn forecast_adds_the_estimates_left() {
+        let task = |task_id: i32, status: &str, duration: i32, assignee: Option<i32>| TaskCost {
+            task_id,
+            status: status.to_string(),
+            duration,
+            assignee,
+        };
+        // 1: 1 day done, 2: 2 days open with 4 hours logged, 3: unassigned
+        let tasks = vec![task(1, "done", 1, Some(4)), task(2, "todo", 2, Some(4)), task(3, "todo", 1, None)];
+        let entries = vec![(4, Some(1), 600), (4, Some(2), 240), (5, None, 60)];
+        let rates = HashMap::from([(4, 10.0)]);
+
+        let (planned, actual, forecast, unpriced_hours) = spend(&tasks, &entries, &rates, "done");
+        assert_eq!(planned, 240.0);
+        assert_eq!(actual, 140.0);
+        assert_eq!(forecast, 140.0 + 120.0);
+        assert_eq!(unpriced_hours, 1.0 + 8.0);
+    }
+
+    #[actix_web::test]
+    async fn budget_report_prices_the_time_logged() {
+        let app = create_test_app().await;
+
+        let rate_req = |token_claims: TokenClaims, rate: Value| {
+            test::TestRequest::post()
+                .uri("/api/cost-rates")
+                .insert_header(bearer(token_claims))
+                .set_json(rate)
+                .to_request()
+        };
+        let budget_req = |token_claims: TokenClaims| {
+            test::TestRequest::get()
+                .uri("/api/projects/2/budget")
+                .insert_header(bearer(token_claims))
+                .to_request()
+        };
+
+        let developer_rate = serde_json::json!({ "role_type": "Developer", "hourly_rate": 50.0 });
+        let resp = test::call_service(&app, rate_req(projectlead(), developer_rate.clone())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = test::call_service(&app, rate_req(admin(), developer_rate)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(&app, update_project_req(projectlead(), 2, "active")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/time-entries")
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "date": "2030-01-09", "minutes": 120 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(&app, budget_req(developer())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let report: Value = test::call_and_read_body_json(&app, budget_req(projectlead())).await;
+        assert_eq!(report["budget"], 1000.0);
+        assert_eq!(report["actual"], 100.0);
+        assert_eq!(report["over_budget"], false);
+
+        // the party rate comes before the role type rate
+        let party_rate = serde_json::json!({ "party_id": 4, "hourly_rate": 600.0 });
+        let resp = test::call_service(&app, rate_req(admin(), party_rate)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let report: Value = test::call_and_read_body_json(&app, budget_req(projectlead())).await;
+        assert_eq!(report["actual"], 1200.0);
+        assert_eq!(report["over_budget"], true);
+    }
+}
@@ -0,0 +1,788 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post, put,
+    web::{self, Data, Json, Query, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::party_roles::party_tenant;
+use super::projects::{ProjectError, Result};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+/// Longest holiday or leave, in days.
+const MAX_DAYS_OFF: i64 = 366;
+
+#[derive(Deserialize)]
+struct CalendarQuery {
+    #[serde(default)]
+    from: Option<NaiveDate>,
+    #[serde(default)]
+    to: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+struct WeekendBody {
+    /// Days of the week off, e.g. `["Sat", "Sun"]`
+    weekend: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct HolidayBody {
+    date: NaiveDate,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct LeaveBody {
+    /// First day
+    from: NaiveDate,
+    /// Last day
+    to: NaiveDate,
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+struct Holiday {
+    holiday_id: i32,
+    /// unix timestamp (seconds) of the day, midnight UTC
+    date: i64,
+    name: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct Calendar {
+    weekend: Vec<String>,
+    holidays: Vec<Holiday>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+struct Leave {
+    leave_id: i32,
+    party_id: i32,
+    /// unix timestamp (seconds) of the first day, midnight UTC
+    from_date: i64,
+    /// unix timestamp (seconds) of the last day, midnight UTC
+    to_date: i64,
+    reason: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct HolidayImport {
+    imported: Vec<Holiday>,
+    /// Days already holidays of the organization
+    skipped: usize,
+}
+
+/// Working days of an organization, less the leave of a party when the calendar is for one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct WorkingCalendar {
+    weekend: Vec<Weekday>,
+    holidays: HashSet<NaiveDate>,
+    /// (first day, last day) of the leave of the party
+    leave: Vec<(NaiveDate, NaiveDate)>,
+}
+
+impl WorkingCalendar {
+    /// The calendar of the party with the leave.
+    pub fn with_leave(mut self, leave: Vec<(NaiveDate, NaiveDate)>) -> Self {
+        self.leave = leave;
+        self
+    }
+
+    pub fn leave(&self) -> &[(NaiveDate, NaiveDate)] {
+        &self.leave
+    }
+
+    pub fn on_leave(&self, day: NaiveDate) -> bool {
+        self.leave.iter().any(|(from, to)| *from <= day && day <= *to)
+    }
+
+    pub fn is_working_day(&self, day: NaiveDate) -> bool {
+        !self.weekend.contains(&day.weekday()) && !self.holidays.contains(&day) && !self.on_leave(day)
+    }
+
+    /// The working day with `n` working days before it from `start` on.
+    pub fn nth_working_day(&self, start: NaiveDate, n: i32) -> NaiveDate {
+        let mut day = start;
+        let mut left = n;
+        loop {
+            if self.is_working_day(day) {
+                if left == 0 {
+                    return day;
+                }
+                left -= 1;
+            }
+            day += Duration::days(1);
+        }
+    }
+
+    /// Working days of `from` ..= `to`.
+    pub fn working_days(&self, from: NaiveDate, to: NaiveDate) -> i64 {
+        from.iter_days()
+            .take_while(|day| *day <= to)
+            .filter(|day| self.is_working_day(*day))
+            .count() as i64
+    }
+}
+
+fn date_of(timestamp: i64) -> NaiveDate {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default().date_naive()
+}
+
+/// Unix timestamp (seconds) of midnight UTC of the day.
+fn day_timestamp(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()
+}
+
+/// Days of the week of `Sat,Sun`, a working day left in the week.
+fn parse_weekend(weekend: &[&str]) -> Result<Vec<Weekday>> {
+    let mut days: Vec<Weekday> = vec![];
+    for day in weekend.iter().map(|day| day.trim()).filter(|day| !day.is_empty()) {
+        let day = day
+            .parse::<Weekday>()
+            .map_err(|_| ProjectError::InvalidInput(format!("unknown day of the week {}", day)))?;
+        if !days.contains(&day) {
+            days.push(day);
+        }
+    }
+    if days.len() == 7 {
+        return Err(ProjectError::InvalidInput("a week has a working day".to_string()));
+    }
+    days.sort_by_key(|day| day.num_days_from_monday());
+    Ok(days)
+}
+
+/// Holidays of an iCalendar file: every day of its events, with their summary. Recurring
+/// events are not expanded, they are rejected.
+fn parse_ical(ical: &str) -> Result<Vec<(NaiveDate, String)>> {
+    // folded lines go on with a space or a tab
+    let mut lines: Vec<String> = vec![];
+    for line in ical.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(folded), Some(last)) => last.push_str(folded),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let date = |value: &str| {
+        value
+            .get(..8)
+            .and_then(|day| NaiveDate::parse_from_str(day, "%Y%m%d").ok())
+            .ok_or_else(|| ProjectError::InvalidInput(format!("invalid iCalendar date {}", value)))
+    };
+
+    let mut days: Vec<(NaiveDate, String)> = vec![];
+    let mut event: Option<(Option<NaiveDate>, Option<NaiveDate>, String)> = None;
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // parameters such as `;VALUE=DATE` follow the property name
+        let property = name.split(';').next().unwrap_or_default().to_ascii_uppercase();
+        if property == "BEGIN" && value.eq_ignore_ascii_case("VEVENT") {
+            event = Some((None, None, String::new()));
+            continue;
+        }
+        let Some((start, end, summary)) = event.as_mut() else {
+            continue;
+        };
+        match property.as_str() {
+            "DTSTART" => *start = Some(date(value)?),
+            "DTEND" => *end = Some(date(value)?),
+            "SUMMARY" => *summary = value.replace("\\,", ",").replace("\\;", ";"),
+            "RRULE" | "RDATE" => {
+                return Err(ProjectError::InvalidInput(
+                    "recurring events are not supported, list each holiday".to_string(),
+                ))
+            }
+            "END" if value.eq_ignore_ascii_case("VEVENT") => {
+                let start = start.ok_or_else(|| ProjectError::InvalidInput("an event has no DTSTART".to_string()))?;
+                // DTEND is the day after the last one
+                let end = end.filter(|end| *end > start).unwrap_or(start + Duration::days(1));
+                if (end - start).num_days() > MAX_DAYS_OFF {
+                    return Err(ProjectError::InvalidInput(format!(
+                        "a holiday spans {} days at most",
+                        MAX_DAYS_OFF
+                    )));
+                }
+                days.extend(start.iter_days().take_while(|day| *day < end).map(|day| (day, summary.clone())));
+                event = None;
+            }
+            _ => {}
+        }
+    }
+    Ok(days)
+}
+
+async fn organization_weekend(db: &Pool<Any>, tenant_id: i32) -> Result<Vec<Weekday>> {
+    let weekend: Option<(Option<String>,)> = sqlx::query_as("SELECT weekend FROM organizations WHERE tenant_id = $1")
+        .bind(tenant_id)
+        .fetch_optional(db)
+        .await?;
+    let weekend = weekend.and_then(|(weekend,)| weekend).unwrap_or_default();
+    parse_weekend(&weekend.split(',').collect::<Vec<&str>>())
+}
+
+async fn organization_holidays(db: &Pool<Any>, tenant_id: i32) -> Result<Vec<Holiday>> {
+    let holidays = sqlx::query_as::<_, Holiday>(
+        "SELECT holiday_id, date, name FROM holidays WHERE tenant_id = $1 ORDER BY date",
+    )
+    .bind(tenant_id)
+    .fetch_all(db)
+    .await?;
+    Ok(holidays)
+}
+
+/// Weekend and holidays of the organization, with no leave.
+pub(crate) async fn organization_calendar(db: &Pool<Any>, tenant_id: i32) -> Result<WorkingCalendar> {
+    let holidays = organization_holidays(db, tenant_id).await?;
+    Ok(WorkingCalendar {
+        weekend: organization_weekend(db, tenant_id).await?,
+        holidays: holidays.iter().map(|holiday| date_of(holiday.date)).collect(),
+        leave: vec![],
+    })
+}
+
+async fn leave_entries(db: &Pool<Any>, party_id: i32) -> Result<Vec<Leave>> {
+    let leave = sqlx::query_as::<_, Leave>(
+        "SELECT leave_id, party_id, from_date, to_date, reason FROM leave_entries
+        WHERE party_id = $1
+        ORDER BY from_date, leave_id",
+    )
+    .bind(party_id)
+    .fetch_all(db)
+    .await?;
+    Ok(leave)
+}
+
+/// (first day, last day) of the leave of the party.
+pub(crate) async fn party_leave(db: &Pool<Any>, party_id: i32) -> Result<Vec<(NaiveDate, NaiveDate)>> {
+    let leave = leave_entries(db, party_id).await?;
+    Ok(leave
+        .iter()
+        .map(|leave| (date_of(leave.from_date), date_of(leave.to_date)))
+        .collect())
+}
+
+/// The party itself, or whoever manages the calendar of its organization.
+fn may_manage_leave(state: &AppState, tk: &TokenClaims, context: &RequestContext, party_id: i32) -> Result<bool> {
+    if tk.is_guest() || tk.anonymous {
+        return Ok(false);
+    }
+    if tk.id == party_id {
+        return Ok(true);
+    }
+    let resource = GroupResource::all_projects();
+    Ok(state
+        .permission
+        .is_authorized(tk, Action::ManageCalendar, &resource, context)?)
+}
+
+/// Weekend and holidays of the organization of the caller, between `from` and `to` when given.
+#[get("/api/calendar")]
+async fn get_calendar(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    range: Query<CalendarQuery>,
+) -> Result<String> {
+    match token_claims {
+        Some(token_claims) => {
+            let tk = token_claims.into_inner();
+            if tk.is_guest() || tk.anonymous {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let weekend = organization_weekend(&state.db, tk.tenant_id).await?;
+            let holidays = organization_holidays(&state.db, tk.tenant_id)
+                .await?
+                .into_iter()
+                .filter(|holiday| {
+                    let date = date_of(holiday.date);
+                    range.from.map_or(true, |from| from <= date) && range.to.map_or(true, |to| date <= to)
+                })
+                .collect();
+            let calendar = Calendar {
+                weekend: weekend.iter().map(|day| day.to_string()).collect(),
+                holidays,
+            };
+
+            let json = serde_json::to_string(&calendar)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[put("/api/calendar/weekend")]
+async fn update_weekend(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    body: Json<WeekendBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let weekend = parse_weekend(&body.weekend.iter().map(String::as_str).collect::<Vec<&str>>())?;
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageCalendar, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let weekend: Vec<String> = weekend.iter().map(|day| day.to_string()).collect();
+            sqlx::query("UPDATE organizations SET weekend = $1 WHERE tenant_id = $2")
+                .bind(weekend.join(","))
+                .bind(tk.tenant_id)
+                .execute(&state.db)
+                .await?;
+
+            let detail = format!("weekend: {}", weekend.join(","));
+            audit::record(&state.db, tk.id, "UpdateWeekend", &resource.uid(), &[], &detail).await?;
+
+            let calendar = Calendar {
+                weekend,
+                holidays: organization_holidays(&state.db, tk.tenant_id).await?,
+            };
+            let json = serde_json::to_string(&calendar)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[post("/api/calendar/holidays")]
+async fn create_holiday(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    body: Json<HolidayBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let body = body.into_inner();
+            let name = body.name.trim();
+            if name.is_empty() {
+                return Err(ProjectError::InvalidInput("a holiday has a name".to_string()));
+            }
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageCalendar, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let holidays = organization_holidays(&state.db, tk.tenant_id).await?;
+            if holidays.iter().any(|holiday| date_of(holiday.date) == body.date) {
+                return Err(ProjectError::InvalidInput(format!("{} is already a holiday", body.date)));
+            }
+            let holiday = sqlx::query_as::<_, Holiday>(
+                "INSERT INTO holidays (tenant_id, date, name, created_by)
+                VALUES ($1, $2, $3, $4)
+                RETURNING holiday_id, date, name",
+            )
+            .bind(tk.tenant_id)
+            .bind(day_timestamp(body.date))
+            .bind(name)
+            .bind(tk.id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!("holiday {} on {}: {}", holiday.holiday_id, body.date, name);
+            audit::record(&state.db, tk.id, "CreateHoliday", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&holiday)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/calendar/holidays/{holiday_id}")]
+async fn delete_holiday(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let holiday_id = path.into_inner().parse::<i32>()?;
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageCalendar, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let holiday = organization_holidays(&state.db, tk.tenant_id)
+                .await?
+                .into_iter()
+                .find(|holiday| holiday.holiday_id == holiday_id)
+                .ok_or(ProjectError::Sqlx(sqlx::Error::RowNotFound))?;
+            sqlx::query("DELETE FROM holidays WHERE holiday_id = $1")
+                .bind(holiday_id)
+                .execute(&state.db)
+                .await?;
+
+            let detail = format!("holiday {}", holiday_id);
+            audit::record(&state.db, tk.id, "DeleteHoliday", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&holiday)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Imports the events of an iCalendar file as holidays, every day of an event spanning several,
+/// the days already holidays skipped.
+#[post("/api/calendar/holidays/import")]
+async fn import_holidays(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    body: String,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let days = parse_ical(&body)?;
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageCalendar, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let mut known: HashSet<NaiveDate> = organization_holidays(&state.db, tk.tenant_id)
+                .await?
+                .iter()
+                .map(|holiday| date_of(holiday.date))
+                .collect();
+            let mut imported: Vec<Holiday> = vec![];
+            let mut skipped = 0;
+            for (date, name) in days {
+                if !known.insert(date) {
+                    skipped += 1;
+                    continue;
+                }
+                let holiday = sqlx::query_as::<_, Holiday>(
+                    "INSERT INTO holidays (tenant_id, date, name, created_by)
+                    VALUES ($1, $2, $3, $4)
+                    RETURNING holiday_id, date, name",
+                )
+                .bind(tk.tenant_id)
+                .bind(day_timestamp(date))
+                .bind(name.trim())
+                .bind(tk.id)
+                .fetch_one(&state.db)
+                .await?;
+                imported.push(holiday);
+            }
+
+            let detail = format!("{} holidays imported, {} skipped", imported.len(), skipped);
+            audit::record(&state.db, tk.id, "ImportHolidays", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&HolidayImport { imported, skipped })?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[get("/api/parties/{party_id}/leave")]
+async fn list_leave(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let party_id = path.into_inner().parse::<i32>()?;
+
+            if party_tenant(&state.db, party_id).await? != Some(tk.tenant_id)
+                || !may_manage_leave(&state, &tk, &context, party_id)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let json = serde_json::to_string(&leave_entries(&state.db, party_id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Records leave of the party, by the party itself or whoever manages the calendar.
+#[post("/api/parties/{party_id}/leave")]
+async fn create_leave(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<LeaveBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let party_id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+            if body.to < body.from {
+                return Err(ProjectError::InvalidInput("a leave ends after its start".to_string()));
+            }
+            if (body.to - body.from).num_days() >= MAX_DAYS_OFF {
+                return Err(ProjectError::InvalidInput(format!(
+                    "a leave spans {} days at most",
+                    MAX_DAYS_OFF
+                )));
+            }
+
+            if party_tenant(&state.db, party_id).await? != Some(tk.tenant_id)
+                || !may_manage_leave(&state, &tk, &context, party_id)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let leave = sqlx::query_as::<_, Leave>(
+                "INSERT INTO leave_entries (party_id, from_date, to_date, reason, created_by)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING leave_id, party_id, from_date, to_date, reason",
+            )
+            .bind(party_id)
+            .bind(day_timestamp(body.from))
+            .bind(day_timestamp(body.to))
+            .bind(body.reason.trim())
+            .bind(tk.id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let resource = GroupResource::all_projects();
+            let detail = format!(
+                "leave {} of party {}: {} to {}",
+                leave.leave_id, party_id, body.from, body.to
+            );
+            audit::record(&state.db, tk.id, "CreateLeave", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&leave)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/parties/{party_id}/leave/{leave_id}")]
+async fn delete_leave(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (party_id, leave_id) = path.into_inner();
+            let party_id = party_id.parse::<i32>()?;
+            let leave_id = leave_id.parse::<i32>()?;
+
+            if party_tenant(&state.db, party_id).await? != Some(tk.tenant_id)
+                || !may_manage_leave(&state, &tk, &context, party_id)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let leave = leave_entries(&state.db, party_id)
+                .await?
+                .into_iter()
+                .find(|leave| leave.leave_id == leave_id)
+                .ok_or(ProjectError::Sqlx(sqlx::Error::RowNotFound))?;
+            sqlx::query("DELETE FROM leave_entries WHERE leave_id = $1")
+                .bind(leave_id)
+                .execute(&state.db)
+                .await?;
+
+            let resource = GroupResource::all_projects();
+            let detail = format!("leave {} of party {}", leave_id, party_id);
+            audit::record(&state.db, tk.id, "DeleteLeave", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&leave)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    fn date(day: u32) -> NaiveDate {
+        // 2030-01-07 is a Monday
+        NaiveDate::from_ymd_opt(2030, 1, day).unwrap()
+    }
+
+    fn calendar() -> WorkingCalendar {
+        WorkingCalendar {
+            weekend: parse_weekend(&["Sat", "Sun"]).unwrap(),
+            holidays: HashSet::from([date(9)]),
+            leave: vec![],
+        }
+    }
+
+    #[actix_web::test]
+    async fn working_days_skip_weekends_holidays_and_leave() {
+        let calendar = calendar();
+        // Mon 7, Tue 8, Thu 10, Fri 11, Mon 14
+        assert_eq!(calendar.working_days(date(7), date(14)), 5);
+        assert_eq!(calendar.nth_working_day(date(7), 0), date(7));
+        assert_eq!(calendar.nth_working_day(date(7), 4), date(14));
+        assert_eq!(calendar.nth_working_day(date(5), 0), date(7));
+
+        let calendar = calendar.with_leave(vec![(date(10), date(11))]);
+        assert_eq!(calendar.working_days(date(7), date(14)), 3);
+        assert_eq!(calendar.nth_working_day(date(7), 2), date(14));
+    }
+
+    #[actix_web::test]
+    async fn a_week_keeps_a_working_day() {
+        assert!(parse_weekend(&["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]).is_err());
+        assert!(parse_weekend(&["Funday"]).is_err());
+        assert_eq!(parse_weekend(&["Sun", "Fri", ""]).unwrap(), vec![Weekday::Fri, Weekday::Sun]);
+    }
+
+    #[actix_web::test]
+    async fn ical_events_are_holidays() {
+        let ical = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20300101\r\n\
+            SUMMARY:New Year\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20301224\r\n\
+            DTEND;VALUE=DATE:20301227\r\n\
+            SUMMARY:Christmas\\, Boxing\r\n  \
+            Day\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        let days = parse_ical(ical).unwrap();
+        let day = |month: u32, day: u32| NaiveDate::from_ymd_opt(2030, month, day).unwrap();
+        assert_eq!(
+            days,
+            vec![
+                (day(1, 1), "New Year".to_string()),
+                (day(12, 24), "Christmas, Boxing Day".to_string()),
+                (day(12, 25), "Christmas, Boxing Day".to_string()),
+                (day(12, 26), "Christmas, Boxing Day".to_string()),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn ical_folded_lines_and_recurring_events() {
+        let ical = "BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:2030\r\n \
+            0704\r\n\
+            SUMMARY:Independence\r\n \
+            Day\r\r\n\
+            END:VEVENT\r\n";
+        let day = NaiveDate::from_ymd_opt(2030, 7, 4).unwrap();
+        assert_eq!(parse_ical(ical).unwrap(), vec![(day, "IndependenceDay".to_string())]);
+
+        let ical = "BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20300101\r\n\
+            RRULE:FREQ=YEARLY\r\n\
+            SUMMARY:New Year\r\n\
+            END:VEVENT\r\n";
+        assert!(parse_ical(ical).is_err());
+    }
+
+    #[actix_web::test]
+    async fn schedule_dates_skip_holidays_and_weekends() {
+        let app = create_test_app().await;
+
+        let import_req = |token_claims: TokenClaims| {
+            test::TestRequest::post()
+                .uri("/api/calendar/holidays/import")
+                .insert_header(bearer(token_claims))
+                .insert_header(("Content-Type", "text/calendar"))
+                .set_payload(
+                    "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20300108\r\nSUMMARY:Founders Day\r\n\
+                    END:VEVENT\r\nEND:VCALENDAR\r\n",
+                )
+                .to_request()
+        };
+        let leave_req = |token_claims: TokenClaims, party_id: i32| {
+            test::TestRequest::post()
+                .uri(&format!("/api/parties/{}/leave", party_id))
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({ "from": "2030-01-14", "to": "2030-01-18", "reason": "vacation" }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, import_req(projectlead())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let import: Value = test::call_and_read_body_json(&app, import_req(admin())).await;
+        assert_eq!(import["imported"].as_array().unwrap().len(), 1);
+        let import: Value = test::call_and_read_body_json(&app, import_req(admin())).await;
+        assert_eq!(import["skipped"], 1);
+
+        let req = test::TestRequest::get()
+            .uri("/api/calendar?from=2030-01-01&to=2030-12-31")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let calendar: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(calendar["weekend"], serde_json::json!(["Sat", "Sun"]));
+        assert_eq!(calendar["holidays"][0]["name"], "Founders Day");
+
+        let resp = test::call_service(&app, leave_req(developer(), 3)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = test::call_service(&app, leave_req(developer(), 4)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Mon 7, Wed 9, Thu 10, Fri 11 and Mon 14, the holiday on Tue 8 and the weekend skipped
+        let req = test::TestRequest::post()
+            .uri("/api/projects/1/tasks")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "title": "design", "duration": 5 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1/schedule?start=2030-01-07")
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let schedule: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(schedule["tasks"][0]["start_date"], "2030-01-07");
+        assert_eq!(schedule["tasks"][0]["finish_date"], "2030-01-14");
+        assert_eq!(schedule["finish_date"], "2030-01-14");
+    }
+}
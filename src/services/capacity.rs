@@ -0,0 +1,490 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    get, put,
+    web::{self, Data, Json, Query, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::calendars::{organization_calendar, party_leave, WorkingCalendar};
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+
+use chrono::{DateTime, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+/// Allocation of a party beyond which it is over-allocated, in percent.
+const FULL_ALLOCATION: i32 = 100;
+
+/// Longest range of a capacity plan, in days.
+const MAX_CAPACITY_DAYS: i64 = 366;
+
+#[derive(Deserialize)]
+struct AllocationBody {
+    /// Percent of the time of the party, 1 to 100
+    allocation: i32,
+    /// First day, none when open
+    #[serde(default)]
+    from: Option<NaiveDate>,
+    /// Last day, none when open
+    #[serde(default)]
+    to: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+struct RangeQuery {
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+struct Assignment {
+    party_id: i32,
+    project_id: i32,
+    project_role: String,
+    /// percent
+    allocation: i32,
+    /// unix timestamp (seconds) of the first day
+    allocated_from: Option<i64>,
+    /// unix timestamp (seconds) of the last day
+    allocated_to: Option<i64>,
+}
+
+#[derive(FromRow)]
+struct PartyAssignment {
+    first_name: String,
+    last_name: String,
+    #[sqlx(flatten)]
+    assignment: Assignment,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct ProjectAllocation {
+    project_id: i32,
+    allocation: i32,
+}
+
+/// Days over which the allocations of a party do not change.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct AllocationPeriod {
+    from: NaiveDate,
+    to: NaiveDate,
+    /// Sum of the allocations, in percent
+    allocation: i32,
+    /// Days of the period the party works, its leave and the weekend and holidays excluded
+    working_days: i64,
+    on_leave: bool,
+    /// Above 100% on a working day
+    over_allocated: bool,
+    projects: Vec<ProjectAllocation>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct PartyCapacity {
+    party_id: i32,
+    name: String,
+    over_allocated: bool,
+    periods: Vec<AllocationPeriod>,
+}
+
+/// Allocation of an assignment over its days, open ends included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Span {
+    project_id: i32,
+    allocation: i32,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+impl Span {
+    fn covers(&self, day: NaiveDate) -> bool {
+        self.from.map_or(true, |from| from <= day) && self.to.map_or(true, |to| day <= to)
+    }
+}
+
+fn date_of(timestamp: i64) -> NaiveDate {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default().date_naive()
+}
+
+/// Unix timestamp (seconds) of midnight UTC of the day.
+fn day_timestamp(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()
+}
+
+/// Splits `from` ..= `to` into the periods over which the allocations of the spans and the leave
+/// in the calendar of the party do not change.
+fn allocation_periods(
+    spans: &[Span],
+    calendar: &WorkingCalendar,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<AllocationPeriod> {
+    let bounds = spans
+        .iter()
+        .map(|span| (span.from, span.to))
+        .chain(calendar.leave().iter().map(|(first, last)| (Some(*first), Some(*last))));
+    let mut starts = vec![from];
+    for (first, last) in bounds {
+        starts.extend(first.filter(|day| from < *day && *day <= to));
+        starts.extend(last.filter(|day| from <= *day && *day < to).map(|day| day + Duration::days(1)));
+    }
+    starts.sort();
+    starts.dedup();
+
+    let mut periods: Vec<AllocationPeriod> = vec![];
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(to, |next| *next - Duration::days(1));
+        let projects: Vec<ProjectAllocation> = spans
+            .iter()
+            .filter(|span| span.covers(*start))
+            .map(|span| ProjectAllocation {
+                project_id: span.project_id,
+                allocation: span.allocation,
+            })
+            .collect();
+        let on_leave = calendar.on_leave(*start);
+        let working_days = calendar.working_days(*start, end);
+        match periods.last_mut() {
+            Some(period) if period.projects == projects && period.on_leave == on_leave => {
+                period.to = end;
+                period.working_days += working_days;
+                period.over_allocated = period.allocation > FULL_ALLOCATION && period.working_days > 0;
+            }
+            _ => {
+                let allocation = projects.iter().map(|project| project.allocation).sum();
+                periods.push(AllocationPeriod {
+                    from: *start,
+                    to: end,
+                    allocation,
+                    working_days,
+                    on_leave,
+                    over_allocated: allocation > FULL_ALLOCATION && working_days > 0,
+                    projects,
+                });
+            }
+        }
+    }
+    periods
+}
+
+async fn project_assignments(db: &Pool<Any>, project_id: i32) -> Result<Vec<Assignment>> {
+    let assignments = sqlx::query_as::<_, Assignment>(
+        "SELECT party_role.party_id, assignments.project_id, assignments.project_role, assignments.allocation,
+        assignments.allocated_from, assignments.allocated_to
+        FROM assignments
+        JOIN party_role ON party_role.party_role_id = assignments.party_role_id
+        WHERE assignments.project_id = $1
+        ORDER BY party_role.party_id",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+    Ok(assignments)
+}
+
+#[get("/api/projects/{id}/assignments")]
+async fn list_assignments(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let json = serde_json::to_string(&project_assignments(&state.db, id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Sets the allocation of the party assigned to the project, with whoever assigns its parties.
+#[put("/api/projects/{id}/assignments/{party_id}")]
+async fn update_allocation(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<AllocationBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, party_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let party_id = party_id.parse::<i32>()?;
+            let body = body.into_inner();
+            if body.allocation <= 0 || body.allocation > FULL_ALLOCATION {
+                return Err(ProjectError::InvalidInput(format!(
+                    "an allocation is 1 to {} percent",
+                    FULL_ALLOCATION
+                )));
+            }
+            if let (Some(from), Some(to)) = (body.from, body.to) {
+                if to < from {
+                    return Err(ProjectError::InvalidInput("an allocation ends after its start".to_string()));
+                }
+            }
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::AssignPartyrole, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let updated = sqlx::query(
+                "UPDATE assignments SET allocation = $1, allocated_from = $2, allocated_to = $3
+                WHERE project_id = $4
+                AND party_role_id IN (SELECT party_role_id FROM party_role WHERE party_id = $5)",
+            )
+            .bind(body.allocation)
+            .bind(body.from.map(day_timestamp))
+            .bind(body.to.map(day_timestamp))
+            .bind(id)
+            .bind(party_id)
+            .execute(&state.db)
+            .await?;
+            if updated.rows_affected() == 0 {
+                return Err(ProjectError::InvalidInput(format!(
+                    "party {} is not assigned to project {}",
+                    party_id, id
+                )));
+            }
+
+            let detail = format!(
+                "allocation of party {}: {}% from {:?} to {:?}",
+                party_id, body.allocation, body.from, body.to
+            );
+            audit::record(&state.db, tk.id, "UpdateAllocation", &resource.uid(), &[], &detail).await?;
+
+            let assignment = project_assignments(&state.db, id)
+                .await?
+                .into_iter()
+                .find(|assignment| assignment.party_id == party_id)
+                .ok_or(ProjectError::Unknown)?;
+            let json = serde_json::to_string(&assignment)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Allocation over `from` ..= `to` of the parties of the organization of the caller with an
+/// assignment, across all projects, flagged when above 100% on working days of the party.
+#[get("/api/capacity")]
+async fn get_capacity(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    range: Query<RangeQuery>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            if range.to < range.from {
+                return Err(ProjectError::InvalidInput("a capacity plan ends after its start".to_string()));
+            }
+            if (range.to - range.from).num_days() >= MAX_CAPACITY_DAYS {
+                return Err(ProjectError::InvalidInput(format!(
+                    "a capacity plan spans {} days at most",
+                    MAX_CAPACITY_DAYS
+                )));
+            }
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ViewCapacity, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+            // the organization of the party, as recorded, with its calendar
+            let tenant_id = party_tenant(&state.db, tk.id)
+                .await?
+                .ok_or(ProjectError::AuthFailed)?;
+
+            let assignments = sqlx::query_as::<_, PartyAssignment>(
+                "SELECT party_role.party_id, COALESCE(parties.first_name, '') AS first_name,
+                COALESCE(parties.last_name, '') AS last_name,
+                assignments.project_id, assignments.project_role, assignments.allocation,
+                assignments.allocated_from, assignments.allocated_to
+                FROM assignments
+                JOIN party_role ON party_role.party_role_id = assignments.party_role_id
+                JOIN parties ON parties.party_id = party_role.party_id
+                WHERE parties.tenant_id = $1
+                ORDER BY party_role.party_id, assignments.project_id",
+            )
+            .bind(tenant_id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let calendar = organization_calendar(&state.db, tenant_id).await?;
+            let mut capacity: Vec<PartyCapacity> = vec![];
+            let mut spans: Vec<Span> = vec![];
+            for (i, row) in assignments.iter().enumerate() {
+                spans.push(Span {
+                    project_id: row.assignment.project_id,
+                    allocation: row.assignment.allocation,
+                    from: row.assignment.allocated_from.map(date_of),
+                    to: row.assignment.allocated_to.map(date_of),
+                });
+                let last_of_party = assignments
+                    .get(i + 1)
+                    .map_or(true, |next| next.assignment.party_id != row.assignment.party_id);
+                if last_of_party {
+                    let leave = party_leave(&state.db, row.assignment.party_id).await?;
+                    let calendar = calendar.clone().with_leave(leave);
+                    let periods = allocation_periods(&spans, &calendar, range.from, range.to);
+                    capacity.push(PartyCapacity {
+                        party_id: row.assignment.party_id,
+                        name: format!("{} {}", row.first_name, row.last_name).trim().to_string(),
+                        over_allocated: periods.iter().any(|period| period.over_allocated),
+                        periods,
+                    });
+                    spans.clear();
+                }
+            }
+
+            let json = serde_json::to_string(&capacity)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2030, 1, day).unwrap()
+    }
+
+    fn span(project_id: i32, allocation: i32, from: Option<u32>, to: Option<u32>) -> Span {
+        Span {
+            project_id,
+            allocation,
+            from: from.map(date),
+            to: to.map(date),
+        }
+    }
+
+    #[actix_web::test]
+    async fn periods_follow_the_allocations() {
+        // 1: 60% all along, 2: 50% from the 10th to the 20th
+        let spans = vec![span(1, 60, None, None), span(2, 50, Some(10), Some(20))];
+
+        let periods = allocation_periods(&spans, &WorkingCalendar::default(), date(1), date(31));
+        let summary: Vec<(NaiveDate, NaiveDate, i32, bool)> = periods
+            .iter()
+            .map(|period| (period.from, period.to, period.allocation, period.over_allocated))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (date(1), date(9), 60, false),
+                (date(10), date(20), 110, true),
+                (date(21), date(31), 60, false),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn periods_are_clipped_to_the_range() {
+        let spans = vec![span(1, 100, Some(5), None), span(2, 20, None, Some(2))];
+
+        let periods = allocation_periods(&spans, &WorkingCalendar::default(), date(3), date(6));
+        let summary: Vec<(NaiveDate, NaiveDate, i32)> = periods
+            .iter()
+            .map(|period| (period.from, period.to, period.allocation))
+            .collect();
+        assert_eq!(summary, vec![(date(3), date(4), 0), (date(5), date(6), 100)]);
+    }
+
+    #[actix_web::test]
+    async fn leave_is_no_over_allocation() {
+        let spans = vec![span(1, 60, None, None), span(2, 60, None, None)];
+        let calendar = WorkingCalendar::default().with_leave(vec![(date(10), date(12))]);
+
+        let periods = allocation_periods(&spans, &calendar, date(1), date(31));
+        let summary: Vec<(NaiveDate, NaiveDate, i64, bool, bool)> = periods
+            .iter()
+            .map(|period| (period.from, period.to, period.working_days, period.on_leave, period.over_allocated))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (date(1), date(9), 9, false, true),
+                (date(10), date(12), 0, true, false),
+                (date(13), date(31), 19, false, true),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn capacity_flags_overlapping_allocations() {
+        let app = create_test_app().await;
+
+        let allocation_req = |token_claims: TokenClaims, project_id: i32, allocation: Value| {
+            test::TestRequest::put()
+                .uri(&format!("/api/projects/{}/assignments/4", project_id))
+                .insert_header(bearer(token_claims))
+                .set_json(allocation)
+                .to_request()
+        };
+        let capacity_req = |token_claims: TokenClaims| {
+            test::TestRequest::get()
+                .uri("/api/capacity?from=2030-01-01&to=2030-03-31")
+                .insert_header(bearer(token_claims))
+                .to_request()
+        };
+
+        let body = serde_json::json!({ "role": "Developer", "project_id": 1, "reason": "joining the team" });
+        let request: Value = test::call_and_read_body_json(&app, role_request_req(developer(), body)).await;
+        let id = request["request_id"].as_i64().unwrap();
+        let resp = test::call_service(&app, decide_role_request_req(projectlead(), id, "approve")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let allocation = serde_json::json!({ "allocation": 60, "from": "2030-01-01", "to": "2030-01-31" });
+        let resp = test::call_service(&app, allocation_req(developer(), 2, allocation.clone())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = test::call_service(&app, allocation_req(projectlead(), 2, allocation)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let allocation = serde_json::json!({ "allocation": 60, "from": "2030-01-20" });
+        let resp = test::call_service(&app, allocation_req(projectlead(), 1, allocation)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let allocation = serde_json::json!({ "allocation": 120 });
+        let resp = test::call_service(&app, allocation_req(projectlead(), 1, allocation)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = test::call_service(&app, capacity_req(developer())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let capacity: Vec<Value> = test::call_and_read_body_json(&app, capacity_req(projectlead())).await;
+        let party = capacity.iter().find(|party| party["party_id"] == 4).unwrap();
+        assert_eq!(party["over_allocated"], true);
+        let periods = party["periods"].as_array().unwrap();
+        assert_eq!(periods.len(), 3);
+        assert_eq!(periods[1]["from"], "2030-01-20");
+        assert_eq!(periods[1]["to"], "2030-01-31");
+        assert_eq!(periods[1]["allocation"], 120);
+        assert_eq!(periods[2]["allocation"], 60);
+        assert_eq!(periods[2]["over_allocated"], false);
+    }
+}
@@ -0,0 +1,494 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::projects::{project_resource, ProjectError, Result, CONFIDENTIAL_FIELDS};
+
+use cedar_policy::{ParseErrors, Policy, PolicyId, PolicySet};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, FromRow};
+
+/// Project actions a project lead may delegate.
+const DELEGABLE_ACTIONS: [Action; 3] = [
+    Action::ViewProject,
+    Action::AssignPartyrole,
+    Action::ShareProject,
+];
+
+#[derive(Deserialize)]
+struct CreateDelegationBody {
+    delegate_id: i32,
+    actions: Vec<String>,
+    valid_from: DateTime<Utc>,
+    valid_to: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct Delegation {
+    delegation_id: i32,
+    project_id: i32,
+    delegator_id: i32,
+    delegate_id: i32,
+    /// comma separated action names
+    actions: String,
+    /// unix timestamp (seconds)
+    valid_from: i64,
+    /// unix timestamp (seconds), excluded
+    valid_to: i64,
+}
+
+impl Delegation {
+    /// The policies granting the delegated actions, effective during the validity window
+    /// and as long as the delegator owns the project. A delegated `ViewProject` also reads
+    /// the fields of the project, the confidential ones excepted.
+    fn policies(&self) -> std::result::Result<Vec<Policy>, ParseErrors> {
+        let id = format!("Delegation.{}", self.delegation_id);
+        let names: Vec<&str> = self
+            .actions
+            .split(',')
+            .filter(|name| DELEGABLE_ACTIONS.iter().any(|action| action.name() == *name))
+            .collect();
+        let actions: Vec<String> = names.iter().map(|name| format!("Action::\"{}\"", name)).collect();
+        let when = format!(
+            r#"resource.owner == User::"{delegator}" &&
+  {valid_from} <= context.now && context.now < {valid_to}"#,
+            delegator = self.delegator_id,
+            valid_from = self.valid_from,
+            valid_to = self.valid_to,
+        );
+
+        let src = format!(
+            r#"@id("{id}")
+@audit("delegation")
+permit(
+  principal == User::"{delegate}",
+  action in [{actions}],
+  resource == Project::"{project}"
+)
+when {{
+  {when}
+}};"#,
+            id = id,
+            delegate = self.delegate_id,
+            actions = actions.join(", "),
+            project = self.project_id,
+            when = when,
+        );
+        let mut policies = vec![Policy::parse(Some(id.clone()), src)?];
+
+        if names.contains(&Action::ViewProject.name()) {
+            let id = format!("{}.Field", id);
+            let confidential: Vec<String> = CONFIDENTIAL_FIELDS.iter().map(|field| format!("\"{}\"", field)).collect();
+            let src = format!(
+                r#"@id("{id}")
+permit(
+  principal == User::"{delegate}",
+  action == Action::"ViewProjectField",
+  resource == Project::"{project}"
+)
+when {{
+  {when} &&
+  context has field &&
+  ![{confidential}].contains(context.field)
+}};"#,
+                id = id,
+                delegate = self.delegate_id,
+                project = self.project_id,
+                when = when,
+                confidential = confidential.join(", "),
+            );
+            policies.push(Policy::parse(Some(id), src)?);
+        }
+        Ok(policies)
+    }
+}
+
+/// The permission of a party: the policies, the policies of its organization and the
+/// delegations it received.
+pub(crate) async fn permission_for(state: &AppState, token_claims: &TokenClaims) -> Result<Permission> {
+    let party_id = token_claims.id;
+    let delegations = sqlx::query_as::<_, Delegation>(
+        "SELECT delegation_id, project_id, delegator_id, delegate_id, actions, valid_from, valid_to
+        FROM delegations
+        WHERE delegate_id = $1 AND revoked = 0 AND valid_to > $2",
+    )
+    .bind(party_id)
+    .bind(Utc::now().timestamp())
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut policies = delegations
+        .iter()
+        .map(Delegation::policies)
+        .collect::<std::result::Result<Vec<Vec<Policy>>, ParseErrors>>()
+        .map_err(AuthorizerError::from)?
+        .concat();
+    policies.extend(tenant_policies(state, token_claims.tenant_id).await?);
+    Ok(state.permission.with_policies(policies)?)
+}
+
+/// The additional policies of an organization, renamed `Tenant<tenant_id>.<id>`. They cannot
+/// grant anything across organizations, `TenantPolicy` forbids it.
+async fn tenant_policies(state: &AppState, tenant_id: i32) -> Result<Vec<Policy>> {
+    let src: Option<(String,)> = sqlx::query_as("SELECT policies FROM organizations WHERE tenant_id = $1")
+        .bind(tenant_id)
+        .fetch_optional(&state.db)
+        .await?;
+    let src = match src {
+        Some((src,)) if !src.trim().is_empty() => src,
+        _ => return Ok(vec![]),
+    };
+
+    let policies = PolicySet::from_str(&src).map_err(AuthorizerError::from)?;
+    Ok(policies
+        .policies()
+        .map(|policy| {
+            let id = policy.annotation("id").unwrap_or(policy.id().as_ref()).to_string();
+            policy.new_id(PolicyId::from_str(&format!("Tenant{}.{}", tenant_id, id)).unwrap())
+        })
+        .collect())
+}
+
+#[post("/api/projects/{id}/delegations")]
+async fn create_delegation(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<CreateDelegationBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+
+            if body.valid_to <= body.valid_from {
+                return Err(ProjectError::InvalidInput("valid_to must follow valid_from".to_string()));
+            }
+            let mut actions = vec![];
+            for name in &body.actions {
+                match DELEGABLE_ACTIONS.iter().find(|action| action.name() == name) {
+                    Some(action) => actions.push(*action),
+                    None => return Err(ProjectError::InvalidInput(format!("{} cannot be delegated", name))),
+                }
+            }
+            if actions.is_empty() {
+                return Err(ProjectError::InvalidInput("no action to delegate".to_string()));
+            }
+
+            // only the rights the lead holds can be delegated, DelegateProject itself is not
+            // delegable so delegates do not delegate further
+            let resource = project_resource(&state.db, id).await?;
+            let permission = permission_for(&state, &tk).await?;
+            for action in actions.iter().copied().chain([Action::DelegateProject]) {
+                if !permission.is_authorized(&tk, action, &resource, &context)? {
+                    return Err(ProjectError::AuthFailed);
+                }
+            }
+
+            let delegation = sqlx::query_as::<_, Delegation>(
+                "INSERT INTO delegations (project_id, delegator_id, delegate_id, actions, valid_from, valid_to)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING delegation_id, project_id, delegator_id, delegate_id, actions, valid_from, valid_to",
+            )
+            .bind(id)
+            .bind(tk.id)
+            .bind(body.delegate_id)
+            .bind(body.actions.join(","))
+            .bind(body.valid_from.timestamp())
+            .bind(body.valid_to.timestamp())
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!(
+                "delegation {} of {} to party {} from {} to {}",
+                delegation.delegation_id, delegation.actions, delegation.delegate_id, body.valid_from, body.valid_to
+            );
+            audit::record(&state.db, tk.id, "CreateDelegation", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&delegation)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[get("/api/projects/{id}/delegations")]
+async fn list_delegations(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            if !state
+                .permission
+                .is_authorized(&tk, Action::DelegateProject, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let delegations = sqlx::query_as::<_, Delegation>(
+                "SELECT delegation_id, project_id, delegator_id, delegate_id, actions, valid_from, valid_to
+                FROM delegations
+                WHERE project_id = $1 AND revoked = 0
+                ORDER BY delegation_id",
+            )
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let json = serde_json::to_string(&delegations)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/projects/{id}/delegations/{delegation_id}")]
+async fn revoke_delegation(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, delegation_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let delegation_id = delegation_id.parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            if !state
+                .permission
+                .is_authorized(&tk, Action::DelegateProject, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let delegation = sqlx::query_as::<_, Delegation>(
+                "UPDATE delegations SET revoked = 1
+                WHERE delegation_id = $1 AND project_id = $2
+                RETURNING delegation_id, project_id, delegator_id, delegate_id, actions, valid_from, valid_to",
+            )
+            .bind(delegation_id)
+            .bind(id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!("delegation {} revoked", delegation.delegation_id);
+            audit::record(&state.db, tk.id, "RevokeDelegation", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&delegation)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use chrono::TimeZone;
+    use dotenv::dotenv;
+    use serde_json::Value;
+
+    fn delegation(valid_from: DateTime<Utc>, valid_to: DateTime<Utc>) -> Delegation {
+        Delegation {
+            delegation_id: 1,
+            project_id: 1,
+            delegator_id: 3,
+            delegate_id: 4,
+            actions: "ViewProject,ShareProject".to_string(),
+            valid_from: valid_from.timestamp(),
+            valid_to: valid_to.timestamp(),
+        }
+    }
+
+    fn view_project_at(delegation: &Delegation, owner: i32, now: DateTime<Utc>) -> Authorization {
+        let permission = Permission::default()
+            .with_policies(delegation.policies().unwrap())
+            .unwrap();
+        let token_claims = TokenClaims {
+            id: 4,
+            roles: vec!["Developer".to_string()],
+            ..Default::default()
+        };
+        let resource = ProjectResource {
+            id: 1,
+            owner,
+            assignees: vec![],
+            project_roles: vec![],
+            status: "active".to_string(),
+            legal_hold: false,
+            public: false,
+            tenant: 1,
+            teams: vec![],
+            team_members: vec![],
+        };
+        let context = RequestContext::new(&token_claims, None, now);
+        permission
+            .authorize(&token_claims, Action::ViewProject, &resource, &context)
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn delegation_is_effective_within_its_window() {
+        let from = Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 7, 15, 0, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 7, 3, 12, 0, 0).unwrap();
+
+        let authorization = view_project_at(&delegation(from, to), 3, now);
+        assert!(authorization.allowed);
+        assert!(authorization.audit);
+        assert_eq!(authorization.policy_ids, vec!["Delegation.1"]);
+    }
+
+    #[actix_web::test]
+    async fn delegation_expires() {
+        let from = Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 7, 15, 0, 0, 0).unwrap();
+
+        let before = Utc.with_ymd_and_hms(2024, 6, 30, 23, 59, 59).unwrap();
+        assert!(!view_project_at(&delegation(from, to), 3, before).allowed);
+        assert!(!view_project_at(&delegation(from, to), 3, to).allowed);
+    }
+
+    #[actix_web::test]
+    async fn delegation_ends_with_ownership() {
+        let from = Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 7, 15, 0, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 7, 3, 12, 0, 0).unwrap();
+
+        assert!(!view_project_at(&delegation(from, to), 5, now).allowed);
+    }
+
+    #[actix_web::test]
+    async fn delegate_can_view_project_and_is_audited() {
+        let app = create_test_app().await;
+
+        let resp = test::call_service(&app, get_project_req(developer(), 1)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let now = chrono::Utc::now();
+        let req = test::TestRequest::post()
+            .uri("/api/projects/1/delegations")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({
+                "delegate_id": 4,
+                "actions": ["ViewProject"],
+                "valid_from": now - chrono::Duration::hours(1),
+                "valid_to": now + chrono::Duration::hours(1),
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // the delegate reads the fields of the project, the confidential ones excepted
+        let project: Value = test::call_and_read_body_json(&app, get_project_req(developer(), 1)).await;
+        assert_eq!(project["name"], "my project");
+        assert_eq!(project["description"], "this project");
+        assert!(project.get("budget").is_none());
+        assert!(project.get("internal_description").is_none());
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1/audit")
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let entries: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        let actions: Vec<&str> = entries.iter().map(|e| e["action"].as_str().unwrap()).collect();
+        assert_eq!(actions, vec!["CreateDelegation", "ViewProject"]);
+        assert_eq!(entries[1]["policies"], "Delegation.1");
+    }
+
+    #[actix_web::test]
+    async fn tenants_are_isolated() {
+        dotenv().ok();
+        sqlx::any::install_default_drivers();
+        let app_data = Arc::new(create_app_data().await.unwrap());
+        for statement in [
+            "INSERT INTO organizations (tenant_id, name, policies) VALUES (2, 'Acme',
+            '@id(\"AcmeDevelopers\") permit(principal in Role::\"Developer\", action == Action::\"ViewProject\", resource);')",
+            "INSERT INTO parties (party_id, first_name, last_name, created_by, tenant_id) VALUES (5, 'Olga', 'Petrova', 1, 2)",
+            "INSERT INTO party_role (party_role_id, party_id, role_type_id, created_by) VALUES (5, 5, 4, 1)",
+            "INSERT INTO projects (id, name, description, owned_by, created_by, updated_by, tenant_id)
+            VALUES (3, 'acme project', 'their project', 5, 1, 1, 2)",
+        ] {
+            sqlx::query(statement).execute(&app_data.db).await.unwrap();
+        }
+        let app = create_test_app_with(app_data).await;
+
+        let acme_developer = || TokenClaims {
+            id: 5,
+            roles: vec!["Developer".to_string()],
+            tenant_id: 2,
+            ..Default::default()
+        };
+
+        // the policies of Acme grant its developers every project of Acme, and nothing else
+        let projects: Vec<Value> = test::call_and_read_body_json(&app, view_project_req(acme_developer())).await;
+        let ids: Vec<&Value> = projects.iter().map(|project| &project["id"]).collect();
+        assert_eq!(ids, vec![3]);
+        let resp = test::call_service(&app, get_project_req(acme_developer(), 1)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let resp = test::call_service(&app, get_project_req(developer(), 3)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let resp = test::call_service(&app, get_project_req(TokenClaims { tenant_id: 2, ..developer() }, 2)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn anonymous_can_view_public_projects_of_every_tenant() {
+        dotenv().ok();
+        sqlx::any::install_default_drivers();
+        let app_data = Arc::new(create_app_data().await.unwrap());
+        for statement in [
+            "INSERT INTO organizations (tenant_id, name, policies) VALUES (2, 'Acme', '')",
+            "INSERT INTO parties (party_id, first_name, last_name, created_by, tenant_id) VALUES (5, 'Olga', 'Petrova', 1, 2)",
+            "INSERT INTO party_role (party_role_id, party_id, role_type_id, created_by) VALUES (5, 5, 3, 1)",
+            "INSERT INTO projects (id, name, description, owned_by, created_by, updated_by, tenant_id, public)
+            VALUES (3, 'acme project', 'their project', 5, 1, 1, 2, 1)",
+            "INSERT INTO projects (id, name, description, owned_by, created_by, updated_by, tenant_id)
+            VALUES (4, 'acme secret', 'their other project', 5, 1, 1, 2)",
+        ] {
+            sqlx::query(statement).execute(&app_data.db).await.unwrap();
+        }
+        let app = create_test_app_with(app_data).await;
+        let anonymous_req = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+        let project: Value = test::call_and_read_body_json(&app, anonymous_req("/api/projects/3")).await;
+        assert_eq!(project["name"], "acme project");
+        let resp = test::call_service(&app, anonymous_req("/api/projects/4")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let projects: Vec<Value> = test::call_and_read_body_json(&app, anonymous_req("/api/projects")).await;
+        let ids: Vec<&Value> = projects.iter().map(|project| &project["id"]).collect();
+        assert_eq!(ids, vec![3]);
+
+        // the members of another organization still do not read it
+        let resp = test::call_service(&app, get_project_req(developer(), 3)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+}
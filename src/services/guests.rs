@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+#[derive(Deserialize)]
+struct CreateGuestInviteBody {
+    /// e.g. the e-mail address of the guest
+    label: String,
+    valid_to: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct GuestInvite {
+    invite_id: i32,
+    project_id: i32,
+    /// party_id of the party sharing the project
+    invited_by: i32,
+    label: String,
+    /// unix timestamp (seconds), excluded
+    valid_to: i64,
+}
+
+#[derive(Serialize)]
+struct GuestInviteLink {
+    #[serde(flatten)]
+    invite: GuestInvite,
+    /// bearer token of the `Guest` principal, expiring with the invite
+    token: String,
+}
+
+/// The invite is neither revoked nor expired, checked on every request of a guest.
+pub async fn active_invite(db: &Pool<Any>, invite_id: i32, project_id: i32) -> Result<bool> {
+    let invite: Option<(i32,)> = sqlx::query_as(
+        "SELECT invite_id FROM guest_invites
+        WHERE invite_id = $1 AND project_id = $2 AND revoked = 0 AND valid_to > $3",
+    )
+    .bind(invite_id)
+    .bind(project_id)
+    .bind(Utc::now().timestamp())
+    .fetch_optional(db)
+    .await?;
+    Ok(invite.is_some())
+}
+
+/// Invites an external collaborator to view the project, the caller needs `ShareProject`.
+#[post("/api/projects/{id}/guests")]
+async fn create_guest_invite(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<CreateGuestInviteBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+
+            let label = body.label.trim();
+            if label.is_empty() {
+                return Err(ProjectError::InvalidInput("a label is required".to_string()));
+            }
+            if body.valid_to <= Utc::now() {
+                return Err(ProjectError::InvalidInput("valid_to must be in the future".to_string()));
+            }
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ShareProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let invite = sqlx::query_as::<_, GuestInvite>(
+                "INSERT INTO guest_invites (project_id, invited_by, label, valid_to)
+                VALUES ($1, $2, $3, $4)
+                RETURNING invite_id, project_id, invited_by, label, valid_to",
+            )
+            .bind(id)
+            .bind(tk.id)
+            .bind(label)
+            .bind(body.valid_to.timestamp())
+            .fetch_one(&state.db)
+            .await?;
+
+            let token = TokenService::generate_token(TokenClaims {
+                id: invite.invite_id,
+                tenant_id: resource.tenant,
+                guest_project: Some(invite.project_id),
+                exp: Some(invite.valid_to),
+                ..Default::default()
+            })?;
+
+            let detail = format!(
+                "guest invite {} for {} until {}",
+                invite.invite_id, invite.label, body.valid_to
+            );
+            audit::record(&state.db, tk.id, "CreateGuestInvite", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&GuestInviteLink { invite, token })?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[get("/api/projects/{id}/guests")]
+async fn list_guest_invites(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ShareProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let invites = sqlx::query_as::<_, GuestInvite>(
+                "SELECT invite_id, project_id, invited_by, label, valid_to
+                FROM guest_invites
+                WHERE project_id = $1 AND revoked = 0 AND valid_to > $2
+                ORDER BY invite_id",
+            )
+            .bind(id)
+            .bind(Utc::now().timestamp())
+            .fetch_all(&state.db)
+            .await?;
+
+            let json = serde_json::to_string(&invites)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/projects/{id}/guests/{invite_id}")]
+async fn revoke_guest_invite(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, invite_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let invite_id = invite_id.parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ShareProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let invite = sqlx::query_as::<_, GuestInvite>(
+                "UPDATE guest_invites SET revoked = 1
+                WHERE invite_id = $1 AND project_id = $2
+                RETURNING invite_id, project_id, invited_by, label, valid_to",
+            )
+            .bind(invite_id)
+            .bind(id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!("guest invite {} revoked", invite.invite_id);
+            audit::record(&state.db, tk.id, "RevokeGuestInvite", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&invite)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn guest_can_view_invited_project_only() {
+        let app = create_test_app().await;
+
+        let guest_req = |token: &str, uri: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/1/guests")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({
+                "label": "jane@partner.example",
+                "valid_to": chrono::Utc::now() + chrono::Duration::days(7),
+            }))
+            .to_request();
+        let invite: Value = test::call_and_read_body_json(&app, req).await;
+        let token = invite["token"].as_str().unwrap().to_string();
+
+        let project: Value = test::call_and_read_body_json(&app, guest_req(&token, "/api/projects/1")).await;
+        assert_eq!(project["name"], "my project");
+        assert!(project.get("budget").is_none());
+
+        let resp = test::call_service(&app, guest_req(&token, "/api/projects/2")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = test::call_service(&app, guest_req(&token, "/api/projects")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::post()
+            .uri("/api/break-glass")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(serde_json::json!({ "justification": "incident 42" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/api/projects/1/guests/{}", invite["invite_id"]))
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(&app, guest_req(&token, "/api/projects/1")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,475 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post, put,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::calendars::organization_calendar;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+use super::schedule::{compute_schedule, project_dependencies};
+use super::workflows::workflow;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Deserialize)]
+struct MilestoneBody {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    due_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct Milestone {
+    milestone_id: i32,
+    project_id: i32,
+    name: String,
+    description: String,
+    /// unix timestamp (seconds)
+    due_date: Option<i64>,
+}
+
+#[derive(FromRow)]
+struct MilestoneTask {
+    task_id: i32,
+    status: String,
+    /// days
+    duration: i32,
+    /// unix timestamp (seconds)
+    due_date: Option<i64>,
+    milestone_id: Option<i32>,
+}
+
+/// Progress of a milestone, from the tasks attached to it.
+#[derive(Serialize, Debug, PartialEq)]
+struct MilestoneRollup {
+    #[serde(flatten)]
+    milestone: Milestone,
+    tasks: usize,
+    done_tasks: usize,
+    /// Share of the estimated days done, of the tasks when none is estimated
+    percent_complete: f64,
+    /// task_id of the open tasks past their due date
+    overdue_tasks: Vec<i32>,
+    /// unix timestamp (seconds) the open tasks are forecast to be done, from their schedule,
+    /// none once they are all done
+    forecast_completion: Option<i64>,
+    /// The forecast completion is past the due date
+    late: bool,
+}
+
+const MILESTONE_COLUMNS: &str = "milestone_id, project_id, name, description, due_date";
+
+async fn milestones(db: &Pool<Any>, project_id: i32) -> Result<Vec<Milestone>> {
+    let milestones = sqlx::query_as::<_, Milestone>(&format!(
+        "SELECT {} FROM milestones WHERE project_id = $1 ORDER BY due_date, milestone_id",
+        MILESTONE_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+    Ok(milestones)
+}
+
+/// The milestone of the project, as a Cedar `Milestone` entity.
+async fn milestone_resource(db: &Pool<Any>, project_id: i32, milestone_id: i32) -> Result<MilestoneResource> {
+    let project = project_resource(db, project_id).await?;
+    let _: (i32,) = sqlx::query_as("SELECT milestone_id FROM milestones WHERE milestone_id = $1 AND project_id = $2")
+        .bind(milestone_id)
+        .bind(project_id)
+        .fetch_one(db)
+        .await?;
+    Ok(MilestoneResource {
+        id: milestone_id,
+        project,
+    })
+}
+
+/// The milestones of the project the caller may view.
+async fn visible_milestones(
+    state: &AppState,
+    token_claims: &TokenClaims,
+    context: &RequestContext,
+    project_id: i32,
+) -> Result<Vec<Milestone>> {
+    let project = project_resource(&state.db, project_id).await?;
+    let permission = delegations::permission_for(state, token_claims).await?;
+    let mut visible = vec![];
+    for milestone in milestones(&state.db, project_id).await? {
+        let resource = MilestoneResource {
+            id: milestone.milestone_id,
+            project: project.clone(),
+        };
+        if permission.is_authorized(token_claims, Action::ViewMilestone, &resource, context)? {
+            visible.push(milestone);
+        }
+    }
+    Ok(visible)
+}
+
+fn rollup(
+    milestone: Milestone,
+    tasks: &[MilestoneTask],
+    done_state: Option<&str>,
+    forecast: &[(i32, i64)],
+    now: i64,
+) -> MilestoneRollup {
+    let tasks: Vec<&MilestoneTask> = tasks
+        .iter()
+        .filter(|task| task.milestone_id == Some(milestone.milestone_id))
+        .collect();
+    let done = |task: &MilestoneTask| Some(task.status.as_str()) == done_state;
+    let done_tasks = tasks.iter().filter(|task| done(task)).count();
+
+    let days: i32 = tasks.iter().map(|task| task.duration).sum();
+    let done_days: i32 = tasks.iter().filter(|task| done(task)).map(|task| task.duration).sum();
+    let percent_complete = if days > 0 {
+        100.0 * done_days as f64 / days as f64
+    } else if !tasks.is_empty() {
+        100.0 * done_tasks as f64 / tasks.len() as f64
+    } else {
+        0.0
+    };
+
+    let open: Vec<&MilestoneTask> = tasks.iter().filter(|task| !done(task)).copied().collect();
+    let overdue_tasks = open
+        .iter()
+        .filter(|task| task.due_date.is_some_and(|due_date| due_date < now))
+        .map(|task| task.task_id)
+        .collect();
+    let forecast_completion = forecast
+        .iter()
+        .filter(|(task_id, _)| open.iter().any(|task| task.task_id == *task_id))
+        .map(|(_, finish)| *finish)
+        .max();
+    let late = match (forecast_completion, milestone.due_date) {
+        (Some(forecast), Some(due_date)) => forecast > due_date,
+        _ => false,
+    };
+
+    MilestoneRollup {
+        milestone,
+        tasks: tasks.len(),
+        done_tasks,
+        percent_complete,
+        overdue_tasks,
+        forecast_completion,
+        late,
+    }
+}
+
+#[get("/api/projects/{id}/milestones")]
+async fn list_milestones(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let milestones = visible_milestones(&state, &tk, &context, id).await?;
+            let json = serde_json::to_string(&milestones)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[post("/api/projects/{id}/milestones")]
+async fn create_milestone(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<MilestoneBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+            if body.name.trim().is_empty() {
+                return Err(ProjectError::InvalidInput("a name is required".to_string()));
+            }
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::CreateMilestone, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let milestone = sqlx::query_as::<_, Milestone>(&format!(
+                "INSERT INTO milestones (project_id, name, description, due_date, created_by)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING {}",
+                MILESTONE_COLUMNS
+            ))
+            .bind(id)
+            .bind(body.name.trim())
+            .bind(&body.description)
+            .bind(body.due_date.map(|due_date| due_date.timestamp()))
+            .bind(tk.id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!("milestone {} {}", milestone.milestone_id, milestone.name);
+            audit::record(&state.db, tk.id, "CreateMilestone", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&milestone)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[put("/api/projects/{id}/milestones/{milestone_id}")]
+async fn update_milestone(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<MilestoneBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, milestone_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let milestone_id = milestone_id.parse::<i32>()?;
+            let body = body.into_inner();
+            if body.name.trim().is_empty() {
+                return Err(ProjectError::InvalidInput("a name is required".to_string()));
+            }
+
+            let resource = milestone_resource(&state.db, id, milestone_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ManageMilestone, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let milestone = sqlx::query_as::<_, Milestone>(&format!(
+                "UPDATE milestones SET name = $1, description = $2, due_date = $3
+                WHERE milestone_id = $4
+                RETURNING {}",
+                MILESTONE_COLUMNS
+            ))
+            .bind(body.name.trim())
+            .bind(&body.description)
+            .bind(body.due_date.map(|due_date| due_date.timestamp()))
+            .bind(milestone_id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!("milestone {} {}", milestone.milestone_id, milestone.name);
+            audit::record(&state.db, tk.id, "UpdateMilestone", &resource.project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&milestone)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Deletes the milestone, its tasks are kept, detached.
+#[delete("/api/projects/{id}/milestones/{milestone_id}")]
+async fn delete_milestone(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, milestone_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let milestone_id = milestone_id.parse::<i32>()?;
+
+            let resource = milestone_resource(&state.db, id, milestone_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ManageMilestone, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let mut tx = state.db.begin().await?;
+            sqlx::query("UPDATE tasks SET milestone_id = NULL WHERE milestone_id = $1")
+                .bind(milestone_id)
+                .execute(&mut *tx)
+                .await?;
+            let milestone = sqlx::query_as::<_, Milestone>(&format!(
+                "DELETE FROM milestones WHERE milestone_id = $1 RETURNING {}",
+                MILESTONE_COLUMNS
+            ))
+            .bind(milestone_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            let detail = format!("milestone {} {}", milestone.milestone_id, milestone.name);
+            audit::record(&state.db, tk.id, "DeleteMilestone", &resource.project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&milestone)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Percent complete, overdue tasks and forecast completion of the milestones the caller may view.
+///
+/// A task is done in the last state of the workflow of the project. The forecast schedules the
+/// open tasks of the project from now, along their dependencies, over the working days of the
+/// organization.
+#[get("/api/projects/{id}/milestones/rollup")]
+async fn milestone_rollup(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let milestones = visible_milestones(&state, &tk, &context, id).await?;
+            let workflow = workflow(&state.db, id).await?;
+            let done_state = workflow.states.last().map(String::as_str);
+            let tasks = sqlx::query_as::<_, MilestoneTask>(
+                "SELECT task_id, status, duration, due_date, milestone_id FROM tasks
+                WHERE project_id = $1 AND deleted_at IS NULL
+                ORDER BY task_id",
+            )
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+
+            // done tasks take no more time
+            let durations: Vec<(i32, i32)> = tasks
+                .iter()
+                .map(|task| {
+                    let duration = if Some(task.status.as_str()) == done_state { 0 } else { task.duration };
+                    (task.task_id, duration)
+                })
+                .collect();
+            let dependencies = project_dependencies(&state.db, id).await?;
+            let schedule = compute_schedule(&durations, &dependencies).ok_or(ProjectError::Unknown)?;
+            let now = Utc::now();
+            // the calendar of the organization of the project, whoever views it
+            let resource = project_resource(&state.db, id).await?;
+            let calendar = organization_calendar(&state.db, resource.tenant).await?;
+            // a task is done at the end of its last working day
+            let forecast: Vec<(i32, i64)> = schedule
+                .tasks
+                .iter()
+                .map(|task| {
+                    let finish = match task.earliest_finish {
+                        0 => now.timestamp(),
+                        days => {
+                            let last_day = calendar.nth_working_day(now.date_naive(), days - 1);
+                            last_day.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() + SECONDS_PER_DAY
+                        }
+                    };
+                    (task.task_id, finish)
+                })
+                .collect();
+
+            let now = now.timestamp();
+            let rollups: Vec<MilestoneRollup> = milestones
+                .into_iter()
+                .map(|milestone| rollup(milestone, &tasks, done_state, &forecast, now))
+                .collect();
+
+            let json = serde_json::to_string(&rollups)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn milestone_rollup_reports_progress() {
+        let app = create_test_app().await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/1/milestones")
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "name": "beta" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/1/milestones")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "name": "beta", "due_date": "2000-01-01T00:00:00Z" }))
+            .to_request();
+        let milestone: Value = test::call_and_read_body_json(&app, req).await;
+        let milestone_id = milestone["milestone_id"].as_i64().unwrap();
+
+        let mut task_ids = vec![];
+        for (title, duration) in [("alpha", 1), ("beta", 3)] {
+            let req = test::TestRequest::post()
+                .uri("/api/projects/1/tasks")
+                .insert_header(bearer(projectlead()))
+                .set_json(serde_json::json!({
+                    "title": title,
+                    "duration": duration,
+                    "due_date": "2000-01-01T00:00:00Z",
+                    "milestone_id": milestone_id,
+                }))
+                .to_request();
+            let task: Value = test::call_and_read_body_json(&app, req).await;
+            task_ids.push(task["task_id"].as_i64().unwrap());
+        }
+        for to in ["in_progress", "done"] {
+            let req = test::TestRequest::post()
+                .uri(&format!("/api/projects/1/tasks/{}/transitions", task_ids[0]))
+                .insert_header(bearer(projectlead()))
+                .set_json(serde_json::json!({ "to": to }))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1/milestones/rollup")
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let rollups: Value = test::call_and_read_body_json(&app, req).await;
+        let rollup = rollups
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|rollup| rollup["milestone_id"].as_i64() == Some(milestone_id))
+            .unwrap();
+        assert_eq!(rollup["tasks"], 2);
+        assert_eq!(rollup["done_tasks"], 1);
+        assert_eq!(rollup["percent_complete"], 25.0);
+        assert_eq!(rollup["overdue_tasks"], serde_json::json!([task_ids[1]]));
+        assert!(rollup["forecast_completion"].as_i64().unwrap() > chrono::Utc::now().timestamp());
+        assert_eq!(rollup["late"], true);
+    }
+}
@@ -1,5 +1,56 @@
+mod access_reviews;
+mod articles;
+mod audit;
+mod break_glass;
+mod budgets;
+mod calendars;
+mod capacity;
+mod delegations;
+mod guests;
+mod milestones;
+mod party_roles;
 mod projects;
+mod reports;
+mod role_requests;
+mod schedule;
+mod sprints;
+mod tasks;
+mod teams;
+mod time_entries;
+mod workflows;
 mod permission;
+#[cfg(test)]
+mod test_support;
 
+pub use access_reviews::{
+    create_access_review_campaign, decide_access_review_item, export_access_review, get_access_review,
+};
+pub use articles::{
+    create_article, delete_article, get_article, list_article_revisions, list_articles, publish_article,
+    restore_article_revision, update_article,
+};
+pub use audit::list_audit_entries;
+pub use break_glass::request_break_glass;
+pub use budgets::{delete_cost_rate, get_budget, list_cost_rates, set_cost_rate};
+pub use calendars::{
+    create_holiday, create_leave, delete_holiday, delete_leave, get_calendar, import_holidays, list_leave,
+    update_weekend,
+};
+pub use capacity::{get_capacity, list_assignments, update_allocation};
+pub use delegations::{create_delegation, list_delegations, revoke_delegation};
+pub use guests::{active_invite, create_guest_invite, list_guest_invites, revoke_guest_invite};
+pub use milestones::{create_milestone, delete_milestone, list_milestones, milestone_rollup, update_milestone};
+pub use party_roles::{active_roles, party_tenant, sweep_expired_party_roles, SWEEP_INTERVAL_SECONDS};
 pub use projects::*;
+pub use reports::{cumulative_flow_report, milestone_burndown, sprint_burndown};
+pub use role_requests::{approve_role_request, create_role_request, list_role_requests, reject_role_request};
+pub use schedule::{create_dependency, delete_dependency, get_schedule, list_dependencies};
+pub use sprints::{close_sprint, commit_sprint_tasks, create_sprint, get_velocity, list_sprints, start_sprint};
+pub use tasks::{create_task, delete_task, get_task, list_tasks, transition_task, update_task};
+pub use teams::{add_team_member, assign_team, create_team, list_teams, remove_team_member, unassign_team};
+pub use time_entries::{
+    approve_timesheet, create_time_entry, delete_time_entry, get_project_time, get_timesheet, list_time_entries,
+    update_time_entry,
+};
+pub use workflows::{get_workflow, update_workflow};
 pub use permission::*;
\ No newline at end of file
@@ -0,0 +1,135 @@
+use super::audit;
+use super::projects::Result;
+
+use crate::services::*;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+/// Interval of the background sweep of expired role assignments.
+pub const SWEEP_INTERVAL_SECONDS: u64 = 15 * 60;
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub struct ExpiredPartyRole {
+    party_role_id: i32,
+    party_id: i32,
+    role: String,
+    /// unix timestamp (seconds)
+    valid_to: i64,
+}
+
+/// The roles of a party valid now, as Cedar role ids.
+pub async fn active_roles(db: &Pool<Any>, party_id: i32) -> Result<Vec<String>> {
+    let now = Utc::now().timestamp();
+    let roles: Vec<(String,)> = sqlx::query_as(
+        "SELECT role_type.name FROM party_role
+        JOIN role_type ON role_type.role_type_id = party_role.role_type_id
+        WHERE party_role.party_id = $1
+        AND (party_role.valid_from IS NULL OR party_role.valid_from <= $2)
+        AND (party_role.valid_to IS NULL OR party_role.valid_to > $2)",
+    )
+    .bind(party_id)
+    .bind(now)
+    .fetch_all(db)
+    .await?;
+
+    Ok(roles.iter().map(|(name,)| role_id(name)).collect())
+}
+
+/// The organization of a party, none for an unknown party.
+pub async fn party_tenant(db: &Pool<Any>, party_id: i32) -> Result<Option<i32>> {
+    let tenant: Option<(i32,)> = sqlx::query_as("SELECT tenant_id FROM parties WHERE party_id = $1")
+        .bind(party_id)
+        .fetch_optional(db)
+        .await?;
+    Ok(tenant.map(|(tenant_id,)| tenant_id))
+}
+
+/// Flags the role assignments expired since the last sweep and records them in the audit trail.
+pub async fn sweep_expired_party_roles(db: &Pool<Any>) -> Result<Vec<ExpiredPartyRole>> {
+    let expired = sqlx::query_as::<_, ExpiredPartyRole>(
+        "UPDATE party_role SET expired = 1
+        WHERE expired = 0 AND valid_to IS NOT NULL AND valid_to <= $1
+        RETURNING party_role_id, party_id,
+        (SELECT name FROM role_type WHERE role_type.role_type_id = party_role.role_type_id) AS role,
+        valid_to",
+    )
+    .bind(Utc::now().timestamp())
+    .fetch_all(db)
+    .await?;
+
+    for grant in &expired {
+        let detail = format!(
+            "role {} (party_role {}) expired at {}",
+            grant.role, grant.party_role_id, grant.valid_to
+        );
+        let party = entity_uid("User", grant.party_id);
+        audit::record(db, grant.party_id, "ExpireRole", &party, &[], &detail).await?;
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use dotenv::dotenv;
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn expired_or_future_role_assignment_is_ignored() {
+        dotenv().ok();
+        sqlx::any::install_default_drivers();
+        let now = chrono::Utc::now().timestamp();
+
+        for (valid_from, valid_to) in [(now - 7200, now - 3600), (now + 3600, now + 7200)] {
+            let app_data = Arc::new(create_app_data().await.unwrap());
+            sqlx::query("UPDATE party_role SET valid_from = $1, valid_to = $2 WHERE party_role_id = 4")
+                .bind(valid_from)
+                .bind(valid_to)
+                .execute(&app_data.db)
+                .await
+                .unwrap();
+            let app = create_test_app_with(app_data).await;
+
+            let resp = test::call_service(&app, get_project_req(developer(), 2)).await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+            let projects: Vec<Value> = test::call_and_read_body_json(&app, view_project_req(developer())).await;
+            assert_eq!(projects, vec![]);
+        }
+    }
+
+    #[actix_web::test]
+    async fn expired_role_assignments_are_swept_once() {
+        dotenv().ok();
+        sqlx::any::install_default_drivers();
+        let app_data = create_app_data().await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+
+        sqlx::query("UPDATE party_role SET valid_to = $1 WHERE party_role_id = 4")
+            .bind(now - 60)
+            .execute(&app_data.db)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE party_role SET valid_to = $1 WHERE party_role_id = 3")
+            .bind(now + 3600)
+            .execute(&app_data.db)
+            .await
+            .unwrap();
+
+        let expired = sweep_expired_party_roles(&app_data.db).await.unwrap();
+        let expired = serde_json::to_value(&expired).unwrap();
+        assert_eq!(
+            expired,
+            serde_json::json!([{ "party_role_id": 4, "party_id": 4, "role": "Developer", "valid_to": now - 60 }])
+        );
+
+        let expired = sweep_expired_party_roles(&app_data.db).await.unwrap();
+        assert!(expired.is_empty());
+    }
+}
@@ -1,18 +1,97 @@
 use std::str::FromStr;
 use cedar_policy::{EntityId, EntityTypeName, EntityUid};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
-    ViewProject
+    ViewProject,
+    ListProject,
+    CreateProject,
+    AuditProject,
+    ShareProject,
+    UpdateProject,
+    ViewProjectField,
+    AssignPartyrole,
+    DelegateProject,
+    PublishProject,
+    RequestBreakGlass,
+    AssignRole,
+    ManageTeam,
+    CreateTask,
+    ViewTask,
+    UpdateTask,
+    DeleteTask,
+    TransitionTask,
+    ManageWorkflow,
+    CreateMilestone,
+    ViewMilestone,
+    ManageMilestone,
+    ManageSprint,
+    LogTime,
+    ViewProjectTime,
+    ApproveTimesheet,
+    ViewTimeEntry,
+    UpdateTimeEntry,
+    DeleteTimeEntry,
+    CreateArticle,
+    ViewArticle,
+    EditArticle,
+    PublishArticle,
+    DeleteArticle,
+    ViewBudget,
+    ManageCostRates,
+    ManageCalendar,
+    ViewCapacity,
 }
 
-impl From<Action> for EntityUid {
-    fn from(action: Action) -> Self {
-        let action_str = match action {
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
             Action::ViewProject => "ViewProject",
+            Action::ListProject => "ListProject",
+            Action::CreateProject => "CreateProject",
+            Action::AuditProject => "AuditProject",
+            Action::ShareProject => "ShareProject",
+            Action::UpdateProject => "UpdateProject",
+            Action::ViewProjectField => "ViewProjectField",
+            Action::AssignPartyrole => "AssignPartyrole",
+            Action::DelegateProject => "DelegateProject",
+            Action::PublishProject => "PublishProject",
+            Action::RequestBreakGlass => "RequestBreakGlass",
+            Action::AssignRole => "AssignRole",
+            Action::ManageTeam => "ManageTeam",
+            Action::CreateTask => "CreateTask",
+            Action::ViewTask => "ViewTask",
+            Action::UpdateTask => "UpdateTask",
+            Action::DeleteTask => "DeleteTask",
+            Action::TransitionTask => "TransitionTask",
+            Action::ManageWorkflow => "ManageWorkflow",
+            Action::CreateMilestone => "CreateMilestone",
+            Action::ViewMilestone => "ViewMilestone",
+            Action::ManageMilestone => "ManageMilestone",
+            Action::ManageSprint => "ManageSprint",
+            Action::LogTime => "LogTime",
+            Action::ViewProjectTime => "ViewProjectTime",
+            Action::ApproveTimesheet => "ApproveTimesheet",
+            Action::ViewTimeEntry => "ViewTimeEntry",
+            Action::UpdateTimeEntry => "UpdateTimeEntry",
+            Action::DeleteTimeEntry => "DeleteTimeEntry",
+            Action::CreateArticle => "CreateArticle",
+            Action::ViewArticle => "ViewArticle",
+            Action::EditArticle => "EditArticle",
+            Action::PublishArticle => "PublishArticle",
+            Action::DeleteArticle => "DeleteArticle",
+            Action::ViewBudget => "ViewBudget",
+            Action::ManageCostRates => "ManageCostRates",
+            Action::ManageCalendar => "ManageCalendar",
+            Action::ViewCapacity => "ViewCapacity",
             // Add other variants here as needed
-        };
+        }
+    }
+}
 
-        let a_eid = EntityId::from_str(action_str).unwrap();
+impl From<Action> for EntityUid {
+    fn from(action: Action) -> Self {
+        let a_eid = EntityId::from_str(action.name()).unwrap();
         let a_name = EntityTypeName::from_str("Action").unwrap();
         EntityUid::from_type_name_and_id(a_name, a_eid)
     }
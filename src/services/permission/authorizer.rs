@@ -1,11 +1,11 @@
 use std::fs;
 
-use super::{action::*, TokenClaims, TokenError};
+use super::{action::*, RequestContext, Resource, RoleHierarchy, TokenClaims, TokenError};
 
 // use cedar_policy::PrincipalConstraint::{Any, Eq, In, Is, IsIn};
 use cedar_policy::{
-    Authorizer, Context, Decision, EntityId, EntityTypeName, EntityUid, Policy, PolicySet, Request,
-    RequestBuilder,
+    Authorizer, ContextJsonError, Decision, Effect, EntityUid, ParseErrors, Policy, PolicySet,
+    PolicySetError, Request, RequestBuilder,
     Schema, /*SlotId, Template,*/
             //ValidationMode, ValidationResult, Validator,
 };
@@ -23,6 +23,12 @@ pub enum AuthorizerError {
     RequestValidationError(cedar_policy_validator::RequestValidationError),
     #[from]
     TokenError(TokenError),
+    #[from]
+    ContextError(ContextJsonError),
+    #[from]
+    PolicySetError(PolicySetError),
+    #[from]
+    ParseErrors(ParseErrors),
 }
 
 impl core::fmt::Display for AuthorizerError {
@@ -50,10 +56,38 @@ pub enum ResourceAuthorizationResult {
     Residual(Vec<Policy>),
 }
 
+impl ResourceAuthorizationResult {
+    /// Ids of the residual policies with the given effect: a residual `forbid`
+    /// must be checked against each resource before a residual `permit` grants it.
+    pub fn policy_ids(&self, effect: Effect) -> Vec<String> {
+        match self {
+            ResourceAuthorizationResult::Residual(policies) => policies
+                .iter()
+                .filter(|p| p.effect() == effect)
+                .filter_map(|p| p.annotation("id").map(|id| id.to_string()))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Outcome of `Permission::authorize`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authorization {
+    pub allowed: bool,
+    /// `@id` of the policies that determined the decision
+    pub policy_ids: Vec<String>,
+    /// One of the determining policies is annotated with `@audit`
+    pub audit: bool,
+    /// One of the determining policies is annotated with `@alert`
+    pub alert: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Permission {
     policies: PolicySet,
     schema: Schema,
+    roles: RoleHierarchy,
 }
 
 impl Default for Permission {
@@ -72,6 +106,7 @@ impl Default for Permission {
         Self {
             policies,
             schema: schema,
+            roles: RoleHierarchy::default(),
         }
     }
 }
@@ -87,52 +122,85 @@ impl Permission {
             println!("{:?}", w);
         }
 
-        Self { policies, schema }
+        Self {
+            policies,
+            schema,
+            roles: RoleHierarchy::default(),
+        }
     }
 
-    fn project() -> EntityUid {
-        let r_eid = EntityId::from_str("1").unwrap(); // does not go through the parser
-        let r_name: EntityTypeName = EntityTypeName::from_str("Project").unwrap(); // through parse_name(s)
-        EntityUid::from_type_name_and_id(r_name, r_eid)
+    /// A copy of this permission with additional policies, e.g. the delegations of a party.
+    pub fn with_policies(&self, policies: impl IntoIterator<Item = Policy>) -> Result<Self> {
+        let mut permission = self.clone();
+        for policy in policies {
+            permission.policies.add(policy)?;
+        }
+        Ok(permission)
     }
 
-    pub fn is_authorized<T>(
+    /// This permission with the role inheritance loaded from `role_type`.
+    pub fn with_role_hierarchy(self, roles: RoleHierarchy) -> Self {
+        Self { roles, ..self }
+    }
+
+    pub fn is_authorized<R: Resource>(
         &self,
         token_claims: &TokenClaims,
         action: Action,
-        _resource: &T,
+        resource: &R,
+        context: &RequestContext,
     ) -> Result<bool> {
+        self.authorize(token_claims, action, resource, context)
+            .map(|authorization| authorization.allowed)
+    }
+
+    pub fn authorize<R: Resource>(
+        &self,
+        token_claims: &TokenClaims,
+        action: Action,
+        resource: &R,
+        context: &RequestContext,
+    ) -> Result<Authorization> {
         let authorizer = Authorizer::new();
 
         let p = token_claims.user().map(|u| u.uid())?;
         let a: EntityUid = action.into();
-        let r = Permission::project();
+        let r = resource.uid();
 
         let request: Request = Request::new(
             Some(p),
             Some(a),
             Some(r),
-            Context::empty(),
+            context.to_context()?,
             Some(&self.schema),
         )?;
 
-        let entities = token_claims.entities(Some(&self.schema)).unwrap();
+        let entities = token_claims.resource_entities(resource, &self.roles, Some(&self.schema))?;
 
         let ans = authorizer.is_authorized(&request, &self.policies, &entities);
 
-        for reason in ans.diagnostics().reason() {
-            //print all the annotations
-            for (key, value) in self.policies.policy(reason).unwrap().annotations() {
-                println!("PolicyID: {}\tKey:{} \tValue:{}", reason, key, value);
-            }
-        }
-        Ok(ans.decision() == Decision::Allow)
+        let reasons: Vec<&Policy> = ans
+            .diagnostics()
+            .reason()
+            .filter_map(|reason| self.policies.policy(reason))
+            .collect();
+
+        Ok(Authorization {
+            allowed: ans.decision() == Decision::Allow,
+            policy_ids: reasons
+                .iter()
+                .map(|p| p.annotation("id").map_or_else(|| p.id().to_string(), |id| id.to_string()))
+                .collect(),
+            audit: reasons.iter().any(|p| p.annotation("audit").is_some()),
+            alert: reasons.iter().any(|p| p.annotation("alert").is_some()),
+        })
     }
 
     pub fn get_policies(
         &self,
         token_claims: &TokenClaims,
         action: Action,
+        context: &RequestContext,
     ) -> Result<ResourceAuthorizationResult> {
         dotenv().ok();
 
@@ -142,9 +210,10 @@ impl Permission {
         let request: Request = RequestBuilder::default()
             .principal(Some(user.uid()))
             .action(Some(action.into()))
+            .context(context.to_context()?)
             .build();
 
-        let entities = token_claims.entities(Some(&self.schema)).unwrap();
+        let entities = token_claims.entities(&self.roles, Some(&self.schema)).unwrap();
 
         let ans = authorizer.is_authorized_partial(&request, &self.policies, &entities);
         match ans.decision() {
@@ -161,7 +230,8 @@ impl Permission {
 #[cfg(test)]
 mod tests {
 
-    use crate::services::TokenClaims;
+    use crate::services::{ProjectResource, TaskResource, TokenClaims};
+    use chrono::{DateTime, TimeZone, Utc};
 
     use super::*;
 
@@ -196,6 +266,45 @@ permit(
 )
 when { principal in resource.assigned_to };"#;
 
+    const CORPORATE_NETWORK_POLICY: &str = r#"@id("CorporateNetworkPolicy")
+permit(
+  principal in Role::"Administrator",
+  action == Action::"AuditProject",
+  resource
+)
+when { context has client_ip && context.client_ip.isInRange(ip("10.0.0.0/8")) };"#;
+
+    const BUSINESS_HOURS_POLICY: &str = r#"@id("BusinessHoursPolicy")
+permit(
+  principal in Role::"ProjectLead",
+  action == Action::"ShareProject",
+  resource
+)
+when { context.weekday <= 5 && context.hour >= 8 && context.hour < 18 };"#;
+
+    const READONLY_POLICY: &str = r#"@id("ReadOnlyProjectPolicy")
+forbid(
+  principal,
+  action in [Action::"UpdateProject", Action::"AssignPartyrole", Action::"ShareProject"],
+  resource
+)
+when { resource.status == "archived" || resource.legal_hold };"#;
+
+    fn project(status: &str, legal_hold: bool) -> ProjectResource {
+        ProjectResource {
+            id: 1,
+            owner: 1,
+            assignees: vec![],
+            project_roles: vec![],
+            status: status.to_string(),
+            legal_hold,
+            public: false,
+            tenant: 1,
+            teams: vec![],
+            team_members: vec![],
+        }
+    }
+
     fn evaluate(
         token_claims: TokenClaims,
         policies: &str,
@@ -204,7 +313,29 @@ when { principal in resource.assigned_to };"#;
         dotenv().ok();
 
         let permission = Permission::new(policies);
-        permission.get_policies(&token_claims, action)
+        permission.get_policies(&token_claims, action, &RequestContext::default())
+    }
+
+    fn authorize_at(
+        policies: &str,
+        role: &str,
+        action: Action,
+        resource: &ProjectResource,
+        client_ip: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let token_claims = TokenClaims {
+            id: 1,
+            roles: vec![role.to_string()],
+            ..Default::default()
+        };
+        let client_ip = client_ip.map(|ip| ip.parse().unwrap());
+        let context = RequestContext::new(&token_claims, client_ip, now);
+
+        let permission = Permission::new(policies);
+        permission
+            .is_authorized(&token_claims, action, resource, &context)
+            .unwrap()
     }
 
     #[test]
@@ -212,6 +343,7 @@ when { principal in resource.assigned_to };"#;
         let token_claims = TokenClaims {
             id: 1,
             roles: vec!["Administrator".to_string()],
+            ..Default::default()
         };
         let ans = evaluate(token_claims, ADMIN_POLICY, Action::ViewProject);
         assert!(ans.is_ok(), "ans is ok");
@@ -227,6 +359,7 @@ when { principal in resource.assigned_to };"#;
         let token_claims = TokenClaims {
             id: 1,
             roles: vec!["ProjectLead".to_string()],
+            ..Default::default()
         };
         let ans = evaluate(token_claims, PROJECTLEAD_POLICY, Action::ViewProject);
         assert!(ans.is_ok(), "ans is ok");
@@ -244,6 +377,7 @@ when { principal in resource.assigned_to };"#;
         let token_claims = TokenClaims {
             id: 1,
             roles: vec!["ProjectLead".to_string()],
+            ..Default::default()
         };
 
         let ans = evaluate(
@@ -270,6 +404,7 @@ when { principal in resource.assigned_to };"#;
         let token_claims = TokenClaims {
             id: 1,
             roles: vec!["ProjectLead".to_string(), "Developer".to_string()],
+            ..Default::default()
         };
 
         let all_policies = format!(
@@ -295,4 +430,211 @@ when { principal in resource.assigned_to };"#;
             }
         }
     }
+
+    #[test]
+    fn audit_is_allowed_from_corporate_network() {
+        let allowed = authorize_at(
+            CORPORATE_NETWORK_POLICY,
+            "Administrator",
+            Action::AuditProject,
+            &project("active", false),
+            Some("10.1.2.3"),
+            Utc::now(),
+        );
+        assert!(allowed);
+    }
+
+    #[test]
+    fn audit_is_denied_outside_corporate_network() {
+        for client_ip in [Some("192.168.1.10"), None] {
+            let allowed = authorize_at(
+                CORPORATE_NETWORK_POLICY,
+                "Administrator",
+                Action::AuditProject,
+                &project("active", false),
+                client_ip,
+                Utc::now(),
+            );
+            assert!(!allowed, "client_ip {:?} should be denied", client_ip);
+        }
+    }
+
+    #[test]
+    fn share_is_denied_outside_business_hours() {
+        // 2024-06-03 is a Monday
+        let monday_morning = Utc.with_ymd_and_hms(2024, 6, 3, 10, 0, 0).unwrap();
+        let monday_night = Utc.with_ymd_and_hms(2024, 6, 3, 22, 0, 0).unwrap();
+        let saturday_morning = Utc.with_ymd_and_hms(2024, 6, 8, 10, 0, 0).unwrap();
+
+        let resource = project("active", false);
+        let share_at = |now| {
+            authorize_at(
+                BUSINESS_HOURS_POLICY,
+                "ProjectLead",
+                Action::ShareProject,
+                &resource,
+                None,
+                now,
+            )
+        };
+        assert!(share_at(monday_morning));
+        assert!(!share_at(monday_night));
+        assert!(!share_at(saturday_morning));
+    }
+
+    #[test]
+    fn owner_can_share_active_project() {
+        let policies = format!("{}\n{}\n", PROJECTLEAD_PROJECT_POLICY, READONLY_POLICY);
+        let allowed = authorize_at(
+            &policies,
+            "ProjectLead",
+            Action::ShareProject,
+            &project("active", false),
+            None,
+            Utc::now(),
+        );
+        assert!(allowed);
+    }
+
+    #[test]
+    fn archived_or_held_project_is_read_only() {
+        let policies = format!("{}\n{}\n", PROJECTLEAD_PROJECT_POLICY, READONLY_POLICY);
+        for resource in [project("archived", false), project("active", true)] {
+            let allowed = authorize_at(
+                &policies,
+                "ProjectLead",
+                Action::ShareProject,
+                &resource,
+                None,
+                Utc::now(),
+            );
+            assert!(!allowed, "{:?} should be read-only", resource);
+        }
+    }
+
+    #[test]
+    fn forbid_policy_is_residual() {
+        let token_claims = TokenClaims {
+            id: 1,
+            roles: vec!["ProjectLead".to_string()],
+            ..Default::default()
+        };
+        let policies = format!("{}\n{}\n", PROJECTLEAD_PROJECT_POLICY, READONLY_POLICY);
+
+        let ans = evaluate(token_claims, &policies, Action::ShareProject).unwrap();
+        assert_eq!(ans.policy_ids(Effect::Permit), vec!["ProjectLeadPolicy.Project"]);
+        assert_eq!(ans.policy_ids(Effect::Forbid), vec!["ReadOnlyProjectPolicy"]);
+    }
+
+    #[test]
+    fn projectlead_inherits_developer_policy() {
+        let token_claims = TokenClaims {
+            id: 1,
+            roles: vec!["ProjectLead".to_string()],
+            ..Default::default()
+        };
+        let resource = ProjectResource {
+            assignees: vec![1],
+            project_roles: vec![(1, "Contributor".to_string())],
+            ..project("active", false)
+        };
+        let context = RequestContext::default();
+
+        let permission = Permission::new(DEVELOPER_POLICY);
+        assert!(!permission
+            .is_authorized(&token_claims, Action::ViewProject, &resource, &context)
+            .unwrap());
+
+        let permission = permission
+            .with_role_hierarchy(RoleHierarchy::new([("Project Lead", "Developer"), ("Developer", "User")]));
+        assert!(permission
+            .is_authorized(&token_claims, Action::ViewProject, &resource, &context)
+            .unwrap());
+        let ans = permission
+            .get_policies(&token_claims, Action::ViewProject, &context)
+            .unwrap();
+        assert_eq!(ans.policy_ids(Effect::Permit), vec!["DeveloperPolicy"]);
+    }
+
+    #[test]
+    fn project_role_grants_project_actions() {
+        let token_claims = TokenClaims {
+            id: 1,
+            roles: vec!["User".to_string()],
+            ..Default::default()
+        };
+        let context = RequestContext::default();
+        let permission = Permission::default();
+        let assigned_as = |project_role: &str| ProjectResource {
+            id: 1,
+            owner: 2,
+            assignees: vec![1],
+            project_roles: vec![(1, project_role.to_string())],
+            status: "active".to_string(),
+            legal_hold: false,
+            public: false,
+            tenant: 1,
+            teams: vec![],
+            team_members: vec![],
+        };
+        let allowed = |project_role: &str, action: Action| {
+            permission
+                .is_authorized(&token_claims, action, &assigned_as(project_role), &context)
+                .unwrap()
+        };
+
+        assert!(allowed("Viewer", Action::ViewProject));
+        assert!(!allowed("Viewer", Action::UpdateProject));
+        assert!(allowed("Reviewer", Action::AuditProject));
+        assert!(!allowed("Reviewer", Action::UpdateProject));
+        assert!(allowed("Contributor", Action::UpdateProject));
+        assert!(!allowed("Contributor", Action::ShareProject));
+        assert!(allowed("Maintainer", Action::ShareProject));
+        assert!(!allowed("Contributor", Action::PublishProject));
+        assert!(!allowed("Maintainer", Action::DelegateProject));
+        assert!(!allowed("Maintainer", Action::PublishProject));
+    }
+
+    #[test]
+    fn task_permissions_cascade_from_project() {
+        let token_claims = TokenClaims {
+            id: 1,
+            roles: vec!["User".to_string()],
+            ..Default::default()
+        };
+        let context = RequestContext::default();
+        let permission = Permission::default();
+        let task = |project: ProjectResource, assignee: Option<i32>| TaskResource {
+            id: 1,
+            project,
+            status: "todo".to_string(),
+            assignee,
+            milestone: None,
+        };
+        let assigned_as = |project_role: &str, status: &str| ProjectResource {
+            assignees: vec![1],
+            project_roles: vec![(1, project_role.to_string())],
+            owner: 2,
+            ..project(status, false)
+        };
+        let allowed = |action: Action, task: &TaskResource| {
+            permission
+                .is_authorized(&token_claims, action, task, &context)
+                .unwrap()
+        };
+
+        let viewer_task = task(assigned_as("Viewer", "active"), None);
+        assert!(allowed(Action::ViewTask, &viewer_task));
+        assert!(!allowed(Action::UpdateTask, &viewer_task));
+        assert!(allowed(Action::UpdateTask, &task(assigned_as("Viewer", "active"), Some(1))));
+
+        let contributor_task = task(assigned_as("Contributor", "active"), None);
+        assert!(allowed(Action::UpdateTask, &contributor_task));
+        assert!(!allowed(Action::DeleteTask, &contributor_task));
+        assert!(allowed(Action::DeleteTask, &task(assigned_as("Maintainer", "active"), None)));
+        assert!(!allowed(Action::UpdateTask, &task(assigned_as("Maintainer", "archived"), None)));
+
+        let unassigned_task = task(ProjectResource { owner: 2, ..project("active", false) }, None);
+        assert!(!allowed(Action::ViewTask, &unassigned_task));
+    }
 }
@@ -0,0 +1,158 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
+use super::TokenClaims;
+
+use actix_web::dev::ServiceRequest;
+use cedar_policy::{Context, ContextJsonError};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde_json::{json, Map, Value};
+
+/// Request attributes exposed to the policies as `context`.
+///
+/// The shape must match the `RequestContext` type declared in
+/// `projects.cedarschema`, otherwise the request is rejected by the schema.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestContext {
+    /// Unix timestamp (seconds, UTC) of the request
+    pub now: i64,
+    /// Hour of the day (0-23, UTC)
+    pub hour: i64,
+    /// Day of the week, Monday = 1 ... Sunday = 7
+    pub weekday: i64,
+    /// Client address: the peer of the connection, or the address it forwards when it is a
+    /// trusted proxy (see `client_ip`)
+    pub client_ip: Option<IpAddr>,
+    pub auth_method: String,
+    pub mfa: bool,
+    pub break_glass: bool,
+    /// Field being read, for `ViewProjectField`
+    pub field: Option<String>,
+    /// (from, to) task states, for `TransitionTask`
+    pub transition: Option<(String, String)>,
+}
+
+impl RequestContext {
+    pub fn new(token_claims: &TokenClaims, client_ip: Option<IpAddr>, now: DateTime<Utc>) -> Self {
+        Self {
+            now: now.timestamp(),
+            hour: now.hour() as i64,
+            weekday: now.weekday().number_from_monday() as i64,
+            client_ip,
+            auth_method: token_claims.auth_method.clone(),
+            mfa: token_claims.mfa,
+            break_glass: token_claims.break_glass,
+            field: None,
+            transition: None,
+        }
+    }
+
+    pub fn with_field(&self, field: &str) -> Self {
+        Self {
+            field: Some(field.to_string()),
+            ..self.clone()
+        }
+    }
+
+    pub fn with_transition(&self, from: &str, to: &str) -> Self {
+        Self {
+            transition: Some((from.to_string(), to.to_string())),
+            ..self.clone()
+        }
+    }
+
+    pub fn from_request(req: &ServiceRequest, token_claims: &TokenClaims) -> Self {
+        let peer = req.peer_addr().map(|addr| addr.ip());
+        let forwarded_for = req
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok());
+        let client_ip = client_ip(peer, forwarded_for, trusted_proxies());
+        Self::new(token_claims, client_ip, Utc::now())
+    }
+
+    pub fn to_context(&self) -> std::result::Result<Context, ContextJsonError> {
+        let mut attrs = Map::new();
+        attrs.insert("now".to_string(), json!(self.now));
+        attrs.insert("hour".to_string(), json!(self.hour));
+        attrs.insert("weekday".to_string(), json!(self.weekday));
+        attrs.insert("auth_method".to_string(), json!(self.auth_method));
+        attrs.insert("mfa".to_string(), json!(self.mfa));
+        attrs.insert("break_glass".to_string(), json!(self.break_glass));
+        if let Some(ip) = self.client_ip {
+            attrs.insert(
+                "client_ip".to_string(),
+                json!({ "__extn": { "fn": "ip", "arg": ip.to_string() } }),
+            );
+        }
+        if let Some(field) = &self.field {
+            attrs.insert("field".to_string(), json!(field));
+        }
+        if let Some((from, to)) = &self.transition {
+            attrs.insert("transition".to_string(), json!({ "from": from, "to": to }));
+        }
+        Context::from_json_value(Value::Object(attrs), None)
+    }
+}
+
+/// Proxies whose `X-Forwarded-For` header is honored, from the comma-separated `TRUSTED_PROXIES`
+/// environment variable. None by default: the client is the peer of the connection.
+fn trusted_proxies() -> &'static [IpAddr] {
+    static TRUSTED_PROXIES: OnceLock<Vec<IpAddr>> = OnceLock::new();
+    TRUSTED_PROXIES.get_or_init(|| {
+        std::env::var("TRUSTED_PROXIES")
+            .map(|proxies| proxies.split(',').filter_map(|proxy| parse_ip(proxy.trim())).collect())
+            .unwrap_or_default()
+    })
+}
+
+/// The client behind `peer`: `peer` itself unless it is a trusted proxy, then the last address of
+/// `X-Forwarded-For` that is not one, the addresses before it being set by the client.
+fn client_ip(peer: Option<IpAddr>, forwarded_for: Option<&str>, trusted: &[IpAddr]) -> Option<IpAddr> {
+    let mut client = peer?;
+    if !trusted.contains(&client) {
+        return Some(client);
+    }
+    for addr in forwarded_for.unwrap_or_default().rsplit(',') {
+        match parse_ip(addr.trim()) {
+            Some(ip) => client = ip,
+            None => break,
+        }
+        if !trusted.contains(&client) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+fn parse_ip(addr: &str) -> Option<IpAddr> {
+    addr.parse::<SocketAddr>()
+        .map(|socket| socket.ip())
+        .or_else(|_| addr.parse::<IpAddr>())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_is_only_honored_from_trusted_proxies() {
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        // a client cannot claim another address
+        let spoofed = Some("10.1.2.3");
+        assert_eq!(client_ip(Some(ip("203.0.113.7")), spoofed, &trusted), Some(ip("203.0.113.7")));
+        assert_eq!(client_ip(Some(ip("203.0.113.7")), spoofed, &[]), Some(ip("203.0.113.7")));
+
+        // behind the proxies, the addresses the client prepends are ignored
+        let forwarded = Some("10.1.2.3, 203.0.113.7, 10.0.0.2");
+        assert_eq!(client_ip(Some(ip("10.0.0.1")), forwarded, &trusted), Some(ip("203.0.113.7")));
+        assert_eq!(client_ip(Some(ip("10.0.0.1")), None, &trusted), Some(ip("10.0.0.1")));
+        assert_eq!(client_ip(None, forwarded, &trusted), None);
+    }
+}
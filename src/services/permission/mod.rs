@@ -1,7 +1,13 @@
 mod authorizer;
 mod action;
+mod context;
+mod resource;
+mod role;
 mod token;
 
 pub use action::*;
 pub use authorizer::*;
+pub use context::*;
+pub use resource::*;
+pub use role::*;
 pub use token::*;
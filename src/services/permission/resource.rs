@@ -0,0 +1,408 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use super::token::Result;
+
+use cedar_policy::{Entity, EntityId, EntityTypeName, EntityUid, RestrictedExpression};
+
+const ENTITY_TYPE_GROUP: &str = "Group";
+const ENTITY_TYPE_PROJECT: &str = "Project";
+const ENTITY_TYPE_USER: &str = "User";
+const ENTITY_TYPE_ORGANIZATION: &str = "Organization";
+const ENTITY_TYPE_TASK: &str = "Task";
+const ENTITY_TYPE_MILESTONE: &str = "Milestone";
+const ENTITY_TYPE_TIME_ENTRY: &str = "TimeEntry";
+const ENTITY_TYPE_ARTICLE: &str = "Article";
+
+const GROUP_ALL_PROJECTS: &str = "AllProjects";
+
+/// Roles of an assignment on a project, each exposed as the `<role>s` group of the project.
+pub const PROJECT_ROLES: [&str; 4] = ["Contributor", "Reviewer", "Viewer", "Maintainer"];
+
+/// A resource that authorization requests can be evaluated against.
+pub trait Resource {
+    fn uid(&self) -> EntityUid;
+
+    /// Entities required to evaluate the policies, the resource itself included.
+    fn entities(&self) -> Result<Vec<Entity>>;
+
+    /// Groups the principal belongs to through this resource.
+    fn groups(&self, _principal_id: i32) -> Vec<EntityUid> {
+        vec![]
+    }
+}
+
+pub(crate) fn entity_uid(entity_type: &str, id: impl ToString) -> EntityUid {
+    let type_name = EntityTypeName::from_str(entity_type).unwrap();
+    EntityUid::from_type_name_and_id(type_name, EntityId::new(id.to_string()))
+}
+
+/// A `Group` entity, e.g. `Group::"AllProjects"` for the actions covering every project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupResource {
+    pub name: String,
+}
+
+impl GroupResource {
+    pub fn all_projects() -> Self {
+        GroupResource {
+            name: GROUP_ALL_PROJECTS.to_string(),
+        }
+    }
+}
+
+impl Resource for GroupResource {
+    fn uid(&self) -> EntityUid {
+        entity_uid(ENTITY_TYPE_GROUP, &self.name)
+    }
+
+    fn entities(&self) -> Result<Vec<Entity>> {
+        Ok(vec![Entity::with_uid(self.uid())])
+    }
+}
+
+/// The `Project` entity as declared in `projects.cedarschema`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectResource {
+    pub id: i32,
+    /// party_id of the owner
+    pub owner: i32,
+    /// party_id of the assigned parties
+    pub assignees: Vec<i32>,
+    /// (party_id, project role) of the assignments
+    pub project_roles: Vec<(i32, String)>,
+    pub status: String,
+    pub legal_hold: bool,
+    /// Published, readable by anybody
+    pub public: bool,
+    /// `tenant_id` of the organization owning the project
+    pub tenant: i32,
+    /// (team_id, project role) of the teams assigned to the project
+    pub teams: Vec<(i32, String)>,
+    /// (team_id, party_id) of the members of these teams
+    pub team_members: Vec<(i32, i32)>,
+}
+
+impl ProjectResource {
+    fn assignees_group(&self) -> EntityUid {
+        entity_uid(ENTITY_TYPE_GROUP, format!("Project{}_assignees", self.id))
+    }
+
+    /// `Group::"Project1_maintainers"` for the maintainers of project 1.
+    fn project_role_group(&self, project_role: &str) -> EntityUid {
+        entity_uid(
+            ENTITY_TYPE_GROUP,
+            format!("Project{}_{}", self.id, project_role_attr(project_role)),
+        )
+    }
+}
+
+/// The `Project` attribute of a project role, e.g. `maintainers`.
+pub(crate) fn project_role_attr(project_role: &str) -> String {
+    format!("{}s", project_role.to_lowercase())
+}
+
+impl Resource for ProjectResource {
+    fn uid(&self) -> EntityUid {
+        entity_uid(ENTITY_TYPE_PROJECT, self.id)
+    }
+
+    fn entities(&self) -> Result<Vec<Entity>> {
+        let all_projects = entity_uid(ENTITY_TYPE_GROUP, GROUP_ALL_PROJECTS);
+        let mut attrs: HashMap<String, RestrictedExpression> = HashMap::from([
+            (
+                "owner".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_USER, self.owner)),
+            ),
+            (
+                "assigned_to".to_string(),
+                RestrictedExpression::new_entity_uid(self.assignees_group()),
+            ),
+            (
+                "status".to_string(),
+                RestrictedExpression::new_string(self.status.clone()),
+            ),
+            (
+                "legal_hold".to_string(),
+                RestrictedExpression::new_bool(self.legal_hold),
+            ),
+            (
+                "public".to_string(),
+                RestrictedExpression::new_bool(self.public),
+            ),
+            (
+                "tenant".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_ORGANIZATION, self.tenant)),
+            ),
+        ]);
+        for project_role in PROJECT_ROLES {
+            attrs.insert(
+                project_role_attr(project_role),
+                RestrictedExpression::new_entity_uid(self.project_role_group(project_role)),
+            );
+        }
+        let project = Entity::new(self.uid(), attrs, HashSet::from([all_projects.clone()]))?;
+
+        let mut entities = vec![
+            project,
+            Entity::with_uid(self.assignees_group()),
+            Entity::with_uid(all_projects),
+        ];
+        entities.extend(
+            PROJECT_ROLES
+                .iter()
+                .map(|project_role| Entity::with_uid(self.project_role_group(project_role))),
+        );
+        // an assigned team is an assignee, with its project role
+        for (team_id, project_role) in &self.teams {
+            let team = TeamResource {
+                id: *team_id,
+                tenant: self.tenant,
+                created_by: None,
+            };
+            let parents = HashSet::from([self.assignees_group(), self.project_role_group(project_role)]);
+            entities.push(team.entity(parents)?);
+        }
+        Ok(entities)
+    }
+
+    fn groups(&self, principal_id: i32) -> Vec<EntityUid> {
+        let mut groups = vec![];
+        if self.assignees.contains(&principal_id) {
+            groups.push(self.assignees_group());
+        }
+        groups.extend(
+            self.project_roles
+                .iter()
+                .filter(|(party_id, _)| *party_id == principal_id)
+                .map(|(_, project_role)| self.project_role_group(project_role)),
+        );
+        groups.extend(
+            self.team_members
+                .iter()
+                .filter(|(_, party_id)| *party_id == principal_id)
+                .map(|(team_id, _)| team_uid(*team_id)),
+        );
+        groups
+    }
+}
+
+fn team_uid(team_id: i32) -> EntityUid {
+    entity_uid(ENTITY_TYPE_GROUP, format!("Team{}", team_id))
+}
+
+/// A team, the `Group::"Team<team_id>"` entity of its organization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeamResource {
+    pub id: i32,
+    /// `tenant_id` of the organization of the team
+    pub tenant: i32,
+    /// party_id of the creator, only needed to manage the team
+    pub created_by: Option<i32>,
+}
+
+impl TeamResource {
+    fn entity(&self, parents: HashSet<EntityUid>) -> Result<Entity> {
+        let mut attrs: HashMap<String, RestrictedExpression> = HashMap::from([(
+            "tenant".to_string(),
+            RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_ORGANIZATION, self.tenant)),
+        )]);
+        if let Some(created_by) = self.created_by {
+            attrs.insert(
+                "created_by".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_USER, created_by)),
+            );
+        }
+        Ok(Entity::new(self.uid(), attrs, parents)?)
+    }
+}
+
+impl Resource for TeamResource {
+    fn uid(&self) -> EntityUid {
+        team_uid(self.id)
+    }
+
+    fn entities(&self) -> Result<Vec<Entity>> {
+        Ok(vec![self.entity(HashSet::new())?])
+    }
+}
+
+/// The `Task` entity, child of its `Project` whose entities are evaluated along.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskResource {
+    pub id: i32,
+    pub project: ProjectResource,
+    pub status: String,
+    /// party_id of the assignee
+    pub assignee: Option<i32>,
+    /// milestone_id of the milestone the task is attached to
+    pub milestone: Option<i32>,
+}
+
+impl Resource for TaskResource {
+    fn uid(&self) -> EntityUid {
+        entity_uid(ENTITY_TYPE_TASK, self.id)
+    }
+
+    fn entities(&self) -> Result<Vec<Entity>> {
+        let mut attrs: HashMap<String, RestrictedExpression> = HashMap::from([
+            (
+                "project".to_string(),
+                RestrictedExpression::new_entity_uid(self.project.uid()),
+            ),
+            (
+                "status".to_string(),
+                RestrictedExpression::new_string(self.status.clone()),
+            ),
+            (
+                "tenant".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_ORGANIZATION, self.project.tenant)),
+            ),
+        ]);
+        if let Some(assignee) = self.assignee {
+            attrs.insert(
+                "assignee".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_USER, assignee)),
+            );
+        }
+        let mut parents = HashSet::from([self.project.uid()]);
+        let milestone = self.milestone.map(|id| MilestoneResource {
+            id,
+            project: self.project.clone(),
+        });
+        if let Some(milestone) = &milestone {
+            parents.insert(milestone.uid());
+        }
+        let task = Entity::new(self.uid(), attrs, parents)?;
+
+        let mut entities = vec![task];
+        match milestone {
+            Some(milestone) => entities.extend(milestone.entities()?),
+            None => entities.extend(self.project.entities()?),
+        }
+        Ok(entities)
+    }
+
+    fn groups(&self, principal_id: i32) -> Vec<EntityUid> {
+        self.project.groups(principal_id)
+    }
+}
+
+/// The `Milestone` entity, child of its `Project` whose entities are evaluated along.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MilestoneResource {
+    pub id: i32,
+    pub project: ProjectResource,
+}
+
+impl Resource for MilestoneResource {
+    fn uid(&self) -> EntityUid {
+        entity_uid(ENTITY_TYPE_MILESTONE, self.id)
+    }
+
+    fn entities(&self) -> Result<Vec<Entity>> {
+        let attrs: HashMap<String, RestrictedExpression> = HashMap::from([
+            (
+                "project".to_string(),
+                RestrictedExpression::new_entity_uid(self.project.uid()),
+            ),
+            (
+                "tenant".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_ORGANIZATION, self.project.tenant)),
+            ),
+        ]);
+        let milestone = Entity::new(self.uid(), attrs, HashSet::from([self.project.uid()]))?;
+
+        let mut entities = vec![milestone];
+        entities.extend(self.project.entities()?);
+        Ok(entities)
+    }
+
+    fn groups(&self, principal_id: i32) -> Vec<EntityUid> {
+        self.project.groups(principal_id)
+    }
+}
+
+/// The `TimeEntry` entity, child of its `Project` whose entities are evaluated along.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeEntryResource {
+    pub id: i32,
+    pub project: ProjectResource,
+    /// party_id of the party the time was logged by
+    pub party: i32,
+    /// The timesheet of the week of the entry is approved
+    pub locked: bool,
+}
+
+impl Resource for TimeEntryResource {
+    fn uid(&self) -> EntityUid {
+        entity_uid(ENTITY_TYPE_TIME_ENTRY, self.id)
+    }
+
+    fn entities(&self) -> Result<Vec<Entity>> {
+        let attrs: HashMap<String, RestrictedExpression> = HashMap::from([
+            (
+                "project".to_string(),
+                RestrictedExpression::new_entity_uid(self.project.uid()),
+            ),
+            (
+                "party".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_USER, self.party)),
+            ),
+            ("locked".to_string(), RestrictedExpression::new_bool(self.locked)),
+            (
+                "tenant".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_ORGANIZATION, self.project.tenant)),
+            ),
+        ]);
+        let entry = Entity::new(self.uid(), attrs, HashSet::from([self.project.uid()]))?;
+
+        let mut entities = vec![entry];
+        entities.extend(self.project.entities()?);
+        Ok(entities)
+    }
+
+    fn groups(&self, principal_id: i32) -> Vec<EntityUid> {
+        self.project.groups(principal_id)
+    }
+}
+
+/// The `Article` entity, a page of the wiki of its `Project` whose entities are evaluated along.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArticleResource {
+    pub id: i32,
+    pub project: ProjectResource,
+    /// The page is published, a draft otherwise
+    pub published: bool,
+}
+
+impl Resource for ArticleResource {
+    fn uid(&self) -> EntityUid {
+        entity_uid(ENTITY_TYPE_ARTICLE, self.id)
+    }
+
+    fn entities(&self) -> Result<Vec<Entity>> {
+        let attrs: HashMap<String, RestrictedExpression> = HashMap::from([
+            (
+                "project".to_string(),
+                RestrictedExpression::new_entity_uid(self.project.uid()),
+            ),
+            ("published".to_string(), RestrictedExpression::new_bool(self.published)),
+            (
+                "tenant".to_string(),
+                RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_ORGANIZATION, self.project.tenant)),
+            ),
+        ]);
+        let article = Entity::new(self.uid(), attrs, HashSet::from([self.project.uid()]))?;
+
+        let mut entities = vec![article];
+        entities.extend(self.project.entities()?);
+        Ok(entities)
+    }
+
+    fn groups(&self, principal_id: i32) -> Vec<EntityUid> {
+        self.project.groups(principal_id)
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::{HashMap, HashSet};
+
+use cedar_policy::{Entity, EntityUid};
+use sqlx::{self, Any, Pool};
+
+use super::resource::entity_uid;
+
+const ENTITY_TYPE_ROLE: &str = "Role";
+
+/// Role inheritance: each role is a member of the roles it implies, e.g. `ProjectLead`
+/// is in `Developer`, so `principal in Role::"Developer"` also holds for project leads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoleHierarchy {
+    parents: HashMap<String, Vec<String>>,
+}
+
+impl RoleHierarchy {
+    /// Builds the hierarchy from (role, implied role) pairs.
+    pub fn new<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+        for (role, parent) in pairs {
+            parents
+                .entry(role_id(role))
+                .or_default()
+                .push(role_id(parent));
+        }
+        Self { parents }
+    }
+
+    /// Loads the `parent_role_type_id` relation of `role_type`.
+    pub async fn load(db: &Pool<Any>) -> sqlx::Result<Self> {
+        let pairs: Vec<(String, String)> = sqlx::query_as(
+            "SELECT role_type.name, parent.name FROM role_type
+            JOIN role_type parent ON parent.role_type_id = role_type.parent_role_type_id",
+        )
+        .fetch_all(db)
+        .await?;
+        Ok(Self::new(
+            pairs.iter().map(|(role, parent)| (role.as_str(), parent.as_str())),
+        ))
+    }
+
+    /// The `Role` entities of the given roles and of every role they imply, with their parents.
+    pub fn entities(&self, roles: &[String]) -> Vec<Entity> {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut pending: Vec<String> = roles.iter().map(|role| role_id(role)).collect();
+        let mut entities = vec![];
+        while let Some(role) = pending.pop() {
+            if !seen.insert(role.clone()) {
+                continue;
+            }
+            let parents = self.parents.get(&role).cloned().unwrap_or_default();
+            let parent_uids: HashSet<EntityUid> = parents
+                .iter()
+                .map(|parent| entity_uid(ENTITY_TYPE_ROLE, parent))
+                .collect();
+            entities.push(Entity::new_no_attrs(entity_uid(ENTITY_TYPE_ROLE, &role), parent_uids));
+            pending.extend(parents);
+        }
+        entities
+    }
+}
+
+/// The Cedar id of a role type, `role_type.name` without spaces ("Project Lead" is `ProjectLead`).
+pub(crate) fn role_id(name: &str) -> String {
+    name.split_whitespace().collect()
+}
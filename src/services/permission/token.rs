@@ -2,6 +2,7 @@ use cedar_policy::{
     Entities, EntitiesError, Entity, EntityAttrEvaluationError, EntityId, EntityTypeName,
     EntityUid, RestrictedExpression, Schema,
 };
+use chrono::Utc;
 use derive_more::From;
 use hmac::{Hmac, Mac};
 use jwt::{SignWithKey, VerifyWithKey};
@@ -12,6 +13,8 @@ use std::{
     str::FromStr,
 };
 
+use super::{resource::entity_uid, Resource, RoleHierarchy};
+
 pub type Result<T> = std::result::Result<T, TokenError>;
 
 #[allow(dead_code)]
@@ -23,6 +26,7 @@ pub enum TokenError {
     EntitiesError(EntitiesError),
     #[from]
     JwtError(jwt::Error),
+    Expired,
 }
 
 impl core::fmt::Display for TokenError {
@@ -36,31 +40,121 @@ impl std::error::Error for TokenError {}
 // const ENTITY_TYPE_GROUP: &str = "Group";
 // const ENTITY_TYPE_ROLE: &str = "Role";
 const ENTITY_TYPE_USER: &str = "User";
-// const ENTITY_TYPE_PROJECT: &str = "Project";
+const ENTITY_TYPE_GUEST: &str = "Guest";
+
+/// Principal of the requests without token
+const ANONYMOUS_USER: &str = "anonymous";
+const ANONYMOUS_ROLE: &str = "User";
+const ENTITY_TYPE_PROJECT: &str = "Project";
+const ENTITY_TYPE_ORGANIZATION: &str = "Organization";
+
+/// Organization of the tokens issued before tenants, and of the anonymous requests
+pub const DEFAULT_TENANT: i32 = 1;
+
+fn default_tenant() -> i32 {
+    DEFAULT_TENANT
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TokenClaims {
     pub id: i32,
     pub roles: Vec<String>,
+    /// `tenant_id` of the organization of the party
+    #[serde(default = "default_tenant")]
+    pub tenant_id: i32,
+    #[serde(default)]
+    pub auth_method: String,
+    #[serde(default)]
+    pub mfa: bool,
+    /// Emergency elevation granted through the break-glass flow
+    #[serde(default)]
+    pub break_glass: bool,
+    /// Expiration (unix timestamp, seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    /// Project of an invite link: the principal is then `Guest::"<id>"`, `id` being the invite id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_project: Option<i32>,
+    /// Request without token, never read from a token
+    #[serde(skip)]
+    pub anonymous: bool,
+}
+
+impl Default for TokenClaims {
+    fn default() -> Self {
+        TokenClaims {
+            id: 0,
+            roles: vec![],
+            tenant_id: DEFAULT_TENANT,
+            auth_method: String::new(),
+            mfa: false,
+            break_glass: false,
+            exp: None,
+            guest_project: None,
+            anonymous: false,
+        }
+    }
 }
 
 impl TokenClaims {
     pub fn user(&self) -> Result<Entity> {
+        self.user_in(std::iter::empty())
+    }
+
+    /// The claims of a request without token, evaluated as `User::"anonymous"`.
+    pub fn anonymous() -> Self {
+        TokenClaims {
+            roles: vec![ANONYMOUS_ROLE.to_string()],
+            anonymous: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_guest(&self) -> bool {
+        self.guest_project.is_some()
+    }
+
+    /// The user entity, member of its roles and of the given groups.
+    ///
+    /// A guest is a `Guest` entity scoped to the project of its invite, member of nothing.
+    pub fn user_in(&self, groups: impl IntoIterator<Item = EntityUid>) -> Result<Entity> {
+        if let Some(project) = self.guest_project {
+            let attrs: HashMap<String, RestrictedExpression> = HashMap::from([
+                (
+                    "project".to_string(),
+                    RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_PROJECT, project)),
+                ),
+                ("tenant".to_string(), self.tenant()),
+            ]);
+            let guest = Entity::new(entity_uid(ENTITY_TYPE_GUEST, self.id), attrs, HashSet::new())?;
+            return Ok(guest);
+        }
+
         let user_type = EntityTypeName::from_str(ENTITY_TYPE_USER).unwrap();
 
-        let euid =
-            EntityUid::from_type_name_and_id(user_type.clone(), EntityId::new(self.id.to_string()));
-        let attrs: HashMap<String, RestrictedExpression> = HashMap::new();
+        let id = if self.anonymous {
+            ANONYMOUS_USER.to_string()
+        } else {
+            self.id.to_string()
+        };
+        let euid = EntityUid::from_type_name_and_id(user_type.clone(), EntityId::new(id));
+        let attrs: HashMap<String, RestrictedExpression> =
+            HashMap::from([("tenant".to_string(), self.tenant())]);
         /*from([
             ("age".to_string(), RestrictedExpression::from_str("21").unwrap()),
             ("department".to_string(), RestrictedExpression::from_str("\"CS\"").unwrap()),
         ]);*/
 
-        let parents: HashSet<EntityUid> = self.roles_ids().collect();
+        let parents: HashSet<EntityUid> = self.roles_ids().chain(groups).collect();
         let u = Entity::new(euid, attrs, parents)?;
         Ok(u)
     }
 
+    /// The `Organization` of the principal.
+    fn tenant(&self) -> RestrictedExpression {
+        RestrictedExpression::new_entity_uid(entity_uid(ENTITY_TYPE_ORGANIZATION, self.tenant_id))
+    }
+
     pub fn roles_ids(&self) -> impl Iterator<Item = EntityUid> {
         let role_type = EntityTypeName::from_str("Role").unwrap();
         self.roles
@@ -69,13 +163,27 @@ impl TokenClaims {
             .map(move |r| EntityUid::from_type_name_and_id(role_type.clone(), EntityId::new(r)))
     }
 
-    pub fn roles(&self) -> impl Iterator<Item = Entity> {
-        self.roles_ids().map(|e| Entity::with_uid(e))
+    /// The role entities, including the roles implied through the hierarchy.
+    pub fn roles(&self, hierarchy: &RoleHierarchy) -> impl Iterator<Item = Entity> {
+        hierarchy.entities(&self.roles).into_iter()
     }
 
-    pub fn entities(&self, schema: Option<&Schema>) -> Result<Entities> {
+    pub fn entities(&self, hierarchy: &RoleHierarchy, schema: Option<&Schema>) -> Result<Entities> {
         let u = self.user()?;
-        let iter = self.roles().chain(vec![u]);
+        let iter = self.roles(hierarchy).chain(vec![u]);
+        let entities = Entities::from_entities(iter, schema)?;
+        Ok(entities)
+    }
+
+    /// Entities of the user, its roles and the resource being accessed.
+    pub fn resource_entities<R: Resource>(
+        &self,
+        resource: &R,
+        hierarchy: &RoleHierarchy,
+        schema: Option<&Schema>,
+    ) -> Result<Entities> {
+        let u = self.user_in(resource.groups(self.id))?;
+        let iter = self.roles(hierarchy).chain(vec![u]).chain(resource.entities()?);
         let entities = Entities::from_entities(iter, schema)?;
         Ok(entities)
     }
@@ -114,6 +222,9 @@ impl TokenService {
     pub async fn verify(&self, bearer_token: &str) -> Result<TokenClaims> {
         let key: Hmac<Sha256> = Hmac::new_from_slice(self.jwt_secret.as_bytes()).unwrap();
         let claims: TokenClaims = bearer_token.verify_with_key(&key)?;
-        Ok(claims)
+        match claims.exp {
+            Some(exp) if exp <= Utc::now().timestamp() => Err(TokenError::Expired),
+            _ => Ok(claims),
+        }
     }
 }
@@ -4,16 +4,20 @@ use crate::AppState;
 use actix_web::{
     error, get,
     http::{header::ContentType, StatusCode},
-    post,
+    post, put,
     web::{self, Data, Json, ReqData},
     HttpResponse, Responder,
 };
 
 use crate::services::*;
 
+use super::{audit, delegations, workflows};
+
+use cedar_policy::Effect;
+use chrono::Utc;
 use derive_more::From;
 use serde::{Deserialize, Serialize};
-use sqlx::{self, FromRow};
+use sqlx::{self, Any, FromRow, Pool};
 
 #[derive(Deserialize)]
 struct CreateProjectBody {
@@ -21,13 +25,64 @@ struct CreateProjectBody {
     description: String,
 }
 
-#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+#[derive(Deserialize)]
+struct UpdateProjectBody {
+    name: String,
+    description: String,
+    status: String,
+    /// Confidential, unchanged when missing
+    #[serde(default)]
+    budget: Option<f64>,
+    /// Confidential, unchanged when missing
+    #[serde(default)]
+    internal_description: Option<String>,
+    /// Confidential ISO 4217 code, unchanged when missing
+    #[serde(default)]
+    budget_currency: Option<String>,
+    /// Publishes (or unpublishes) the project, unchanged when missing
+    #[serde(default)]
+    public: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Default)]
+#[serde(default)] // redacted fields are missing
 struct Project {
     id: i32,
     name: String,
     description: String,
+    status: String,
+    budget: f64,
+    budget_currency: String,
+    internal_description: String,
+}
+
+#[derive(FromRow)]
+struct ProjectOwner {
+    id: i32,
+    owner: i32,
+    status: String,
+    legal_hold: i32,
+    public: i32,
+    tenant_id: i32,
 }
 
+const PROJECT_STATUSES: [&str; 2] = ["active", "archived"];
+
+/// Project fields readable by the owner and auditors only, the budget by the owner only, see
+/// `ProjectFieldPolicy`.
+pub(crate) const CONFIDENTIAL_FIELDS: [&str; 3] = ["budget", "budget_currency", "internal_description"];
+
+/// Residual policies `list_projects` translates to SQL.
+const SQL_POLICIES: [&str; 4] = [
+    "ProjectLeadPolicy.Project",
+    "DeveloperPolicy",
+    "PublicProjectPolicy",
+    "ProjectRolePolicy.Viewer",
+];
+
+/// Residual forbid policies `list_projects` translates to SQL.
+const SQL_FORBIDS: [&str; 1] = ["TenantPolicy"];
+
 pub type Result<T> = std::result::Result<T, ProjectError>;
 
 #[allow(dead_code)]
@@ -43,8 +98,11 @@ pub enum ProjectError {
     Sqlx(sqlx::Error),
     #[from]
     Serde(serde_json::Error),
+    InvalidInput(String),
     #[from]
     TokenError(TokenError),
+    #[from]
+    AuthorizerError(AuthorizerError),
 }
 
 impl core::fmt::Display for ProjectError {
@@ -64,33 +122,59 @@ impl error::ResponseError for ProjectError {
 
     fn status_code(&self) -> StatusCode {
         match *self {
-            ProjectError::Parse(_) => StatusCode::BAD_REQUEST,
+            ProjectError::Parse(_) | ProjectError::InvalidInput(_) => StatusCode::BAD_REQUEST,
             ProjectError::AuthFailed => StatusCode::FORBIDDEN,
             ProjectError::Unknown
             | ProjectError::Io(_)
             | ProjectError::Sqlx(_)
             | ProjectError::Serde(_)
-            | ProjectError::TokenError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            | ProjectError::TokenError(_)
+            | ProjectError::AuthorizerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
+/// Creates a project of the organization of the caller, owned by the caller.
 #[post("/api/projects")]
-async fn create_project(state: Data<Arc<AppState>>, body: Json<CreateProjectBody>) -> impl Responder {
-    let project = body.into_inner();
+async fn create_project(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    body: Json<CreateProjectBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let body = body.into_inner();
 
-    match sqlx::query_as::<_, Project>(
-        "INSERT INTO projects (name, description, owned_by, created_by, updated_by)
-        VALUES ($1, $2, 2, 2, 2)
-        RETURNING id, name, description",
-    )
-    .bind(project.name)
-    .bind(project.description)
-    .fetch_one(&state.db)
-    .await
-    {
-        Ok(project) => HttpResponse::Ok().json(project),
-        Err(error) => HttpResponse::InternalServerError().json(format!("Error: {:?}", error)),
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::CreateProject, &GroupResource::all_projects(), &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let mut tx = state.db.begin().await?;
+            let project = sqlx::query_as::<_, Project>(
+                "INSERT INTO projects (name, description, owned_by, created_by, updated_by, tenant_id)
+                VALUES ($1, $2, $3, $3, $3, $4)
+                RETURNING id, name, description, status, budget, budget_currency, internal_description",
+            )
+            .bind(body.name)
+            .bind(body.description)
+            .bind(tk.id)
+            .bind(tk.tenant_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            workflows::seed_default_workflow(&mut tx, project.id).await?;
+            tx.commit().await?;
+
+            let resource = project_resource(&state.db, project.id).await?;
+            let detail = format!("project {}", project.name);
+            audit::record(&state.db, tk.id, "CreateProject", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&project)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
     }
 }
 
@@ -100,74 +184,44 @@ async fn create_project(state: Data<Arc<AppState>>, body: Json<CreateProjectBody
 mod tests {
 
     use super::*;
-    use crate::repository::{Migrate, Repository};
-    use crate::validator;
-    use actix_http::Request;
-    use actix_web::dev::Service;
-    use actix_web::http::header::ContentType;
+    use crate::services::test_support::*;
     use actix_web::test;
-    use actix_web::App;
-    use actix_web_httpauth::middleware::HttpAuthentication;
-    use dotenv::dotenv;
     use serde_json::Value;
-    use sqlx::any::AnyPoolOptions;
-    use sqlx::{Any, Pool};
-
-    async fn create_app_data() -> Result<AppState> {
-        dotenv().ok();
 
-        let db_url = "sqlite::memory:";
-
-        let pool: Pool<Any> = {
-            AnyPoolOptions::new()
-            .max_connections(1)
-            .connect(&db_url)
-            .await.unwrap()
+    #[actix_web::test]
+    async fn projectlead_creates_its_own_projects() {
+        let app = create_test_app().await;
+        let create_project_req = |token_claims: TokenClaims| {
+            test::TestRequest::post()
+                .uri("/api/projects")
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({ "name": "new project", "description": "a new project" }))
+                .to_request()
         };
 
-        let repo = Repository::new(&pool, "./sqlite-migrations").await;
-        
-        let migration_error = repo.migrate().await.map_err(|err| err.to_string());
-        assert_eq!(migration_error, Ok(()));
+        let project: Value = test::call_and_read_body_json(&app, create_project_req(projectlead())).await;
+        assert_eq!(project["name"], "new project");
+        let id = project["id"].as_i64().unwrap() as i32;
+        let resp = test::call_service(&app, get_project_req(projectlead(), id)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/projects/{}/workflow", id))
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let workflow: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(workflow["states"], serde_json::json!(["todo", "in_progress", "done"]));
+        let resp = test::call_service(&app, get_project_req(developer(), id)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
 
-        Ok(AppState {
-            db: pool.clone(),
-            permission: Permission::default(),
-        })
-    }
+        let resp = test::call_service(&app, create_project_req(developer())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
 
-    async fn create_test_app() -> impl Service<
-        actix_http::Request,
-        Response = actix_web::dev::ServiceResponse,
-        Error = actix_web::Error>
-     {
-        dotenv().ok();
-        sqlx::any::install_default_drivers();
-        let bearer_middleware = HttpAuthentication::bearer(validator);
-       
-        let app_data = Arc::new(create_app_data().await.unwrap());
-
-        test::init_service(
-            App::new()
-                .app_data(Data::new(app_data.clone()))
-                .service(
-                    web::scope("")
-                        .wrap(bearer_middleware.clone())
-                        .service(list_projects),
-                ),
-        )
-        .await
-    }
-
-    fn view_project_req(token_claims: TokenClaims) -> Request {
-        let token = TokenService::generate_token(token_claims).unwrap();
-        
-        let req = test::TestRequest::get().uri("/api/projects")
-            .insert_header(ContentType::plaintext())
-            .insert_header(("Authorization", format!("Bearer {}", token)))
+        let req = test::TestRequest::post()
+            .uri("/api/projects")
+            .set_json(serde_json::json!({ "name": "new project", "description": "a new project" }))
             .to_request();
-
-        req
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
     }
 
     #[actix_web::test]
@@ -176,7 +230,8 @@ mod tests {
 
         let token_claims = TokenClaims { 
             id: 2,
-            roles: vec!["Administrator".to_string()]
+            roles: vec!["Administrator".to_string()],
+            ..Default::default()
         };
         
         let req = view_project_req(token_claims);
@@ -191,15 +246,16 @@ mod tests {
 
         let token_claims = TokenClaims { 
             id: 3,
-            roles: vec!["ProjectLead".to_string()]
+            roles: vec!["ProjectLead".to_string()],
+            ..Default::default()
         };
         
         let req = view_project_req(token_claims);
         let projects: Vec<Project> = test::call_and_read_body_json(&app, req).await;
 
         assert_eq!(projects, vec![
-            Project { id: 1, name: "my project".to_string(), description: "this project".to_string() },
-            Project { id: 2, name: "my other project".to_string(), description: "that project".to_string() },
+            Project { id: 1, name: "my project".to_string(), description: "this project".to_string(), status: "active".to_string(), ..Default::default() },
+            Project { id: 2, name: "my other project".to_string(), description: "that project".to_string(), status: "active".to_string(), ..Default::default() },
         ]);    
     }
 
@@ -209,18 +265,146 @@ mod tests {
 
         let token_claims = TokenClaims { 
             id: 4,
-            roles: vec!["Developer".to_string()]
+            roles: vec!["Developer".to_string()],
+            ..Default::default()
         };
         
         let req = view_project_req(token_claims);
         let projects: Vec<Project> = test::call_and_read_body_json(&app, req).await;
 
         assert_eq!(projects, vec![
-            Project { id: 2, name: "my other project".to_string(), description: "that project".to_string() },
+            Project { id: 2, name: "my other project".to_string(), description: "that project".to_string(), status: "active".to_string(), ..Default::default() },
         ]);  
 
     }
 
+    #[actix_web::test]
+    async fn developer_can_view_assigned_project_only() {
+        let app = create_test_app().await;
+
+        let req = test::TestRequest::get().uri("/api/projects/2")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/api/projects/1")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn archived_project_is_read_only() {
+        let app = create_test_app().await;
+
+        let req = update_project_req(projectlead(), 1, "archived");
+        let project: Project = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(project.status, "archived");
+
+        let req = update_project_req(projectlead(), 1, "active");
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn confidential_fields_are_redacted_for_assignees() {
+        let app = create_test_app().await;
+
+        let req = update_project_req(projectlead(), 2, "active");
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let project: Value = test::call_and_read_body_json(&app, get_project_req(projectlead(), 2)).await;
+        assert_eq!(project["budget"], 1000.0);
+        assert_eq!(project["internal_description"], "internal");
+
+        let project: Value = test::call_and_read_body_json(&app, get_project_req(developer(), 2)).await;
+        assert_eq!(project["name"], "my project");
+        assert!(project.get("budget").is_none());
+        assert!(project.get("internal_description").is_none());
+
+        // contributors update the project but not its confidential fields
+        let resp = test::call_service(&app, update_project_req(developer(), 2, "active")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::put()
+            .uri("/api/projects/2")
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "name": "renamed", "description": "this project", "status": "active" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let project: Value = test::call_and_read_body_json(&app, get_project_req(projectlead(), 2)).await;
+        assert_eq!(project["name"], "renamed");
+        assert_eq!(project["budget"], 1000.0);
+        assert_eq!(project["internal_description"], "internal");
+    }
+
+    #[actix_web::test]
+    async fn anonymous_can_view_public_projects() {
+        let app = create_test_app().await;
+
+        let projectlead = TokenClaims {
+            id: 3,
+            roles: vec!["ProjectLead".to_string()],
+            ..Default::default()
+        };
+        let anonymous_req = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+        let resp = test::call_service(&app, anonymous_req("/api/projects/1")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::put()
+            .uri("/api/projects/1")
+            .insert_header(bearer(projectlead))
+            .set_json(serde_json::json!({
+                "name": "my project",
+                "description": "this project",
+                "status": "active",
+                "budget": 1000.0,
+                "public": true,
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let project: Value = test::call_and_read_body_json(&app, anonymous_req("/api/projects/1")).await;
+        assert_eq!(project["name"], "my project");
+        assert!(project.get("budget").is_none());
+
+        let projects: Vec<Value> = test::call_and_read_body_json(&app, anonymous_req("/api/projects")).await;
+        let ids: Vec<&Value> = projects.iter().map(|project| &project["id"]).collect();
+        assert_eq!(ids, vec![1]);
+
+        let resp = test::call_service(&app, anonymous_req("/api/projects/2")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        // contributors update the project but do not publish it
+        let developer = TokenClaims {
+            id: 4,
+            roles: vec!["Developer".to_string()],
+            ..Default::default()
+        };
+        let req = test::TestRequest::put()
+            .uri("/api/projects/2")
+            .insert_header(bearer(developer))
+            .set_json(serde_json::json!({
+                "name": "my other project",
+                "description": "that project",
+                "status": "active",
+                "public": true,
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let resp = test::call_service(&app, anonymous_req("/api/projects/2")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
 }
 
 #[get("/")]
@@ -237,47 +421,90 @@ async fn index_post() -> impl Responder {
 async fn list_projects(
     state: Data<Arc<AppState>>,
     token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
 ) -> Result<String> {
-    match token_claims {
-        Some(token_claims) => {
-            let tk = TokenClaims {
-                id: token_claims.id,
-                roles: token_claims.roles.clone(),
-            };
-            let ans = state.permission.get_policies(&tk, Action::ViewProject);
-
-            let policies = match ans {
-                Ok(ResourceAuthorizationResult::Deny) => return Ok("[]".to_string()),
-                Ok(ResourceAuthorizationResult::Residual(policies)) => {
-                    let ids = policies.iter().filter_map(|p| p.annotation("id").map(|id| id.to_string())).collect();
-                    ids
-                },
-                _ => vec![]
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let permission = delegations::permission_for(&state, &tk).await?;
+
+            // a guest may view the project of its invite, not list it (GuestPolicy.Forbid)
+            if let Some(project_id) = tk.guest_project {
+                let resource = project_resource(&state.db, project_id).await?;
+                if !permission.is_authorized(&tk, Action::ListProject, &resource, &context)? {
+                    return Err(ProjectError::AuthFailed);
+                }
+            }
+
+            let ans = permission.get_policies(&tk, Action::ViewProject, &context)?;
+
+            let (policies, forbids) = match &ans {
+                ResourceAuthorizationResult::Deny => return Ok("[]".to_string()),
+                ResourceAuthorizationResult::Allow => (vec![], vec![]),
+                residual => (
+                    residual.policy_ids(Effect::Permit),
+                    residual.policy_ids(Effect::Forbid),
+                ),
             };
 
             let projectlead_project = policies.contains(&"ProjectLeadPolicy.Project".to_string());
             let developer_project = policies.contains(&"DeveloperPolicy".to_string());
-
-            let sql_result =
-                sqlx::query_as::<_, Project>("SELECT id, name, description FROM projects
+            let public_project = policies.contains(&"PublicProjectPolicy".to_string());
+            let viewer_project = policies.contains(&"ProjectRolePolicy.Viewer".to_string());
+            // residual policies without SQL translation are evaluated on each project, as are
+            // wholesale allows (break-glass) so that each access is audited
+            let checked = ans == ResourceAuthorizationResult::Allow
+                || forbids.iter().any(|id| !SQL_FORBIDS.contains(&id.as_str()))
+                || policies.iter().any(|id| !SQL_POLICIES.contains(&id.as_str()));
+
+            let projects =
+                sqlx::query_as::<_, Project>("SELECT DISTINCT projects.id, name, description, projects.status, projects.budget, projects.budget_currency, projects.internal_description FROM projects
+                JOIN party_role owner ON owner.party_role_id = projects.owned_by              -- project lead policy
                 LEFT JOIN assignments ON assignments.project_id = projects.id                -- developer policy
                 LEFT JOIN party_role ON assignments.party_role_id = party_role.party_role_id -- developer policy
-                WHERE (NOT $1 OR projects.owned_by = $2) AND                                 -- project lead policy
-                (NOT $3 OR party_role.party_id = $2);                                        -- developer policy
+                LEFT JOIN team_assignments ON team_assignments.project_id = projects.id      -- assigned teams
+                LEFT JOIN team_members ON team_members.team_id = team_assignments.team_id    -- assigned teams
+                WHERE (projects.tenant_id = $8 OR $9) AND ($1 OR                             -- tenant policy, anonymous excepted
+                ($2 AND owner.party_id = $3                                                  -- project lead policy
+                    AND (owner.valid_from IS NULL OR owner.valid_from <= $5)
+                    AND (owner.valid_to IS NULL OR owner.valid_to > $5)) OR
+                ($4 AND party_role.party_id = $3                                             -- developer policy
+                    AND (party_role.valid_from IS NULL OR party_role.valid_from <= $5)
+                    AND (party_role.valid_to IS NULL OR party_role.valid_to > $5)) OR
+                ($4 AND team_members.party_id = $3) OR                                       -- developer policy, teams
+                ($6 AND projects.public = 1) OR                                              -- public project policy
+                ($7 AND party_role.party_id = $3 AND assignments.project_role = 'Viewer'     -- viewer project role policy
+                    AND (party_role.valid_from IS NULL OR party_role.valid_from <= $5)
+                    AND (party_role.valid_to IS NULL OR party_role.valid_to > $5)) OR
+                ($7 AND team_members.party_id = $3 AND team_assignments.project_role = 'Viewer')) -- viewer project role policy, teams
+                ORDER BY projects.id;
                 ")
+                    .bind(checked)
                     .bind(projectlead_project)
-                    .bind(tk.id.clone())
+                    .bind(tk.id)
                     .bind(developer_project)
+                    .bind(context.now)
+                    .bind(public_project)
+                    .bind(viewer_project)
+                    .bind(tk.tenant_id)
+                    .bind(tk.anonymous)
                     .fetch_all(&state.db)
-                    .await;
-
-            let err = match &sql_result {
-                Err(err) => err.to_string(),
-                Ok(_) => "".to_string()
-            };
-            let projects = sql_result?;
+                    .await?;
+
+            let mut redacted = vec![];
+            for project in projects {
+                let resource = project_resource(&state.db, project.id).await?;
+                if checked {
+                    let authorization = permission.authorize(&tk, Action::ViewProject, &resource, &context)?;
+                    if !authorization.allowed {
+                        continue;
+                    }
+                    audit::record_authorization(&state.db, &tk, Action::ViewProject, &resource, &authorization).await?;
+                }
+                redacted.push(redact(&permission, &tk, &context, &resource, &project)?);
+            }
 
-            let json = serde_json::to_string(&projects)?;
+            let json = serde_json::to_string(&redacted)?;
             Ok(json)
         }
         _ => Err(ProjectError::AuthFailed),
@@ -288,32 +515,102 @@ async fn list_projects(
 async fn get_project(
     state: Data<Arc<AppState>>,
     token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
     path: web::Path<String>,
 ) -> Result<String> {
-    match token_claims {
-        Some(token_claims) => {
-            let tk = TokenClaims {
-                id: token_claims.id,
-                roles: token_claims.roles.clone(),
-            };
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
             let project_id: String = path.into_inner();
             let id = project_id.parse::<i64>()?;
             let project = sqlx::query_as::<_, Project>(
-                "SELECT id, name, description from projects
+                "SELECT id, name, description, status, budget, budget_currency, internal_description from projects
                 WHERE id = $1",
             )
             .bind(id)
             .fetch_one(&state.db)
             .await?;
 
-            if state
-                .permission
-                .is_authorized(&tk, Action::ViewProject, &project)
-                .is_err()
-            {
+            let resource = project_resource(&state.db, project.id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            let authorization = permission.authorize(&tk, Action::ViewProject, &resource, &context)?;
+            if !authorization.allowed {
+                return Err(ProjectError::AuthFailed);
+            }
+            audit::record_authorization(&state.db, &tk, Action::ViewProject, &resource, &authorization).await?;
+
+            let project = redact(&permission, &tk, &context, &resource, &project)?;
+            let json = serde_json::to_string(&project)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[put("/api/projects/{id}")]
+async fn update_project(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<UpdateProjectBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+            if !PROJECT_STATUSES.contains(&body.status.as_str()) {
+                return Err(ProjectError::InvalidInput(format!("unknown status {}", body.status)));
+            }
+            if let Some(currency) = &body.budget_currency {
+                if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+                    return Err(ProjectError::InvalidInput(format!("unknown currency {}", currency)));
+                }
+            }
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::UpdateProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            // confidential fields are only written by who may read them
+            let given = [
+                body.budget.is_some(),
+                body.budget_currency.is_some(),
+                body.internal_description.is_some(),
+            ];
+            for (field, _) in CONFIDENTIAL_FIELDS.iter().zip(given).filter(|(_, given)| *given) {
+                if !permission.is_authorized(&tk, Action::ViewProjectField, &resource, &context.with_field(field))? {
+                    return Err(ProjectError::AuthFailed);
+                }
+            }
+            // only the owner publishes the project
+            if body.public.is_some() && !permission.is_authorized(&tk, Action::PublishProject, &resource, &context)? {
                 return Err(ProjectError::AuthFailed);
             }
 
+            let project = sqlx::query_as::<_, Project>(
+                "UPDATE projects SET name = $1, description = $2, status = $3,
+                budget = COALESCE($4, budget), internal_description = COALESCE($5, internal_description),
+                public = COALESCE($7, public), budget_currency = COALESCE($8, budget_currency),
+                updated_at = CURRENT_TIMESTAMP
+                WHERE id = $6
+                RETURNING id, name, description, status, budget, budget_currency, internal_description",
+            )
+            .bind(body.name)
+            .bind(body.description)
+            .bind(body.status)
+            .bind(body.budget)
+            .bind(body.internal_description)
+            .bind(id)
+            .bind(body.public.map(i32::from))
+            .bind(body.budget_currency)
+            .fetch_one(&state.db)
+            .await?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let project = redact(&permission, &tk, &context, &resource, &project)?;
             let json = serde_json::to_string(&project)?;
             Ok(json)
         }
@@ -321,6 +618,94 @@ async fn get_project(
     }
 }
 
+/// Serializes the project without the fields the principal may not read (`ViewProjectField`).
+fn redact(
+    permission: &Permission,
+    token_claims: &TokenClaims,
+    context: &RequestContext,
+    resource: &ProjectResource,
+    project: &Project,
+) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(project)?;
+    if let serde_json::Value::Object(fields) = &mut value {
+        let mut hidden = vec![];
+        for field in fields.keys().filter(|field| *field != "id") {
+            let context = context.with_field(field);
+            if !permission.is_authorized(token_claims, Action::ViewProjectField, resource, &context)? {
+                hidden.push(field.clone());
+            }
+        }
+        for field in hidden {
+            fields.remove(&field);
+        }
+    }
+    Ok(value)
+}
+
+/// Loads the Cedar `Project` entity: owner, assignees and their project roles, assigned teams,
+/// status, legal hold and publication.
+///
+/// Owner and assignees only count through the party roles valid now, an expired or future
+/// owner role leaves the project without owner (party 0).
+pub(crate) async fn project_resource(db: &Pool<Any>, id: i32) -> Result<ProjectResource> {
+    let now = Utc::now().timestamp();
+    let project = sqlx::query_as::<_, ProjectOwner>(
+        "SELECT projects.id,
+        CASE WHEN (party_role.valid_from IS NULL OR party_role.valid_from <= $2)
+            AND (party_role.valid_to IS NULL OR party_role.valid_to > $2)
+            THEN party_role.party_id ELSE 0 END AS owner,
+        projects.status, projects.legal_hold, projects.public, projects.tenant_id
+        FROM projects
+        JOIN party_role ON party_role.party_role_id = projects.owned_by
+        WHERE projects.id = $1",
+    )
+    .bind(id)
+    .bind(now)
+    .fetch_one(db)
+    .await?;
+
+    let assignees: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT party_role.party_id, assignments.project_role FROM assignments
+        JOIN party_role ON assignments.party_role_id = party_role.party_role_id
+        WHERE assignments.project_id = $1
+        AND (party_role.valid_from IS NULL OR party_role.valid_from <= $2)
+        AND (party_role.valid_to IS NULL OR party_role.valid_to > $2)",
+    )
+    .bind(id)
+    .bind(now)
+    .fetch_all(db)
+    .await?;
+
+    let teams: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT team_id, project_role FROM team_assignments WHERE project_id = $1",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    let team_members: Vec<(i32, i32)> = sqlx::query_as(
+        "SELECT team_members.team_id, team_members.party_id FROM team_members
+        JOIN team_assignments ON team_assignments.team_id = team_members.team_id
+        WHERE team_assignments.project_id = $1",
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(ProjectResource {
+        id: project.id,
+        owner: project.owner,
+        assignees: assignees.iter().map(|(party_id, _)| *party_id).collect(),
+        project_roles: assignees,
+        status: project.status,
+        legal_hold: project.legal_hold != 0,
+        public: project.public != 0,
+        tenant: project.tenant_id,
+        teams,
+        team_members,
+    })
+}
+
 #[get("/status")]
 async fn status(_state: Data<AppState>) -> impl Responder {
     HttpResponse::Ok().json(r#"{ "status": "Ok" }"#)
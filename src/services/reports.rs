@@ -0,0 +1,442 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    get,
+    web::{self, Data, Query, ReqData},
+};
+
+use crate::services::*;
+
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+use super::workflows::workflow;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+/// Longest range of a report, in days.
+const MAX_REPORT_DAYS: i64 = 366;
+
+#[derive(Deserialize)]
+struct RangeQuery {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+#[derive(FromRow, Debug, Clone, PartialEq, Eq)]
+struct StatusChange {
+    task_id: i32,
+    to_state: String,
+    /// unix timestamp (seconds)
+    changed_at: i64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct BurndownPoint {
+    date: NaiveDate,
+    /// Days of the tasks not done at the end of the day, none for the days to come
+    remaining: Option<i32>,
+    remaining_tasks: Option<usize>,
+    /// Remaining days of a steady burn from the scope of the first day to none on the last day
+    ideal: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct FlowPoint {
+    date: NaiveDate,
+    /// Tasks in each state at the end of the day, in the order of the states of the workflow
+    counts: Vec<usize>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct CumulativeFlow {
+    states: Vec<String>,
+    points: Vec<FlowPoint>,
+}
+
+/// Unix timestamp (seconds) of the end of the day, the midnight after it.
+fn end_of_day(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() + 24 * 60 * 60
+}
+
+fn date_of(timestamp: i64) -> NaiveDate {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default().date_naive()
+}
+
+fn report_days(from: NaiveDate, to: NaiveDate) -> Result<Vec<NaiveDate>> {
+    if to < from {
+        return Err(ProjectError::InvalidInput("a report ends after its start".to_string()));
+    }
+    if (to - from).num_days() >= MAX_REPORT_DAYS {
+        return Err(ProjectError::InvalidInput(format!(
+            "a report spans {} days at most",
+            MAX_REPORT_DAYS
+        )));
+    }
+    Ok(from.iter_days().take_while(|date| *date <= to).collect())
+}
+
+/// Status of the task just before `before`, none when it did not exist yet. The history is in
+/// chronological order.
+fn status_at(history: &[StatusChange], task_id: i32, before: i64) -> Option<&str> {
+    history
+        .iter()
+        .rev()
+        .find(|change| change.task_id == task_id && change.changed_at < before)
+        .map(|change| change.to_state.as_str())
+}
+
+/// Daily remaining estimate of the (task_id, duration) tasks, up to today.
+fn burndown(
+    tasks: &[(i32, i32)],
+    history: &[StatusChange],
+    done_state: &str,
+    days: &[NaiveDate],
+    now: i64,
+) -> Vec<BurndownPoint> {
+    let open = |before: i64| -> Vec<(i32, i32)> {
+        tasks
+            .iter()
+            .filter(|(task_id, _)| status_at(history, *task_id, before).is_some_and(|status| status != done_state))
+            .copied()
+            .collect()
+    };
+    let scope: i32 = days
+        .first()
+        .map(|day| open(end_of_day(*day)).iter().map(|(_, duration)| duration).sum())
+        .unwrap_or_default();
+    let last = days.len().saturating_sub(1).max(1) as f64;
+
+    days.iter()
+        .enumerate()
+        .map(|(i, day)| {
+            let before = end_of_day(*day);
+            let remaining = if before - 24 * 60 * 60 <= now {
+                Some(open(before.min(now + 1)))
+            } else {
+                None
+            };
+            BurndownPoint {
+                date: *day,
+                remaining: remaining
+                    .as_ref()
+                    .map(|open| open.iter().map(|(_, duration)| duration).sum()),
+                remaining_tasks: remaining.as_ref().map(Vec::len),
+                ideal: scope as f64 * (1.0 - (i as f64 / last).min(1.0)),
+            }
+        })
+        .collect()
+}
+
+/// Daily count of the (task_id, deleted_at) tasks in each state, a deleted task counts until
+/// its deletion.
+fn cumulative_flow(
+    tasks: &[(i32, Option<i64>)],
+    history: &[StatusChange],
+    states: &[String],
+    days: &[NaiveDate],
+) -> Vec<FlowPoint> {
+    days.iter()
+        .map(|day| {
+            let before = end_of_day(*day);
+            let mut counts = vec![0; states.len()];
+            for (task_id, deleted_at) in tasks {
+                if deleted_at.is_some_and(|deleted_at| deleted_at < before) {
+                    continue;
+                }
+                if let Some(status) = status_at(history, *task_id, before) {
+                    if let Some(i) = states.iter().position(|state| state == status) {
+                        counts[i] += 1;
+                    }
+                }
+            }
+            FlowPoint { date: *day, counts }
+        })
+        .collect()
+}
+
+async fn project_history(db: &Pool<Any>, project_id: i32) -> Result<Vec<StatusChange>> {
+    let history = sqlx::query_as::<_, StatusChange>(
+        "SELECT task_id, to_state, changed_at FROM task_status_history
+        WHERE project_id = $1
+        ORDER BY changed_at, history_id",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+    Ok(history)
+}
+
+async fn authorize_report(
+    state: &AppState,
+    token_claims: &TokenClaims,
+    context: &RequestContext,
+    project_id: i32,
+) -> Result<()> {
+    let resource = project_resource(&state.db, project_id).await?;
+    let permission = delegations::permission_for(state, token_claims).await?;
+    if !permission.is_authorized(token_claims, Action::ViewProject, &resource, context)? {
+        return Err(ProjectError::AuthFailed);
+    }
+    Ok(())
+}
+
+/// Daily burndown of the tasks committed to the sprint, from its start to its end.
+#[get("/api/projects/{id}/sprints/{sprint_id}/burndown")]
+async fn sprint_burndown(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, sprint_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let sprint_id = sprint_id.parse::<i32>()?;
+
+            authorize_report(&state, &tk, &context, id).await?;
+
+            let (start_date, end_date): (i64, i64) =
+                sqlx::query_as("SELECT start_date, end_date FROM sprints WHERE sprint_id = $1 AND project_id = $2")
+                    .bind(sprint_id)
+                    .bind(id)
+                    .fetch_one(&state.db)
+                    .await?;
+            let tasks: Vec<(i32, i32)> = sqlx::query_as(
+                "SELECT tasks.task_id, tasks.duration FROM sprint_tasks
+                JOIN tasks ON tasks.task_id = sprint_tasks.task_id
+                WHERE sprint_tasks.sprint_id = $1
+                ORDER BY tasks.task_id",
+            )
+            .bind(sprint_id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let workflow = workflow(&state.db, id).await?;
+            let done_state = workflow.states.last().cloned().unwrap_or_default();
+            let history = project_history(&state.db, id).await?;
+            let days = report_days(date_of(start_date), date_of(end_date))?;
+            let points = burndown(&tasks, &history, &done_state, &days, Utc::now().timestamp());
+
+            let json = serde_json::to_string(&points)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Daily burndown of the tasks of the milestone, by default from the creation of its first task
+/// to its due date.
+#[get("/api/projects/{id}/milestones/{milestone_id}/burndown")]
+async fn milestone_burndown(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    range: Query<RangeQuery>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, milestone_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let milestone_id = milestone_id.parse::<i32>()?;
+
+            authorize_report(&state, &tk, &context, id).await?;
+
+            let (due_date,): (Option<i64>,) =
+                sqlx::query_as("SELECT due_date FROM milestones WHERE milestone_id = $1 AND project_id = $2")
+                    .bind(milestone_id)
+                    .bind(id)
+                    .fetch_one(&state.db)
+                    .await?;
+            let tasks: Vec<(i32, i32)> = sqlx::query_as(
+                "SELECT task_id, duration FROM tasks WHERE milestone_id = $1 AND deleted_at IS NULL ORDER BY task_id",
+            )
+            .bind(milestone_id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let workflow = workflow(&state.db, id).await?;
+            let done_state = workflow.states.last().cloned().unwrap_or_default();
+            let history = project_history(&state.db, id).await?;
+            let now = Utc::now().timestamp();
+            // the tasks created before the history start today
+            let first_change = history
+                .iter()
+                .filter(|change| change.changed_at > 0 && tasks.iter().any(|(task_id, _)| *task_id == change.task_id))
+                .map(|change| change.changed_at)
+                .min();
+            let from = range.from.unwrap_or_else(|| date_of(first_change.unwrap_or(now)));
+            let to = range.to.unwrap_or_else(|| date_of(due_date.unwrap_or(now)).max(from));
+            let days = report_days(from, to)?;
+            let points = burndown(&tasks, &history, &done_state, &days, now);
+
+            let json = serde_json::to_string(&points)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Daily count of the tasks of the project in each state of its workflow, over `from` ..= `to`.
+#[get("/api/projects/{id}/cumulative-flow")]
+async fn cumulative_flow_report(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    range: Query<RangeQuery>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let (from, to) = match (range.from, range.to) {
+                (Some(from), Some(to)) => (from, to),
+                _ => return Err(ProjectError::InvalidInput("from and to are required".to_string())),
+            };
+            let days = report_days(from, to)?;
+
+            authorize_report(&state, &tk, &context, id).await?;
+
+            let tasks: Vec<(i32, Option<i64>)> =
+                sqlx::query_as("SELECT task_id, deleted_at FROM tasks WHERE project_id = $1 ORDER BY task_id")
+                    .bind(id)
+                    .fetch_all(&state.db)
+                    .await?;
+            let states = workflow(&state.db, id).await?.states;
+            let history = project_history(&state.db, id).await?;
+            let points = cumulative_flow(&tasks, &history, &states, &days);
+
+            let json = serde_json::to_string(&CumulativeFlow { states, points })?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    fn change(task_id: i32, to_state: &str, date: NaiveDate, hour: i64) -> StatusChange {
+        StatusChange {
+            task_id,
+            to_state: to_state.to_string(),
+            changed_at: end_of_day(date) - 24 * 60 * 60 + hour * 60 * 60,
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2030, 1, day).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn status_follows_the_history() {
+        let history = vec![
+            change(1, "todo", date(1), 9),
+            change(1, "in_progress", date(2), 9),
+            change(1, "done", date(2), 17),
+        ];
+        assert_eq!(status_at(&history, 1, end_of_day(date(1)) - 1), Some("todo"));
+        assert_eq!(status_at(&history, 1, end_of_day(date(2))), Some("done"));
+        assert_eq!(status_at(&history, 1, change(1, "", date(1), 8).changed_at), None);
+        assert_eq!(status_at(&history, 2, end_of_day(date(2))), None);
+    }
+
+    #[actix_web::test]
+    async fn burndown_counts_the_open_estimate() {
+        // 1 (2 days) done on the 2nd, 2 (3 days) added on the 2nd, done on the 3rd
+        let history = vec![
+            change(1, "todo", date(1), 9),
+            change(1, "done", date(2), 12),
+            change(2, "todo", date(2), 12),
+            change(2, "done", date(3), 12),
+        ];
+        let days = report_days(date(1), date(5)).unwrap();
+        let now = change(0, "", date(4), 12).changed_at;
+
+        let points = burndown(&[(1, 2), (2, 3)], &history, "done", &days, now);
+        let remaining: Vec<Option<i32>> = points.iter().map(|point| point.remaining).collect();
+        assert_eq!(remaining, vec![Some(2), Some(3), Some(0), Some(0), None]);
+        assert_eq!(points[0].remaining_tasks, Some(1));
+        assert_eq!(points[0].ideal, 2.0);
+        assert_eq!(points[2].ideal, 1.0);
+        assert_eq!(points[4].ideal, 0.0);
+    }
+
+    #[actix_web::test]
+    async fn cumulative_flow_counts_the_states() {
+        let history = vec![
+            change(1, "todo", date(1), 9),
+            change(2, "todo", date(1), 9),
+            change(1, "in_progress", date(2), 9),
+            change(2, "in_progress", date(3), 9),
+            change(1, "done", date(3), 17),
+        ];
+        let states: Vec<String> = ["todo", "in_progress", "done"].iter().map(|state| state.to_string()).collect();
+
+        let points = cumulative_flow(&[(1, None), (2, None)], &history, &states, &report_days(date(1), date(3)).unwrap());
+        let counts: Vec<Vec<usize>> = points.into_iter().map(|point| point.counts).collect();
+        assert_eq!(counts, vec![vec![2, 0, 0], vec![1, 1, 0], vec![0, 1, 1]]);
+    }
+
+    #[actix_web::test]
+    async fn report_ranges_are_bounded() {
+        assert!(report_days(date(2), date(1)).is_err());
+        assert_eq!(report_days(date(1), date(1)).unwrap(), vec![date(1)]);
+        assert!(report_days(date(1), date(1) + chrono::Duration::days(MAX_REPORT_DAYS)).is_err());
+    }
+
+    #[actix_web::test]
+    async fn cumulative_flow_follows_the_task_history() {
+        let app = create_test_app().await;
+
+        let today = chrono::Utc::now().date_naive();
+        let cfd_req = |token_claims: TokenClaims| {
+            test::TestRequest::get()
+                .uri(&format!("/api/projects/1/cumulative-flow?from={}&to={}", today, today))
+                .insert_header(bearer(token_claims))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, cfd_req(TokenClaims {
+            id: 4,
+            roles: vec!["Developer".to_string()],
+            ..Default::default()
+        }))
+        .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let before: Value = test::call_and_read_body_json(&app, cfd_req(projectlead())).await;
+        assert_eq!(before["states"], serde_json::json!(["todo", "in_progress", "done"]));
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/1/tasks")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "title": "chart the flow" }))
+            .to_request();
+        let task: Value = test::call_and_read_body_json(&app, req).await;
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/projects/1/tasks/{}/transitions", task["task_id"]))
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "to": "in_progress" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let after: Value = test::call_and_read_body_json(&app, cfd_req(projectlead())).await;
+        let count = |cfd: &Value, state: usize| cfd["points"][0]["counts"][state].as_i64().unwrap();
+        assert_eq!(count(&after, 0), count(&before, 0));
+        assert_eq!(count(&after, 1), count(&before, 1) + 1);
+    }
+}
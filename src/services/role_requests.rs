@@ -0,0 +1,480 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    get, post,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool, Transaction};
+
+#[derive(Deserialize)]
+struct CreateRoleRequestBody {
+    /// Cedar role id, e.g. `Developer`
+    role: String,
+    /// Requests an assignment to the project through the party role of `role`
+    #[serde(default)]
+    project_id: Option<i32>,
+    /// Role on the project for an assignment request, `Contributor` by default
+    #[serde(default)]
+    project_role: Option<String>,
+    reason: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct RoleRequest {
+    request_id: i32,
+    party_id: i32,
+    /// organization of the requesting party
+    tenant_id: i32,
+    role: String,
+    /// 0 for a role request
+    project_id: i32,
+    /// Role on the project, for an assignment request
+    project_role: String,
+    reason: String,
+    status: String,
+    /// party_id of the approver, 0 while pending
+    decided_by: i32,
+    /// unix timestamp (seconds), 0 while pending
+    decided_at: i64,
+    /// unix timestamp (seconds)
+    requested_at: i64,
+}
+
+impl RoleRequest {
+    fn is_assignment(&self) -> bool {
+        self.project_id != 0
+    }
+}
+
+async fn fetch_role_request(db: &Pool<Any>, request_id: i32) -> Result<RoleRequest> {
+    let request = sqlx::query_as::<_, RoleRequest>(
+        "SELECT request_id, role_requests.party_id, parties.tenant_id, role_type.name AS role, project_id,
+        project_role, reason, status, decided_by, decided_at, requested_at
+        FROM role_requests
+        JOIN role_type ON role_type.role_type_id = role_requests.role_type_id
+        JOIN parties ON parties.party_id = role_requests.party_id
+        WHERE request_id = $1",
+    )
+    .bind(request_id)
+    .fetch_one(db)
+    .await?;
+    Ok(request)
+}
+
+/// The approver of a role request needs `AssignRole`, of an assignment request `AssignPartyrole`
+/// on the project (project lead or delegate).
+async fn may_decide(
+    state: &AppState,
+    token_claims: &TokenClaims,
+    context: &RequestContext,
+    request: &RoleRequest,
+) -> Result<bool> {
+    if request.party_id == token_claims.id || request.tenant_id != token_claims.tenant_id {
+        return Ok(false);
+    }
+    if request.is_assignment() {
+        let permission = delegations::permission_for(state, token_claims).await?;
+        let resource = project_resource(&state.db, request.project_id).await?;
+        Ok(permission.is_authorized(token_claims, Action::AssignPartyrole, &resource, context)?)
+    } else {
+        let resource = GroupResource::all_projects();
+        Ok(state
+            .permission
+            .is_authorized(token_claims, Action::AssignRole, &resource, context)?)
+    }
+}
+
+#[post("/api/role-requests")]
+async fn create_role_request(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    body: Json<CreateRoleRequestBody>,
+) -> Result<String> {
+    match token_claims {
+        Some(token_claims) => {
+            let tk = token_claims.into_inner();
+            if tk.is_guest() || tk.anonymous {
+                return Err(ProjectError::AuthFailed);
+            }
+            let body = body.into_inner();
+            let reason = body.reason.trim();
+            if reason.is_empty() {
+                return Err(ProjectError::InvalidInput("a reason is required".to_string()));
+            }
+
+            let role_types: Vec<(i32, String)> =
+                sqlx::query_as("SELECT role_type_id, name FROM role_type")
+                    .fetch_all(&state.db)
+                    .await?;
+            let role_type_id = match role_types.iter().find(|(_, name)| role_id(name) == body.role) {
+                Some((role_type_id, _)) => *role_type_id,
+                None => return Err(ProjectError::InvalidInput(format!("unknown role {}", body.role))),
+            };
+            if let Some(project_id) = body.project_id {
+                let resource = project_resource(&state.db, project_id).await?;
+                if resource.tenant != tk.tenant_id {
+                    return Err(ProjectError::AuthFailed);
+                }
+            }
+            let project_role = body.project_role.unwrap_or_else(|| PROJECT_ROLES[0].to_string());
+            if !PROJECT_ROLES.contains(&project_role.as_str()) {
+                return Err(ProjectError::InvalidInput(format!("unknown project role {}", project_role)));
+            }
+
+            let (request_id,): (i32,) = sqlx::query_as(
+                "INSERT INTO role_requests (party_id, role_type_id, project_id, project_role, reason, requested_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING request_id",
+            )
+            .bind(tk.id)
+            .bind(role_type_id)
+            .bind(body.project_id.unwrap_or(0))
+            .bind(&project_role)
+            .bind(reason)
+            .bind(Utc::now().timestamp())
+            .fetch_one(&state.db)
+            .await?;
+
+            let request = fetch_role_request(&state.db, request_id).await?;
+            let json = serde_json::to_string(&request)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// The requests of the caller and the pending requests it may decide.
+#[get("/api/role-requests")]
+async fn list_role_requests(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let requests = sqlx::query_as::<_, RoleRequest>(
+                "SELECT request_id, role_requests.party_id, parties.tenant_id, role_type.name AS role, project_id,
+                project_role, reason, status, decided_by, decided_at, requested_at
+                FROM role_requests
+                JOIN role_type ON role_type.role_type_id = role_requests.role_type_id
+                JOIN parties ON parties.party_id = role_requests.party_id
+                WHERE parties.tenant_id = $2 AND (role_requests.party_id = $1 OR status = 'pending')
+                ORDER BY request_id",
+            )
+            .bind(tk.id)
+            .bind(tk.tenant_id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let mut visible = vec![];
+            for request in requests {
+                if request.party_id == tk.id || may_decide(&state, &tk, &context, &request).await? {
+                    visible.push(request);
+                }
+            }
+
+            let json = serde_json::to_string(&visible)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[post("/api/role-requests/{id}/approve")]
+async fn approve_role_request(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    decide_role_request(state, token_claims, context, path, true).await
+}
+
+#[post("/api/role-requests/{id}/reject")]
+async fn reject_role_request(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    decide_role_request(state, token_claims, context, path, false).await
+}
+
+async fn decide_role_request(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    approve: bool,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let request_id = path.into_inner().parse::<i32>()?;
+
+            let request = fetch_role_request(&state.db, request_id).await?;
+            if request.status != "pending" {
+                return Err(ProjectError::InvalidInput(format!(
+                    "request {} is already {}",
+                    request_id, request.status
+                )));
+            }
+            if !may_decide(&state, &tk, &context, &request).await? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let mut tx = state.db.begin().await?;
+            if approve {
+                grant(&mut tx, &request, tk.id).await?;
+            }
+            sqlx::query(
+                "UPDATE role_requests SET status = $1, decided_by = $2, decided_at = $3
+                WHERE request_id = $4",
+            )
+            .bind(if approve { "approved" } else { "rejected" })
+            .bind(tk.id)
+            .bind(Utc::now().timestamp())
+            .bind(request_id)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            let (action, resource) = if request.is_assignment() {
+                ("AssignPartyrole", entity_uid("Project", request.project_id))
+            } else {
+                ("AssignRole", entity_uid("User", request.party_id))
+            };
+            let detail = format!(
+                "role request {} of party {} for {} {}: {}",
+                request_id,
+                request.party_id,
+                request.role,
+                if approve { "approved" } else { "rejected" },
+                request.reason
+            );
+            audit::record(&state.db, tk.id, action, &resource, &[], &detail).await?;
+
+            let request = fetch_role_request(&state.db, request_id).await?;
+            let json = serde_json::to_string(&request)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Writes the `party_role` or `assignments` row of an approved request, created by the approver.
+/// A party holds a role at most once, an expired `party_role` of the role is granted again.
+async fn grant(tx: &mut Transaction<'_, Any>, request: &RoleRequest, approver_id: i32) -> Result<()> {
+    let now = Utc::now().timestamp();
+    let party_role: Option<(i32, Option<i64>, Option<i64>)> = sqlx::query_as(
+        "SELECT party_role_id, party_role.valid_from, party_role.valid_to FROM party_role
+        JOIN role_type ON role_type.role_type_id = party_role.role_type_id
+        WHERE party_role.party_id = $1 AND role_type.name = $2",
+    )
+    .bind(request.party_id)
+    .bind(&request.role)
+    .fetch_optional(&mut **tx)
+    .await?;
+    let active = |valid_from: Option<i64>, valid_to: Option<i64>| {
+        valid_from.map_or(true, |valid_from| valid_from <= now) && valid_to.map_or(true, |valid_to| valid_to > now)
+    };
+
+    let party_role_id = match (party_role, request.is_assignment()) {
+        (Some((party_role_id, valid_from, valid_to)), _) if active(valid_from, valid_to) => party_role_id,
+        (_, true) => {
+            return Err(ProjectError::InvalidInput(format!(
+                "party {} does not hold the role {}",
+                request.party_id, request.role
+            )))
+        }
+        (Some((party_role_id, _, _)), false) => {
+            sqlx::query(
+                "UPDATE party_role SET valid_from = NULL, valid_to = NULL, expired = 0
+                WHERE party_role_id = $1",
+            )
+            .bind(party_role_id)
+            .execute(&mut **tx)
+            .await?;
+            party_role_id
+        }
+        (None, false) => {
+            sqlx::query(
+                "INSERT INTO party_role (party_id, role_type_id, created_by)
+                VALUES ($1, (SELECT role_type_id FROM role_type WHERE name = $2),
+                (SELECT MIN(party_role_id) FROM party_role WHERE party_id = $3))",
+            )
+            .bind(request.party_id)
+            .bind(&request.role)
+            .bind(approver_id)
+            .execute(&mut **tx)
+            .await?;
+            let (party_role_id,): (i32,) = sqlx::query_as(
+                "SELECT party_role_id FROM party_role
+                JOIN role_type ON role_type.role_type_id = party_role.role_type_id
+                WHERE party_role.party_id = $1 AND role_type.name = $2",
+            )
+            .bind(request.party_id)
+            .bind(&request.role)
+            .fetch_one(&mut **tx)
+            .await?;
+            party_role_id
+        }
+    };
+
+    if request.is_assignment() {
+        sqlx::query(
+            "INSERT INTO assignments (party_role_id, project_id, project_role, created_by, tenant_id)
+            SELECT $1, $2, $3, MIN(party_role_id), (SELECT tenant_id FROM projects WHERE id = $2)
+            FROM party_role WHERE party_id = $4",
+        )
+        .bind(party_role_id)
+        .bind(request.project_id)
+        .bind(&request.project_role)
+        .bind(approver_id)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_http::Request;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use dotenv::dotenv;
+    use serde_json::Value;
+
+    async fn party_roles(db: &Pool<Any>, party_id: i32) -> Vec<(i32, i32, Option<i64>)> {
+        sqlx::query_as("SELECT party_role_id, role_type_id, valid_to FROM party_role WHERE party_id = $1 ORDER BY party_role_id")
+            .bind(party_id)
+            .fetch_all(db)
+            .await
+            .unwrap()
+    }
+
+    fn role_request_req(token_claims: TokenClaims, body: Value) -> Request {
+        test::TestRequest::post()
+            .uri("/api/role-requests")
+            .insert_header(bearer(token_claims))
+            .set_json(body)
+            .to_request()
+    }
+
+    fn decide_role_request_req(token_claims: TokenClaims, id: i64, decision: &str) -> Request {
+        test::TestRequest::post()
+            .uri(&format!("/api/role-requests/{}/{}", id, decision))
+            .insert_header(bearer(token_claims))
+            .to_request()
+    }
+
+    #[actix_web::test]
+    async fn assignment_request_is_approved_by_project_lead() {
+        let app = create_test_app().await;
+
+        let body = serde_json::json!({ "role": "Developer", "project_id": 1, "reason": "" });
+        let resp = test::call_service(&app, role_request_req(developer(), body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let body = serde_json::json!({ "role": "Developer", "project_id": 1, "reason": "joining the team" });
+        let request: Value = test::call_and_read_body_json(&app, role_request_req(developer(), body)).await;
+        assert_eq!(request["status"], "pending");
+        let id = request["request_id"].as_i64().unwrap();
+
+        let resp = test::call_service(&app, decide_role_request_req(developer(), id, "approve")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let requests: Vec<Value> = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get().uri("/api/role-requests").insert_header(bearer(projectlead())).to_request(),
+        )
+        .await;
+        assert_eq!(requests.len(), 1);
+
+        let request: Value = test::call_and_read_body_json(&app, decide_role_request_req(projectlead(), id, "approve")).await;
+        assert_eq!(request["status"], "approved");
+        assert_eq!(request["decided_by"], 3);
+
+        let resp = test::call_service(&app, get_project_req(developer(), 1)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(&app, decide_role_request_req(projectlead(), id, "reject")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn role_request_is_decided_by_administrator() {
+        let app = create_test_app().await;
+
+        let body = serde_json::json!({ "role": "Auditor", "reason": "quarterly review" });
+        let request: Value = test::call_and_read_body_json(&app, role_request_req(developer(), body)).await;
+        let id = request["request_id"].as_i64().unwrap();
+
+        let resp = test::call_service(&app, decide_role_request_req(projectlead(), id, "approve")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let request: Value = test::call_and_read_body_json(&app, decide_role_request_req(admin(), id, "approve")).await;
+        assert_eq!(request["status"], "approved");
+        assert_eq!(request["decided_by"], 2);
+
+        // the role is now honored in the token: auditors read the confidential fields
+        let auditor = TokenClaims {
+            id: 4,
+            roles: vec!["Developer".to_string(), "Auditor".to_string()],
+            ..Default::default()
+        };
+        let project: Value = test::call_and_read_body_json(&app, get_project_req(auditor.clone(), 2)).await;
+        assert!(project.get("internal_description").is_some());
+        // the budget is for the owner only
+        assert!(project.get("budget").is_none());
+
+        // auditors also read the projects they are not assigned to
+        let project: Value = test::call_and_read_body_json(&app, get_project_req(auditor, 1)).await;
+        assert_eq!(project["name"], "my project");
+        assert!(project.get("internal_description").is_some());
+    }
+
+    #[actix_web::test]
+    async fn expired_role_is_granted_again() {
+        dotenv().ok();
+        sqlx::any::install_default_drivers();
+        let app_data = Arc::new(create_app_data().await.unwrap());
+        sqlx::query("UPDATE party_role SET valid_to = $1 WHERE party_role_id = 4")
+            .bind(Utc::now().timestamp() - 60)
+            .execute(&app_data.db)
+            .await
+            .unwrap();
+        let db = app_data.db.clone();
+        let app = create_test_app_with(app_data).await;
+
+        // the expired row is revived rather than duplicated
+        let body = serde_json::json!({ "role": "Developer", "reason": "back on the team" });
+        let request: Value = test::call_and_read_body_json(&app, role_request_req(developer(), body)).await;
+        let id = request["request_id"].as_i64().unwrap();
+        let request: Value = test::call_and_read_body_json(&app, decide_role_request_req(admin(), id, "approve")).await;
+        assert_eq!(request["status"], "approved");
+        assert_eq!(party_roles(&db, 4).await, vec![(4, 4, None)]);
+
+        // a new role is numbered after the existing ones
+        let body = serde_json::json!({ "role": "Auditor", "reason": "quarterly review" });
+        let request: Value = test::call_and_read_body_json(&app, role_request_req(developer(), body)).await;
+        let id = request["request_id"].as_i64().unwrap();
+        test::call_service(&app, decide_role_request_req(admin(), id, "approve")).await;
+        let party_roles = party_roles(&db, 4).await;
+        assert_eq!(party_roles.len(), 2);
+        assert!(party_roles[1].0 > 4);
+    }
+}
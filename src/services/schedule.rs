@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post,
+    web::{self, Data, Json, Query, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::calendars::{organization_calendar, WorkingCalendar};
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+use super::tasks::task_resource;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+const DEPENDENCY_KINDS: [&str; 2] = ["finish_to_start", "start_to_start"];
+
+#[derive(Deserialize)]
+struct CreateDependencyBody {
+    predecessor_id: i32,
+    successor_id: i32,
+    /// `finish_to_start` by default
+    #[serde(default)]
+    kind: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScheduleQuery {
+    /// First day of the project, for the dates of the tasks
+    #[serde(default)]
+    start: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Dependency {
+    predecessor_id: i32,
+    successor_id: i32,
+    kind: String,
+}
+
+#[derive(FromRow)]
+struct TaskDuration {
+    task_id: i32,
+    /// days
+    duration: i32,
+}
+
+/// Schedule of a task, in days from the start of the project.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct TaskSchedule {
+    pub task_id: i32,
+    duration: i32,
+    earliest_start: i32,
+    pub earliest_finish: i32,
+    latest_start: i32,
+    latest_finish: i32,
+    slack: i32,
+    critical: bool,
+    /// First working day of the task, when the project has a start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_date: Option<NaiveDate>,
+    /// Last working day of the task, when the project has a start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finish_date: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub(crate) struct Schedule {
+    /// days
+    duration: i32,
+    pub tasks: Vec<TaskSchedule>,
+    /// task_id of the critical tasks, by earliest start
+    critical_path: Vec<i32>,
+    /// Last working day of the project, when the project has a start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finish_date: Option<NaiveDate>,
+}
+
+impl Schedule {
+    /// The schedule with the dates of the tasks from `start` on, counting the working days of the
+    /// calendar only.
+    fn with_dates(mut self, calendar: &WorkingCalendar, start: NaiveDate) -> Self {
+        // the last day of `days` days of work from `offset` days of work on
+        let last_day = |offset: i32, days: i32| calendar.nth_working_day(start, offset + (days - 1).max(0));
+        for task in self.tasks.iter_mut() {
+            task.start_date = Some(calendar.nth_working_day(start, task.earliest_start));
+            task.finish_date = Some(last_day(task.earliest_start, task.duration));
+        }
+        self.finish_date = Some(last_day(0, self.duration));
+        self
+    }
+}
+
+/// The tasks in an order where every predecessor comes before its successors, none when the
+/// dependencies have a cycle.
+fn topological_order(tasks: &[i32], dependencies: &[Dependency]) -> Option<Vec<i32>> {
+    let mut predecessors: HashMap<i32, usize> = tasks.iter().map(|task_id| (*task_id, 0)).collect();
+    for dependency in dependencies {
+        *predecessors.entry(dependency.successor_id).or_default() += 1;
+    }
+    let mut ready: Vec<i32> = tasks
+        .iter()
+        .filter(|task_id| predecessors[*task_id] == 0)
+        .rev()
+        .copied()
+        .collect();
+
+    let mut order = vec![];
+    while let Some(task_id) = ready.pop() {
+        order.push(task_id);
+        for dependency in dependencies.iter().filter(|d| d.predecessor_id == task_id) {
+            let count = predecessors.get_mut(&dependency.successor_id)?;
+            *count -= 1;
+            if *count == 0 {
+                ready.push(dependency.successor_id);
+            }
+        }
+    }
+    (order.len() == predecessors.len()).then_some(order)
+}
+
+/// The dependency from `predecessor_id` to `successor_id` would close a cycle: the predecessor
+/// already depends, directly or not, on the successor.
+pub(crate) fn creates_cycle(dependencies: &[Dependency], predecessor_id: i32, successor_id: i32) -> bool {
+    let mut pending = vec![successor_id];
+    let mut visited = vec![];
+    while let Some(task_id) = pending.pop() {
+        if task_id == predecessor_id {
+            return true;
+        }
+        if visited.contains(&task_id) {
+            continue;
+        }
+        visited.push(task_id);
+        pending.extend(
+            dependencies
+                .iter()
+                .filter(|dependency| dependency.predecessor_id == task_id)
+                .map(|dependency| dependency.successor_id),
+        );
+    }
+    false
+}
+
+/// Critical path method: a forward pass for the earliest dates, a backward pass for the latest
+/// ones. Start-to-start dependencies only constrain the starts.
+pub(crate) fn compute_schedule(durations: &[(i32, i32)], dependencies: &[Dependency]) -> Option<Schedule> {
+    let task_ids: Vec<i32> = durations.iter().map(|(task_id, _)| *task_id).collect();
+    let duration: HashMap<i32, i32> = durations.iter().copied().collect();
+    let order = topological_order(&task_ids, dependencies)?;
+    let start_to_start = |dependency: &Dependency| dependency.kind == "start_to_start";
+
+    let mut earliest_start: HashMap<i32, i32> = HashMap::new();
+    for task_id in &order {
+        let start = dependencies
+            .iter()
+            .filter(|dependency| dependency.successor_id == *task_id)
+            .map(|dependency| {
+                let predecessor_start = earliest_start[&dependency.predecessor_id];
+                if start_to_start(dependency) {
+                    predecessor_start
+                } else {
+                    predecessor_start + duration[&dependency.predecessor_id]
+                }
+            })
+            .max()
+            .unwrap_or(0);
+        earliest_start.insert(*task_id, start);
+    }
+    let project_duration = order
+        .iter()
+        .map(|task_id| earliest_start[task_id] + duration[task_id])
+        .max()
+        .unwrap_or(0);
+
+    let mut latest_finish: HashMap<i32, i32> = HashMap::new();
+    for task_id in order.iter().rev() {
+        let finish = dependencies
+            .iter()
+            .filter(|dependency| dependency.predecessor_id == *task_id)
+            .map(|dependency| {
+                let successor_start = latest_finish[&dependency.successor_id] - duration[&dependency.successor_id];
+                if start_to_start(dependency) {
+                    successor_start + duration[task_id]
+                } else {
+                    successor_start
+                }
+            })
+            .min()
+            .unwrap_or(project_duration);
+        latest_finish.insert(*task_id, finish);
+    }
+
+    let mut tasks: Vec<TaskSchedule> = task_ids
+        .iter()
+        .map(|task_id| {
+            let duration = duration[task_id];
+            let earliest_start = earliest_start[task_id];
+            let latest_finish = latest_finish[task_id];
+            let slack = latest_finish - duration - earliest_start;
+            TaskSchedule {
+                task_id: *task_id,
+                duration,
+                earliest_start,
+                earliest_finish: earliest_start + duration,
+                latest_start: latest_finish - duration,
+                latest_finish,
+                slack,
+                critical: slack == 0,
+                start_date: None,
+                finish_date: None,
+            }
+        })
+        .collect();
+    tasks.sort_by_key(|task| (task.earliest_start, task.task_id));
+    let critical_path = tasks
+        .iter()
+        .filter(|task| task.critical)
+        .map(|task| task.task_id)
+        .collect();
+
+    Some(Schedule {
+        duration: project_duration,
+        tasks,
+        critical_path,
+        finish_date: None,
+    })
+}
+
+pub(crate) async fn project_dependencies(db: &Pool<Any>, project_id: i32) -> Result<Vec<Dependency>> {
+    let dependencies = sqlx::query_as::<_, Dependency>(
+        "SELECT task_dependencies.predecessor_id, task_dependencies.successor_id, task_dependencies.kind
+        FROM task_dependencies
+        JOIN tasks ON tasks.task_id = task_dependencies.successor_id
+        WHERE tasks.project_id = $1
+        ORDER BY task_dependencies.predecessor_id, task_dependencies.successor_id",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+    Ok(dependencies)
+}
+
+#[get("/api/projects/{id}/dependencies")]
+async fn list_dependencies(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let json = serde_json::to_string(&project_dependencies(&state.db, id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Makes a task wait for another task of the project, the caller needs `UpdateTask` on the
+/// waiting task.
+#[post("/api/projects/{id}/dependencies")]
+async fn create_dependency(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<CreateDependencyBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+            let kind = body.kind.unwrap_or_else(|| DEPENDENCY_KINDS[0].to_string());
+            if !DEPENDENCY_KINDS.contains(&kind.as_str()) {
+                return Err(ProjectError::InvalidInput(format!("unknown dependency kind {}", kind)));
+            }
+            if body.predecessor_id == body.successor_id {
+                return Err(ProjectError::InvalidInput("a task cannot depend on itself".to_string()));
+            }
+
+            // both tasks belong to the project
+            let resource = task_resource(&state.db, id, body.successor_id).await?;
+            task_resource(&state.db, id, body.predecessor_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::UpdateTask, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let dependencies = project_dependencies(&state.db, id).await?;
+            if creates_cycle(&dependencies, body.predecessor_id, body.successor_id) {
+                return Err(ProjectError::InvalidInput(format!(
+                    "task {} already depends on task {}",
+                    body.predecessor_id, body.successor_id
+                )));
+            }
+
+            let dependency = sqlx::query_as::<_, Dependency>(
+                "INSERT INTO task_dependencies (predecessor_id, successor_id, kind, created_by)
+                VALUES ($1, $2, $3, $4)
+                RETURNING predecessor_id, successor_id, kind",
+            )
+            .bind(body.predecessor_id)
+            .bind(body.successor_id)
+            .bind(&kind)
+            .bind(tk.id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!(
+                "task {} depends on task {} ({})",
+                dependency.successor_id, dependency.predecessor_id, dependency.kind
+            );
+            audit::record(&state.db, tk.id, "CreateDependency", &resource.project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&dependency)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/projects/{id}/dependencies/{predecessor_id}/{successor_id}")]
+async fn delete_dependency(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, predecessor_id, successor_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let predecessor_id = predecessor_id.parse::<i32>()?;
+            let successor_id = successor_id.parse::<i32>()?;
+
+            let resource = task_resource(&state.db, id, successor_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::UpdateTask, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let dependency = sqlx::query_as::<_, Dependency>(
+                "DELETE FROM task_dependencies WHERE predecessor_id = $1 AND successor_id = $2
+                RETURNING predecessor_id, successor_id, kind",
+            )
+            .bind(predecessor_id)
+            .bind(successor_id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!(
+                "task {} no longer depends on task {}",
+                dependency.successor_id, dependency.predecessor_id
+            );
+            audit::record(&state.db, tk.id, "DeleteDependency", &resource.project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&dependency)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Earliest and latest dates, slack and critical path of the tasks of the project, in working
+/// days, with the dates of the tasks from `start` on when given.
+#[get("/api/projects/{id}/schedule")]
+async fn get_schedule(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    query: Query<ScheduleQuery>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let durations = sqlx::query_as::<_, TaskDuration>(
+                "SELECT task_id, duration FROM tasks WHERE project_id = $1 AND deleted_at IS NULL ORDER BY task_id",
+            )
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+            let durations: Vec<(i32, i32)> = durations
+                .into_iter()
+                .map(|task| (task.task_id, task.duration))
+                .collect();
+            let dependencies = project_dependencies(&state.db, id).await?;
+            // cycles are rejected on insert
+            let mut schedule = compute_schedule(&durations, &dependencies).ok_or(ProjectError::Unknown)?;
+            if let Some(start) = query.start {
+                let calendar = organization_calendar(&state.db, resource.tenant).await?;
+                schedule = schedule.with_dates(&calendar, start);
+            }
+
+            let json = serde_json::to_string(&schedule)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    fn dependency(predecessor_id: i32, successor_id: i32, kind: &str) -> Dependency {
+        Dependency {
+            predecessor_id,
+            successor_id,
+            kind: kind.to_string(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn cycles_are_detected() {
+        let dependencies = vec![
+            dependency(1, 2, "finish_to_start"),
+            dependency(2, 3, "start_to_start"),
+        ];
+        assert!(creates_cycle(&dependencies, 3, 1));
+        assert!(creates_cycle(&dependencies, 2, 1));
+        assert!(!creates_cycle(&dependencies, 1, 3));
+        assert!(!creates_cycle(&dependencies, 4, 1));
+    }
+
+    #[actix_web::test]
+    async fn critical_path_is_computed() {
+        // 1 (3 days) -> 2 (2 days) -> 4 (1 day)
+        // 1 (3 days) -> 3 (1 day)  -> 4
+        // 5 (2 days) starts with 2
+        let durations = vec![(1, 3), (2, 2), (3, 1), (4, 1), (5, 2)];
+        let dependencies = vec![
+            dependency(1, 2, "finish_to_start"),
+            dependency(1, 3, "finish_to_start"),
+            dependency(2, 4, "finish_to_start"),
+            dependency(3, 4, "finish_to_start"),
+            dependency(2, 5, "start_to_start"),
+        ];
+
+        let schedule = compute_schedule(&durations, &dependencies).unwrap();
+        assert_eq!(schedule.duration, 6);
+        assert_eq!(schedule.critical_path, vec![1, 2, 4]);
+
+        let task = |task_id: i32| schedule.tasks.iter().find(|task| task.task_id == task_id).unwrap();
+        assert_eq!((task(3).earliest_start, task(3).latest_start, task(3).slack), (3, 4, 1));
+        assert_eq!((task(5).earliest_start, task(5).latest_finish, task(5).slack), (3, 6, 1));
+    }
+
+    #[actix_web::test]
+    async fn cyclic_dependencies_have_no_schedule() {
+        let dependencies = vec![
+            dependency(1, 2, "finish_to_start"),
+            dependency(2, 1, "finish_to_start"),
+        ];
+        assert_eq!(compute_schedule(&[(1, 1), (2, 1)], &dependencies), None);
+    }
+
+    #[actix_web::test]
+    async fn schedule_follows_task_dependencies() {
+        let app = create_test_app().await;
+
+        let mut task_ids = vec![];
+        for (title, duration) in [("design", 3), ("build", 5), ("document", 1)] {
+            let req = test::TestRequest::post()
+                .uri("/api/projects/1/tasks")
+                .insert_header(bearer(projectlead()))
+                .set_json(serde_json::json!({ "title": title, "duration": duration }))
+                .to_request();
+            let task: Value = test::call_and_read_body_json(&app, req).await;
+            task_ids.push(task["task_id"].as_i64().unwrap());
+        }
+        let dependency_req = |predecessor_id: i64, successor_id: i64| {
+            test::TestRequest::post()
+                .uri("/api/projects/1/dependencies")
+                .insert_header(bearer(projectlead()))
+                .set_json(serde_json::json!({ "predecessor_id": predecessor_id, "successor_id": successor_id }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, dependency_req(task_ids[0], task_ids[1])).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = test::call_service(&app, dependency_req(task_ids[0], task_ids[2])).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = test::call_service(&app, dependency_req(task_ids[1], task_ids[0])).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1/schedule")
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let schedule: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(schedule["duration"], 8);
+        assert_eq!(schedule["critical_path"], serde_json::json!([task_ids[0], task_ids[1]]));
+    }
+}
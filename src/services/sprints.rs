@@ -0,0 +1,509 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    get, post,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+use super::workflows::workflow;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+#[derive(Deserialize)]
+struct SprintBody {
+    name: String,
+    #[serde(default)]
+    goal: String,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    /// task_id of the tasks of the project committed to the sprint
+    #[serde(default)]
+    task_ids: Vec<i32>,
+}
+
+#[derive(Deserialize)]
+struct CommitBody {
+    task_ids: Vec<i32>,
+}
+
+#[derive(Deserialize)]
+struct CloseBody {
+    /// sprint_id of a planned sprint of the project the unfinished tasks are carried over to,
+    /// back to the backlog when none
+    #[serde(default)]
+    carry_over_to: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+struct Sprint {
+    sprint_id: i32,
+    project_id: i32,
+    name: String,
+    goal: String,
+    /// unix timestamp (seconds)
+    start_date: i64,
+    /// unix timestamp (seconds)
+    end_date: i64,
+    /// planned, active or closed
+    status: String,
+    /// Days of the tasks done on close
+    completed_estimate: Option<i32>,
+    #[sqlx(skip)]
+    task_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Velocity {
+    /// (sprint_id, completed estimate) of the closed sprints, oldest first
+    sprints: Vec<(i32, i32)>,
+    /// Average completed estimate per closed sprint, in days
+    velocity: f64,
+}
+
+const SPRINT_COLUMNS: &str = "sprint_id, project_id, name, goal, start_date, end_date, status, completed_estimate";
+
+async fn sprint_task_ids(db: &Pool<Any>, sprint_id: i32) -> Result<Vec<i32>> {
+    let task_ids: Vec<(i32,)> = sqlx::query_as("SELECT task_id FROM sprint_tasks WHERE sprint_id = $1 ORDER BY task_id")
+        .bind(sprint_id)
+        .fetch_all(db)
+        .await?;
+    Ok(task_ids.into_iter().map(|(task_id,)| task_id).collect())
+}
+
+async fn fetch_sprint(db: &Pool<Any>, project_id: i32, sprint_id: i32) -> Result<Sprint> {
+    let mut sprint = sqlx::query_as::<_, Sprint>(&format!(
+        "SELECT {} FROM sprints WHERE sprint_id = $1 AND project_id = $2",
+        SPRINT_COLUMNS
+    ))
+    .bind(sprint_id)
+    .bind(project_id)
+    .fetch_one(db)
+    .await?;
+    sprint.task_ids = sprint_task_ids(db, sprint_id).await?;
+    Ok(sprint)
+}
+
+/// Commits the tasks of the project to the sprint, the tasks already committed are left as is.
+async fn commit_tasks(db: &Pool<Any>, project_id: i32, sprint_id: i32, task_ids: &[i32]) -> Result<()> {
+    for task_id in task_ids {
+        let task: Option<(i32,)> = sqlx::query_as(
+            "SELECT task_id FROM tasks WHERE task_id = $1 AND project_id = $2 AND deleted_at IS NULL",
+        )
+        .bind(task_id)
+        .bind(project_id)
+        .fetch_optional(db)
+        .await?;
+        if task.is_none() {
+            return Err(ProjectError::InvalidInput(format!("unknown task {}", task_id)));
+        }
+    }
+    let committed = sprint_task_ids(db, sprint_id).await?;
+    for task_id in task_ids.iter().filter(|task_id| !committed.contains(task_id)) {
+        sqlx::query("INSERT INTO sprint_tasks (sprint_id, task_id) VALUES ($1, $2)")
+            .bind(sprint_id)
+            .bind(task_id)
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Checks the project exists and the caller runs its sprints.
+async fn authorize_sprint(
+    state: &AppState,
+    token_claims: &TokenClaims,
+    context: &RequestContext,
+    project_id: i32,
+) -> Result<ProjectResource> {
+    let resource = project_resource(&state.db, project_id).await?;
+    let permission = delegations::permission_for(state, token_claims).await?;
+    if !permission.is_authorized(token_claims, Action::ManageSprint, &resource, context)? {
+        return Err(ProjectError::AuthFailed);
+    }
+    Ok(resource)
+}
+
+#[get("/api/projects/{id}/sprints")]
+async fn list_sprints(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let mut sprints = sqlx::query_as::<_, Sprint>(&format!(
+                "SELECT {} FROM sprints WHERE project_id = $1 ORDER BY start_date, sprint_id",
+                SPRINT_COLUMNS
+            ))
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+            for sprint in sprints.iter_mut() {
+                sprint.task_ids = sprint_task_ids(&state.db, sprint.sprint_id).await?;
+            }
+
+            let json = serde_json::to_string(&sprints)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Plans a sprint of the project with its goal and committed tasks.
+#[post("/api/projects/{id}/sprints")]
+async fn create_sprint(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<SprintBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+            if body.name.trim().is_empty() {
+                return Err(ProjectError::InvalidInput("a name is required".to_string()));
+            }
+            if body.end_date <= body.start_date {
+                return Err(ProjectError::InvalidInput("a sprint ends after its start".to_string()));
+            }
+
+            let resource = authorize_sprint(&state, &tk, &context, id).await?;
+
+            let sprint = sqlx::query_as::<_, Sprint>(&format!(
+                "INSERT INTO sprints (project_id, name, goal, start_date, end_date, created_by)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING {}",
+                SPRINT_COLUMNS
+            ))
+            .bind(id)
+            .bind(body.name.trim())
+            .bind(&body.goal)
+            .bind(body.start_date.timestamp())
+            .bind(body.end_date.timestamp())
+            .bind(tk.id)
+            .fetch_one(&state.db)
+            .await?;
+            commit_tasks(&state.db, id, sprint.sprint_id, &body.task_ids).await?;
+
+            let detail = format!("sprint {} {}", sprint.sprint_id, sprint.name);
+            audit::record(&state.db, tk.id, "CreateSprint", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&fetch_sprint(&state.db, id, sprint.sprint_id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Commits more tasks to a sprint not closed yet.
+#[post("/api/projects/{id}/sprints/{sprint_id}/tasks")]
+async fn commit_sprint_tasks(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<CommitBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, sprint_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let sprint_id = sprint_id.parse::<i32>()?;
+
+            let resource = authorize_sprint(&state, &tk, &context, id).await?;
+            let sprint = fetch_sprint(&state.db, id, sprint_id).await?;
+            if sprint.status == "closed" {
+                return Err(ProjectError::InvalidInput(format!("sprint {} is closed", sprint_id)));
+            }
+            commit_tasks(&state.db, id, sprint_id, &body.task_ids).await?;
+
+            let task_ids: Vec<String> = body.task_ids.iter().map(i32::to_string).collect();
+            let detail = format!("sprint {} tasks {}", sprint_id, task_ids.join(" "));
+            audit::record(&state.db, tk.id, "CommitSprintTasks", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&fetch_sprint(&state.db, id, sprint_id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Starts a planned sprint, once the previous one of the project is closed.
+#[post("/api/projects/{id}/sprints/{sprint_id}/start")]
+async fn start_sprint(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, sprint_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let sprint_id = sprint_id.parse::<i32>()?;
+
+            let resource = authorize_sprint(&state, &tk, &context, id).await?;
+            let sprint = fetch_sprint(&state.db, id, sprint_id).await?;
+            if sprint.status != "planned" {
+                return Err(ProjectError::InvalidInput(format!("sprint {} is {}", sprint_id, sprint.status)));
+            }
+            let active: Option<(i32,)> =
+                sqlx::query_as("SELECT sprint_id FROM sprints WHERE project_id = $1 AND status = 'active'")
+                    .bind(id)
+                    .fetch_optional(&state.db)
+                    .await?;
+            if let Some((active,)) = active {
+                return Err(ProjectError::InvalidInput(format!("sprint {} is still active", active)));
+            }
+
+            sqlx::query("UPDATE sprints SET status = 'active' WHERE sprint_id = $1")
+                .bind(sprint_id)
+                .execute(&state.db)
+                .await?;
+
+            let detail = format!("sprint {} {}", sprint.sprint_id, sprint.name);
+            audit::record(&state.db, tk.id, "StartSprint", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&fetch_sprint(&state.db, id, sprint_id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Closes the active sprint: records the estimate of its tasks done, in the last state of the
+/// workflow of the project, and carries its unfinished tasks over.
+#[post("/api/projects/{id}/sprints/{sprint_id}/close")]
+async fn close_sprint(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<CloseBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, sprint_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let sprint_id = sprint_id.parse::<i32>()?;
+
+            let resource = authorize_sprint(&state, &tk, &context, id).await?;
+            let sprint = fetch_sprint(&state.db, id, sprint_id).await?;
+            if sprint.status != "active" {
+                return Err(ProjectError::InvalidInput(format!("sprint {} is {}", sprint_id, sprint.status)));
+            }
+            if let Some(carry_over_to) = body.carry_over_to {
+                let next = fetch_sprint(&state.db, id, carry_over_to).await?;
+                if next.status != "planned" {
+                    return Err(ProjectError::InvalidInput(format!("sprint {} is {}", carry_over_to, next.status)));
+                }
+            }
+
+            let workflow = workflow(&state.db, id).await?;
+            let done_state = workflow.states.last().cloned().unwrap_or_default();
+            let tasks: Vec<(i32, String, i32)> = sqlx::query_as(
+                "SELECT tasks.task_id, tasks.status, tasks.duration FROM sprint_tasks
+                JOIN tasks ON tasks.task_id = sprint_tasks.task_id
+                WHERE sprint_tasks.sprint_id = $1
+                ORDER BY tasks.task_id",
+            )
+            .bind(sprint_id)
+            .fetch_all(&state.db)
+            .await?;
+            let (done, unfinished): (Vec<_>, Vec<_>) = tasks.into_iter().partition(|(_, status, _)| *status == done_state);
+
+            for (task_id, _, _) in &done {
+                sqlx::query("UPDATE sprint_tasks SET completed = 1 WHERE sprint_id = $1 AND task_id = $2")
+                    .bind(sprint_id)
+                    .bind(task_id)
+                    .execute(&state.db)
+                    .await?;
+            }
+            let completed_estimate: i32 = done.iter().map(|(_, _, duration)| duration).sum();
+            sqlx::query(
+                "UPDATE sprints SET status = 'closed', completed_estimate = $1, closed_at = CURRENT_TIMESTAMP
+                WHERE sprint_id = $2",
+            )
+            .bind(completed_estimate)
+            .bind(sprint_id)
+            .execute(&state.db)
+            .await?;
+            let unfinished: Vec<i32> = unfinished.into_iter().map(|(task_id, _, _)| task_id).collect();
+            if let Some(carry_over_to) = body.carry_over_to {
+                commit_tasks(&state.db, id, carry_over_to, &unfinished).await?;
+            }
+
+            let detail = format!(
+                "sprint {} {} completed {} days, {} tasks carried over",
+                sprint.sprint_id,
+                sprint.name,
+                completed_estimate,
+                if body.carry_over_to.is_some() { unfinished.len() } else { 0 }
+            );
+            audit::record(&state.db, tk.id, "CloseSprint", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&fetch_sprint(&state.db, id, sprint_id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Completed estimates of the closed sprints of the project and their average.
+#[get("/api/projects/{id}/velocity")]
+async fn get_velocity(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let sprints: Vec<(i32, i32)> = sqlx::query_as(
+                "SELECT sprint_id, completed_estimate FROM sprints
+                WHERE project_id = $1 AND status = 'closed'
+                ORDER BY end_date, sprint_id",
+            )
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+            let velocity = if sprints.is_empty() {
+                0.0
+            } else {
+                sprints.iter().map(|(_, completed)| *completed as f64).sum::<f64>() / sprints.len() as f64
+            };
+
+            let json = serde_json::to_string(&Velocity { sprints, velocity })?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn closed_sprint_carries_over_and_measures_velocity() {
+        let app = create_test_app().await;
+
+        let mut task_ids = vec![];
+        for (title, duration) in [("login", 2), ("logout", 3)] {
+            let req = test::TestRequest::post()
+                .uri("/api/projects/1/tasks")
+                .insert_header(bearer(projectlead()))
+                .set_json(serde_json::json!({ "title": title, "duration": duration }))
+                .to_request();
+            let task: Value = test::call_and_read_body_json(&app, req).await;
+            task_ids.push(task["task_id"].as_i64().unwrap());
+        }
+        let sprint_req = |token_claims: TokenClaims, name: &str, task_ids: &[i64]| {
+            test::TestRequest::post()
+                .uri("/api/projects/1/sprints")
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({
+                    "name": name,
+                    "start_date": "2030-01-01T00:00:00Z",
+                    "end_date": "2030-01-15T00:00:00Z",
+                    "task_ids": task_ids,
+                }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, sprint_req(developer(), "sprint 1", &task_ids)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let sprint: Value = test::call_and_read_body_json(&app, sprint_req(projectlead(), "sprint 1", &task_ids)).await;
+        let sprint_id = sprint["sprint_id"].as_i64().unwrap();
+        assert_eq!(sprint["task_ids"], serde_json::json!(task_ids));
+        let next: Value = test::call_and_read_body_json(&app, sprint_req(projectlead(), "sprint 2", &[])).await;
+        let next_id = next["sprint_id"].as_i64().unwrap();
+
+        let start_req = |sprint_id: i64| {
+            test::TestRequest::post()
+                .uri(&format!("/api/projects/1/sprints/{}/start", sprint_id))
+                .insert_header(bearer(projectlead()))
+                .to_request()
+        };
+        let resp = test::call_service(&app, start_req(sprint_id)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = test::call_service(&app, start_req(next_id)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        for to in ["in_progress", "done"] {
+            let req = test::TestRequest::post()
+                .uri(&format!("/api/projects/1/tasks/{}/transitions", task_ids[0]))
+                .insert_header(bearer(projectlead()))
+                .set_json(serde_json::json!({ "to": to }))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/projects/1/sprints/{}/close", sprint_id))
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "carry_over_to": next_id }))
+            .to_request();
+        let sprint: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(sprint["status"], "closed");
+        assert_eq!(sprint["completed_estimate"], 2);
+
+        let resp = test::call_service(&app, start_req(next_id)).await;
+        let next: Value = test::read_body_json(resp).await;
+        assert_eq!(next["status"], "active");
+        assert_eq!(next["task_ids"], serde_json::json!([task_ids[1]]));
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1/velocity")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1/velocity")
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let velocity: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(velocity["sprints"], serde_json::json!([[sprint_id, 2]]));
+        assert_eq!(velocity["velocity"], 2.0);
+    }
+}
@@ -0,0 +1,586 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post, put,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+use super::workflows::{permission_for_workflow, workflow};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+#[derive(Deserialize)]
+struct TaskBody {
+    title: String,
+    #[serde(default)]
+    description: String,
+    /// The initial state of the workflow by default, changed through the transitions only
+    #[serde(default)]
+    status: Option<String>,
+    /// party_role_id of the assignee
+    #[serde(default)]
+    assignee: Option<i32>,
+    #[serde(default)]
+    due_date: Option<DateTime<Utc>>,
+    /// Estimate in days, 1 by default
+    #[serde(default = "default_duration")]
+    duration: i32,
+    /// milestone_id of a milestone of the project
+    #[serde(default)]
+    milestone_id: Option<i32>,
+}
+
+fn default_duration() -> i32 {
+    1
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct Task {
+    task_id: i32,
+    project_id: i32,
+    title: String,
+    description: String,
+    status: String,
+    /// party_role_id of the assignee
+    assignee: Option<i32>,
+    /// unix timestamp (seconds)
+    due_date: Option<i64>,
+    /// days
+    duration: i32,
+    milestone_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct TransitionBody {
+    to: String,
+}
+
+#[derive(FromRow)]
+struct TaskAssignee {
+    task_id: i32,
+    status: String,
+    /// party_id of the assignee
+    assignee: Option<i32>,
+    milestone_id: Option<i32>,
+}
+
+const TASK_COLUMNS: &str =
+    "task_id, project_id, title, description, status, assignee, due_date, duration, milestone_id";
+
+/// Loads the Cedar `Task` entities of a project, all of them when `task_id` is none.
+async fn task_resources(
+    db: &Pool<Any>,
+    project: &ProjectResource,
+    task_id: Option<i32>,
+) -> Result<Vec<TaskResource>> {
+    let tasks = sqlx::query_as::<_, TaskAssignee>(
+        "SELECT tasks.task_id, tasks.status, party_role.party_id AS assignee, tasks.milestone_id FROM tasks
+        LEFT JOIN party_role ON party_role.party_role_id = tasks.assignee
+        WHERE tasks.project_id = $1 AND ($2 IS NULL OR tasks.task_id = $2) AND tasks.deleted_at IS NULL
+        ORDER BY tasks.task_id",
+    )
+    .bind(project.id)
+    .bind(task_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(tasks
+        .into_iter()
+        .map(|task| TaskResource {
+            id: task.task_id,
+            project: project.clone(),
+            status: task.status,
+            assignee: task.assignee,
+            milestone: task.milestone_id,
+        })
+        .collect())
+}
+
+pub(crate) async fn task_resource(db: &Pool<Any>, project_id: i32, task_id: i32) -> Result<TaskResource> {
+    let project = project_resource(db, project_id).await?;
+    task_resources(db, &project, Some(task_id))
+        .await?
+        .pop()
+        .ok_or(ProjectError::Sqlx(sqlx::Error::RowNotFound))
+}
+
+/// Records a status change of the task in its history, `from` is none on creation.
+async fn record_status(db: &Pool<Any>, task: &Task, from: Option<&str>, party_id: i32) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO task_status_history (task_id, project_id, from_state, to_state, changed_by, changed_at)
+        VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(task.task_id)
+    .bind(task.project_id)
+    .bind(from)
+    .bind(&task.status)
+    .bind(party_id)
+    .bind(Utc::now().timestamp())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+async fn fetch_task(db: &Pool<Any>, task_id: i32) -> Result<Task> {
+    let task = sqlx::query_as::<_, Task>(&format!("SELECT {} FROM tasks WHERE task_id = $1", TASK_COLUMNS))
+        .bind(task_id)
+        .fetch_one(db)
+        .await?;
+    Ok(task)
+}
+
+/// Validates the body: a title, a duration, an assignee of the organization of the project, a
+/// milestone of the project.
+async fn validate(db: &Pool<Any>, project: &ProjectResource, body: &TaskBody) -> Result<()> {
+    if body.title.trim().is_empty() {
+        return Err(ProjectError::InvalidInput("a title is required".to_string()));
+    }
+    if body.duration < 0 {
+        return Err(ProjectError::InvalidInput("the duration cannot be negative".to_string()));
+    }
+    if let Some(assignee) = body.assignee {
+        let tenant: Option<(i32,)> = sqlx::query_as(
+            "SELECT parties.tenant_id FROM party_role
+            JOIN parties ON parties.party_id = party_role.party_id
+            WHERE party_role.party_role_id = $1",
+        )
+        .bind(assignee)
+        .fetch_optional(db)
+        .await?;
+        if tenant.map(|(tenant_id,)| tenant_id) != Some(project.tenant) {
+            return Err(ProjectError::InvalidInput(format!("unknown assignee {}", assignee)));
+        }
+    }
+    if let Some(milestone_id) = body.milestone_id {
+        let milestone: Option<(i32,)> =
+            sqlx::query_as("SELECT milestone_id FROM milestones WHERE milestone_id = $1 AND project_id = $2")
+                .bind(milestone_id)
+                .bind(project.id)
+                .fetch_optional(db)
+                .await?;
+        if milestone.is_none() {
+            return Err(ProjectError::InvalidInput(format!("unknown milestone {}", milestone_id)));
+        }
+    }
+    Ok(())
+}
+
+/// The tasks of the project the caller may view.
+#[get("/api/projects/{id}/tasks")]
+async fn list_tasks(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let project = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            let mut visible = vec![];
+            for resource in task_resources(&state.db, &project, None).await? {
+                if permission.is_authorized(&tk, Action::ViewTask, &resource, &context)? {
+                    visible.push(resource.id);
+                }
+            }
+
+            let tasks = sqlx::query_as::<_, Task>(&format!(
+                "SELECT {} FROM tasks WHERE project_id = $1 AND deleted_at IS NULL ORDER BY task_id",
+                TASK_COLUMNS
+            ))
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+            let tasks: Vec<Task> = tasks
+                .into_iter()
+                .filter(|task| visible.contains(&task.task_id))
+                .collect();
+
+            let json = serde_json::to_string(&tasks)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[post("/api/projects/{id}/tasks")]
+async fn create_task(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<TaskBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::CreateTask, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            validate(&state.db, &resource, &body).await?;
+            let workflow = workflow(&state.db, id).await?;
+            let status = match &body.status {
+                Some(status) if workflow.has_state(status) => status.clone(),
+                Some(status) => return Err(ProjectError::InvalidInput(format!("unknown status {}", status))),
+                None => workflow
+                    .initial_state()
+                    .ok_or_else(|| ProjectError::InvalidInput("the project has no workflow".to_string()))?
+                    .to_string(),
+            };
+
+            let task = sqlx::query_as::<_, Task>(&format!(
+                "INSERT INTO tasks (project_id, title, description, status, assignee, due_date, duration, milestone_id,
+                created_by, updated_by)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $9)
+                RETURNING {}",
+                TASK_COLUMNS
+            ))
+            .bind(id)
+            .bind(body.title.trim())
+            .bind(&body.description)
+            .bind(&status)
+            .bind(body.assignee)
+            .bind(body.due_date.map(|due_date| due_date.timestamp()))
+            .bind(body.duration)
+            .bind(body.milestone_id)
+            .bind(tk.id)
+            .fetch_one(&state.db)
+            .await?;
+            record_status(&state.db, &task, None, tk.id).await?;
+
+            let detail = format!("task {} {}", task.task_id, task.title);
+            audit::record(&state.db, tk.id, "CreateTask", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&task)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[get("/api/projects/{id}/tasks/{task_id}")]
+async fn get_task(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, task_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let task_id = task_id.parse::<i32>()?;
+
+            let resource = task_resource(&state.db, id, task_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewTask, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let json = serde_json::to_string(&fetch_task(&state.db, task_id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[put("/api/projects/{id}/tasks/{task_id}")]
+async fn update_task(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<TaskBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, task_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let task_id = task_id.parse::<i32>()?;
+            let body = body.into_inner();
+
+            let resource = task_resource(&state.db, id, task_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::UpdateTask, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            validate(&state.db, &resource.project, &body).await?;
+            if body.status.as_ref().is_some_and(|status| *status != resource.status) {
+                return Err(ProjectError::InvalidInput(
+                    "the status changes through the transitions of the workflow".to_string(),
+                ));
+            }
+
+            let task = sqlx::query_as::<_, Task>(&format!(
+                "UPDATE tasks SET title = $1, description = $2, assignee = $3, due_date = $4, duration = $5,
+                milestone_id = $6, updated_by = $7, updated_at = CURRENT_TIMESTAMP
+                WHERE task_id = $8
+                RETURNING {}",
+                TASK_COLUMNS
+            ))
+            .bind(body.title.trim())
+            .bind(&body.description)
+            .bind(body.assignee)
+            .bind(body.due_date.map(|due_date| due_date.timestamp()))
+            .bind(body.duration)
+            .bind(body.milestone_id)
+            .bind(tk.id)
+            .bind(task_id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!("task {} {} ({})", task.task_id, task.title, task.status);
+            audit::record(&state.db, tk.id, "UpdateTask", &resource.project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&task)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/projects/{id}/tasks/{task_id}")]
+async fn delete_task(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, task_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let task_id = task_id.parse::<i32>()?;
+
+            let resource = task_resource(&state.db, id, task_id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::DeleteTask, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            // the task is kept, deleted, for its status history; the time logged against it
+            // is logged against the project
+            let task = fetch_task(&state.db, task_id).await?;
+            let mut tx = state.db.begin().await?;
+            sqlx::query("DELETE FROM task_dependencies WHERE predecessor_id = $1 OR successor_id = $1")
+                .bind(task_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM sprint_tasks WHERE task_id = $1")
+                .bind(task_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("UPDATE time_entries SET task_id = NULL WHERE task_id = $1")
+                .bind(task_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "UPDATE tasks SET deleted_at = $1, updated_by = $2, updated_at = CURRENT_TIMESTAMP
+                WHERE task_id = $3",
+            )
+            .bind(Utc::now().timestamp())
+            .bind(tk.id)
+            .bind(task_id)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            let detail = format!("task {} {}", task.task_id, task.title);
+            audit::record(&state.db, tk.id, "DeleteTask", &resource.project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&task)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Moves the task to another state of the workflow of its project, along an allowed transition.
+#[post("/api/projects/{id}/tasks/{task_id}/transitions")]
+async fn transition_task(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<TransitionBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, task_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let task_id = task_id.parse::<i32>()?;
+            let to = body.into_inner().to;
+
+            let resource = task_resource(&state.db, id, task_id).await?;
+            if !workflow(&state.db, id).await?.allows(&resource.status, &to) {
+                return Err(ProjectError::InvalidInput(format!(
+                    "illegal transition {} -> {}",
+                    resource.status, to
+                )));
+            }
+            let permission = permission_for_workflow(&state, &tk, id).await?;
+            let context = context.with_transition(&resource.status, &to);
+            let authorization = permission.authorize(&tk, Action::TransitionTask, &resource, &context)?;
+            if !authorization.allowed {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let task = sqlx::query_as::<_, Task>(&format!(
+                "UPDATE tasks SET status = $1, updated_by = $2, updated_at = CURRENT_TIMESTAMP
+                WHERE task_id = $3
+                RETURNING {}",
+                TASK_COLUMNS
+            ))
+            .bind(&to)
+            .bind(tk.id)
+            .bind(task_id)
+            .fetch_one(&state.db)
+            .await?;
+            record_status(&state.db, &task, Some(&resource.status), tk.id).await?;
+
+            let detail = format!("task {} {} -> {}", task.task_id, resource.status, to);
+            audit::record(
+                &state.db,
+                tk.id,
+                "TransitionTask",
+                &resource.project.uid(),
+                &authorization.policy_ids,
+                &detail,
+            )
+            .await?;
+
+            let json = serde_json::to_string(&task)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use dotenv::dotenv;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn task_permissions_follow_the_project() {
+        let app = create_test_app().await;
+
+        let task_req = |token_claims: TokenClaims, uri: &str, status: &str| {
+            test::TestRequest::post()
+                .uri(uri)
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({ "title": "write the specs", "status": status }))
+                .to_request()
+        };
+
+        let task: Value = test::call_and_read_body_json(&app, task_req(projectlead(), "/api/projects/2/tasks", "todo")).await;
+        let uri = format!("/api/projects/2/tasks/{}", task["task_id"]);
+        let resp = test::call_service(&app, task_req(projectlead(), "/api/projects/1/tasks", "todo")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = test::call_service(&app, task_req(projectlead(), "/api/projects/1/tasks", "blocked")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // the developer is a contributor of project 2 only
+        let req = test::TestRequest::get()
+            .uri("/api/projects/2/tasks")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let tasks: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(tasks.len(), 1);
+        let req = test::TestRequest::get()
+            .uri("/api/projects/1/tasks")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let tasks: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(tasks.len(), 0);
+
+        let req = test::TestRequest::put()
+            .uri(&uri)
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "title": "write the first specs", "status": "todo" }))
+            .to_request();
+        let task: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(task["title"], "write the first specs");
+
+        let req = test::TestRequest::delete().uri(&uri).insert_header(bearer(developer())).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete().uri(&uri).insert_header(bearer(projectlead())).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn deleted_task_keeps_its_history() {
+        dotenv().ok();
+        sqlx::any::install_default_drivers();
+        let app_data = Arc::new(create_app_data().await.unwrap());
+        let db = app_data.db.clone();
+        let app = create_test_app_with(app_data).await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/tasks")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "title": "write the specs", "status": "todo" }))
+            .to_request();
+        let task: Value = test::call_and_read_body_json(&app, req).await;
+        let task_id = task["task_id"].as_i64().unwrap() as i32;
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/time-entries")
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "task_id": task_id, "date": "2030-01-09", "minutes": 90 }))
+            .to_request();
+        let entry: Value = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/api/projects/2/tasks/{}", task_id))
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get()
+            .uri("/api/projects/2/tasks")
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let tasks: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert!(tasks.is_empty());
+
+        let (history,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM task_status_history WHERE task_id = $1")
+            .bind(task_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(history, 1);
+        let (entry_task,): (Option<i32>,) = sqlx::query_as("SELECT task_id FROM time_entries WHERE entry_id = $1")
+            .bind(entry["entry_id"].as_i64().unwrap() as i32)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(entry_task, None);
+    }
+}
@@ -0,0 +1,467 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+#[derive(Deserialize)]
+struct CreateTeamBody {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TeamMemberBody {
+    party_id: i32,
+}
+
+#[derive(Deserialize)]
+struct AssignTeamBody {
+    team_id: i32,
+    /// `Contributor` by default
+    #[serde(default)]
+    project_role: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct Team {
+    team_id: i32,
+    tenant_id: i32,
+    name: String,
+    /// party_id of the creator
+    created_by: i32,
+}
+
+impl Team {
+    fn resource(&self) -> TeamResource {
+        TeamResource {
+            id: self.team_id,
+            tenant: self.tenant_id,
+            created_by: Some(self.created_by),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TeamDetail {
+    #[serde(flatten)]
+    team: Team,
+    /// party_id of the members
+    members: Vec<i32>,
+}
+
+async fn fetch_team(db: &Pool<Any>, team_id: i32) -> Result<Team> {
+    let team = sqlx::query_as::<_, Team>(
+        "SELECT team_id, tenant_id, name, created_by FROM teams WHERE team_id = $1",
+    )
+    .bind(team_id)
+    .fetch_one(db)
+    .await?;
+    Ok(team)
+}
+
+async fn team_detail(db: &Pool<Any>, team: Team) -> Result<TeamDetail> {
+    let members: Vec<(i32,)> = sqlx::query_as(
+        "SELECT party_id FROM team_members WHERE team_id = $1 ORDER BY party_id",
+    )
+    .bind(team.team_id)
+    .fetch_all(db)
+    .await?;
+    Ok(TeamDetail {
+        team,
+        members: members.into_iter().map(|(party_id,)| party_id).collect(),
+    })
+}
+
+#[post("/api/teams")]
+async fn create_team(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    body: Json<CreateTeamBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let name = body.into_inner().name.trim().to_string();
+            if name.is_empty() {
+                return Err(ProjectError::InvalidInput("a team name is required".to_string()));
+            }
+
+            let resource = GroupResource::all_projects();
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageTeam, &resource, &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let team = sqlx::query_as::<_, Team>(
+                "INSERT INTO teams (tenant_id, name, created_by)
+                VALUES ($1, $2, $3)
+                RETURNING team_id, tenant_id, name, created_by",
+            )
+            .bind(tk.tenant_id)
+            .bind(&name)
+            .bind(tk.id)
+            .fetch_one(&state.db)
+            .await?;
+
+            let detail = format!("team {} {}", team.team_id, team.name);
+            audit::record(&state.db, tk.id, "CreateTeam", &team.resource().uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&team_detail(&state.db, team).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// The teams of the organization of the caller it manages.
+#[get("/api/teams")]
+async fn list_teams(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let teams = sqlx::query_as::<_, Team>(
+                "SELECT team_id, tenant_id, name, created_by FROM teams
+                WHERE tenant_id = $1
+                ORDER BY team_id",
+            )
+            .bind(tk.tenant_id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let mut details = vec![];
+            for team in teams {
+                if state
+                    .permission
+                    .is_authorized(&tk, Action::ManageTeam, &team.resource(), &context)?
+                {
+                    details.push(team_detail(&state.db, team).await?);
+                }
+            }
+
+            let json = serde_json::to_string(&details)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Adds a party of the organization of the team.
+#[post("/api/teams/{id}/members")]
+async fn add_team_member(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<TeamMemberBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let team_id = path.into_inner().parse::<i32>()?;
+            let party_id = body.into_inner().party_id;
+
+            let team = fetch_team(&state.db, team_id).await?;
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageTeam, &team.resource(), &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+            if party_tenant(&state.db, party_id).await? != Some(team.tenant_id) {
+                return Err(ProjectError::InvalidInput(format!("unknown party {}", party_id)));
+            }
+
+            sqlx::query("INSERT INTO team_members (team_id, party_id) VALUES ($1, $2)")
+                .bind(team_id)
+                .bind(party_id)
+                .execute(&state.db)
+                .await?;
+
+            let detail = format!("party {} added to team {}", party_id, team_id);
+            audit::record(&state.db, tk.id, "AddTeamMember", &team.resource().uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&team_detail(&state.db, team).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/teams/{id}/members/{party_id}")]
+async fn remove_team_member(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (team_id, party_id) = path.into_inner();
+            let team_id = team_id.parse::<i32>()?;
+            let party_id = party_id.parse::<i32>()?;
+
+            let team = fetch_team(&state.db, team_id).await?;
+            if !state
+                .permission
+                .is_authorized(&tk, Action::ManageTeam, &team.resource(), &context)?
+            {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            sqlx::query("DELETE FROM team_members WHERE team_id = $1 AND party_id = $2")
+                .bind(team_id)
+                .bind(party_id)
+                .execute(&state.db)
+                .await?;
+
+            let detail = format!("party {} removed from team {}", party_id, team_id);
+            audit::record(&state.db, tk.id, "RemoveTeamMember", &team.resource().uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&team_detail(&state.db, team).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Assigns every member of a team to the project, the caller needs `AssignPartyrole`.
+#[post("/api/projects/{id}/teams")]
+async fn assign_team(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<AssignTeamBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+            let project_role = body.project_role.unwrap_or_else(|| PROJECT_ROLES[0].to_string());
+            if !PROJECT_ROLES.contains(&project_role.as_str()) {
+                return Err(ProjectError::InvalidInput(format!("unknown project role {}", project_role)));
+            }
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::AssignPartyrole, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            let team = fetch_team(&state.db, body.team_id).await?;
+            if team.tenant_id != resource.tenant {
+                return Err(ProjectError::InvalidInput(format!("unknown team {}", body.team_id)));
+            }
+
+            sqlx::query(
+                "INSERT INTO team_assignments (team_id, project_id, project_role, created_by)
+                VALUES ($1, $2, $3, $4)",
+            )
+            .bind(team.team_id)
+            .bind(id)
+            .bind(&project_role)
+            .bind(tk.id)
+            .execute(&state.db)
+            .await?;
+
+            let detail = format!("team {} assigned as {}", team.team_id, project_role);
+            audit::record(&state.db, tk.id, "AssignTeam", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&team_detail(&state.db, team).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/projects/{id}/teams/{team_id}")]
+async fn unassign_team(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, team_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let team_id = team_id.parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::AssignPartyrole, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            sqlx::query("DELETE FROM team_assignments WHERE team_id = $1 AND project_id = $2")
+                .bind(team_id)
+                .bind(id)
+                .execute(&state.db)
+                .await?;
+
+            let detail = format!("team {} unassigned", team_id);
+            audit::record(&state.db, tk.id, "UnassignTeam", &resource.uid(), &[], &detail).await?;
+
+            let team = fetch_team(&state.db, team_id).await?;
+            let json = serde_json::to_string(&team_detail(&state.db, team).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use dotenv::dotenv;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn team_assignment_grants_its_members() {
+        let app = create_test_app().await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/teams")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "name": "platform" }))
+            .to_request();
+        let team: Value = test::call_and_read_body_json(&app, req).await;
+        let team_id = team["team_id"].as_i64().unwrap();
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/teams/{}/members", team_id))
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "party_id": 4 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/teams/{}/members", team_id))
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "party_id": 4 }))
+            .to_request();
+        let team: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(team["members"], serde_json::json!([4]));
+
+        let resp = test::call_service(&app, get_project_req(developer(), 1)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/1/teams")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "team_id": team_id }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(&app, get_project_req(developer(), 1)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let projects: Vec<Value> = test::call_and_read_body_json(&app, view_project_req(developer())).await;
+        let ids: Vec<&Value> = projects.iter().map(|project| &project["id"]).collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        // leaving the team revokes the access without touching the project
+        let req = test::TestRequest::delete()
+            .uri(&format!("/api/teams/{}/members/4", team_id))
+            .insert_header(bearer(projectlead()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(&app, get_project_req(developer(), 1)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn only_the_creator_manages_a_team() {
+        dotenv().ok();
+        sqlx::any::install_default_drivers();
+        let app_data = Arc::new(create_app_data().await.unwrap());
+        for statement in [
+            "INSERT INTO parties (party_id, first_name, last_name, created_by) VALUES (5, 'Lena', 'Olsen', 1)",
+            "INSERT INTO party_role (party_role_id, party_id, role_type_id, created_by) VALUES (5, 5, 3, 1)",
+        ] {
+            sqlx::query(statement).execute(&app_data.db).await.unwrap();
+        }
+        let app = create_test_app_with(app_data).await;
+
+        let other_lead = || TokenClaims {
+            id: 5,
+            roles: vec!["ProjectLead".to_string()],
+            ..Default::default()
+        };
+        let member_req = |token_claims: TokenClaims, team_id: i64| {
+            test::TestRequest::post()
+                .uri(&format!("/api/teams/{}/members", team_id))
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({ "party_id": 5 }))
+                .to_request()
+        };
+
+        let req = test::TestRequest::post()
+            .uri("/api/teams")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "name": "platform" }))
+            .to_request();
+        let team: Value = test::call_and_read_body_json(&app, req).await;
+        let team_id = team["team_id"].as_i64().unwrap();
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/teams")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "team_id": team_id, "project_role": "Maintainer" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // another lead cannot join the maintainers of a project it does not own
+        let resp = test::call_service(&app, member_req(other_lead(), team_id)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = test::call_service(&app, get_project_req(other_lead(), 2)).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        // nor assign its own team to that project
+        let req = test::TestRequest::post()
+            .uri("/api/teams")
+            .insert_header(bearer(other_lead()))
+            .set_json(serde_json::json!({ "name": "intruders" }))
+            .to_request();
+        let team: Value = test::call_and_read_body_json(&app, req).await;
+        let resp = test::call_service(&app, member_req(other_lead(), team["team_id"].as_i64().unwrap())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/teams")
+            .insert_header(bearer(other_lead()))
+            .set_json(serde_json::json!({ "team_id": team["team_id"], "project_role": "Maintainer" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        // administrators manage every team
+        let resp = test::call_service(&app, member_req(admin(), team_id)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
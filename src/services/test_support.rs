@@ -0,0 +1,201 @@
+//! The application under test, on a fresh in-memory database seeded by the migrations, and
+//! the parties of the seed: the administrator (2), the project lead owning projects 1 and 2 (3)
+//! and the developer assigned to project 2 (4).
+
+use std::sync::Arc;
+
+use crate::repository::{Migrate, Repository};
+use crate::services::*;
+use crate::validator;
+use crate::AppState;
+use actix_http::Request;
+use actix_web::dev::Service;
+use actix_web::http::header::ContentType;
+use actix_web::test;
+use actix_web::web::{self, Data};
+use actix_web::App;
+use actix_web_httpauth::middleware::HttpAuthentication;
+use dotenv::dotenv;
+use sqlx::any::AnyPoolOptions;
+use sqlx::{Any, Pool};
+
+use super::projects::Result;
+
+pub(crate) async fn create_app_data() -> Result<AppState> {
+    dotenv().ok();
+
+    let db_url = "sqlite::memory:";
+
+    let pool: Pool<Any> = {
+        AnyPoolOptions::new()
+        .max_connections(1)
+        .connect(&db_url)
+        .await.unwrap()
+    };
+
+    let repo = Repository::new(&pool, "./sqlite-migrations").await;
+    
+    let migration_error = repo.migrate().await.map_err(|err| err.to_string());
+    assert_eq!(migration_error, Ok(()));
+
+    let roles = RoleHierarchy::load(&pool).await?;
+
+    Ok(AppState {
+        db: pool.clone(),
+        permission: Permission::default().with_role_hierarchy(roles),
+    })
+}
+
+pub(crate) async fn create_test_app() -> impl Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error>
+ {
+    dotenv().ok();
+    sqlx::any::install_default_drivers();
+    let app_data = Arc::new(create_app_data().await.unwrap());
+    create_test_app_with(app_data).await
+}
+
+pub(crate) async fn create_test_app_with(app_data: Arc<AppState>) -> impl Service<
+    actix_http::Request,
+    Response = actix_web::dev::ServiceResponse,
+    Error = actix_web::Error>
+ {
+    let bearer_middleware = HttpAuthentication::with_fn(validator);
+
+    test::init_service(
+        App::new()
+            .app_data(Data::new(app_data.clone()))
+            .service(
+                web::scope("")
+                    .wrap(bearer_middleware.clone())
+                    .service(list_projects)
+                    .service(create_project)
+                    .service(get_project)
+                    .service(update_project)
+                    .service(create_delegation)
+                    .service(create_guest_invite)
+                    .service(revoke_guest_invite)
+                    .service(list_audit_entries)
+                    .service(request_break_glass)
+                    .service(create_role_request)
+                    .service(list_role_requests)
+                    .service(approve_role_request)
+                    .service(reject_role_request)
+                    .service(create_access_review_campaign)
+                    .service(get_access_review)
+                    .service(decide_access_review_item)
+                    .service(export_access_review)
+                    .service(create_team)
+                    .service(add_team_member)
+                    .service(remove_team_member)
+                    .service(assign_team)
+                    .service(list_tasks)
+                    .service(create_task)
+                    .service(get_task)
+                    .service(update_task)
+                    .service(delete_task)
+                    .service(transition_task)
+                    .service(get_workflow)
+                    .service(update_workflow)
+                    .service(create_dependency)
+                    .service(get_schedule)
+                    .service(milestone_rollup)
+                    .service(create_milestone)
+                    .service(create_sprint)
+                    .service(start_sprint)
+                    .service(close_sprint)
+                    .service(get_velocity)
+                    .service(cumulative_flow_report)
+                    .service(create_time_entry)
+                    .service(update_time_entry)
+                    .service(get_timesheet)
+                    .service(get_project_time)
+                    .service(approve_timesheet)
+                    .service(set_cost_rate)
+                    .service(get_budget)
+                    .service(list_assignments)
+                    .service(update_allocation)
+                    .service(get_capacity)
+                    .service(get_calendar)
+                    .service(update_weekend)
+                    .service(create_holiday)
+                    .service(delete_holiday)
+                    .service(import_holidays)
+                    .service(list_leave)
+                    .service(create_leave)
+                    .service(delete_leave)
+                    .service(list_articles)
+                    .service(create_article)
+                    .service(get_article)
+                    .service(update_article)
+                    .service(list_article_revisions)
+                    .service(restore_article_revision)
+                    .service(publish_article)
+                    .service(delete_article),
+            ),
+    )
+    .await
+}
+
+pub(crate) fn bearer(token_claims: TokenClaims) -> (&'static str, String) {
+    let token = TokenService::generate_token(token_claims).unwrap();
+    ("Authorization", format!("Bearer {}", token))
+}
+
+pub(crate) fn update_project_req(token_claims: TokenClaims, id: i32, status: &str) -> Request {
+    test::TestRequest::put()
+        .uri(&format!("/api/projects/{}", id))
+        .insert_header(bearer(token_claims))
+        .set_json(serde_json::json!({
+            "name": "my project",
+            "description": "this project",
+            "status": status,
+            "budget": 1000.0,
+            "internal_description": "internal",
+        }))
+        .to_request()
+}
+
+pub(crate) fn get_project_req(token_claims: TokenClaims, id: i32) -> Request {
+    test::TestRequest::get()
+        .uri(&format!("/api/projects/{}", id))
+        .insert_header(bearer(token_claims))
+        .to_request()
+}
+
+pub(crate) fn view_project_req(token_claims: TokenClaims) -> Request {
+    let token = TokenService::generate_token(token_claims).unwrap();
+    
+    let req = test::TestRequest::get().uri("/api/projects")
+        .insert_header(ContentType::plaintext())
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    req
+}
+
+pub(crate) fn admin() -> TokenClaims {
+    TokenClaims {
+        id: 2,
+        roles: vec!["Administrator".to_string()],
+        ..Default::default()
+    }
+}
+
+pub(crate) fn projectlead() -> TokenClaims {
+    TokenClaims {
+        id: 3,
+        roles: vec!["ProjectLead".to_string()],
+        ..Default::default()
+    }
+}
+
+pub(crate) fn developer() -> TokenClaims {
+    TokenClaims {
+        id: 4,
+        roles: vec!["Developer".to_string()],
+        ..Default::default()
+    }
+}
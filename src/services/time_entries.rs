@@ -0,0 +1,617 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    delete, get, post, put,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool};
+
+/// Longest time entry, a day.
+const MAX_MINUTES: i32 = 24 * 60;
+
+#[derive(Deserialize)]
+struct TimeEntryBody {
+    /// task_id of a task of the project, none for time logged against the project
+    #[serde(default)]
+    task_id: Option<i32>,
+    date: NaiveDate,
+    minutes: i32,
+    #[serde(default)]
+    note: String,
+    #[serde(default = "default_billable")]
+    billable: bool,
+}
+
+fn default_billable() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct ApprovalBody {
+    party_id: i32,
+    /// A day of the week approved
+    week: NaiveDate,
+}
+
+#[derive(FromRow)]
+struct TimeEntryRow {
+    entry_id: i32,
+    project_id: i32,
+    task_id: Option<i32>,
+    party_id: i32,
+    date: i64,
+    minutes: i32,
+    note: String,
+    billable: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct TimeEntry {
+    entry_id: i32,
+    project_id: i32,
+    task_id: Option<i32>,
+    party_id: i32,
+    /// unix timestamp (seconds) of the day
+    date: i64,
+    minutes: i32,
+    note: String,
+    billable: bool,
+}
+
+impl From<TimeEntryRow> for TimeEntry {
+    fn from(row: TimeEntryRow) -> Self {
+        TimeEntry {
+            entry_id: row.entry_id,
+            project_id: row.project_id,
+            task_id: row.task_id,
+            party_id: row.party_id,
+            date: row.date,
+            minutes: row.minutes,
+            note: row.note,
+            billable: row.billable != 0,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+struct Hours {
+    hours: f64,
+    billable_hours: f64,
+}
+
+impl Hours {
+    fn add(&mut self, entry: &TimeEntry) {
+        let hours = entry.minutes as f64 / 60.0;
+        self.hours += hours;
+        if entry.billable {
+            self.billable_hours += hours;
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct TimesheetWeek {
+    week_start: NaiveDate,
+    #[serde(flatten)]
+    hours: Hours,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct PartyHours {
+    party_id: i32,
+    #[serde(flatten)]
+    hours: Hours,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct ProjectTime {
+    #[serde(flatten)]
+    hours: Hours,
+    parties: Vec<PartyHours>,
+}
+
+const TIME_ENTRY_COLUMNS: &str = "entry_id, project_id, task_id, party_id, date, minutes, note, billable";
+
+/// Unix timestamp (seconds) of midnight UTC of the day.
+fn day_timestamp(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()
+}
+
+/// The Monday of the week of the day.
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn week_of(timestamp: i64) -> NaiveDate {
+    week_start(DateTime::from_timestamp(timestamp, 0).unwrap_or_default().date_naive())
+}
+
+/// The timesheet of the party on the project for the week of the day is approved.
+async fn is_locked(db: &Pool<Any>, project_id: i32, party_id: i32, date: i64) -> Result<bool> {
+    let approval: Option<(i32,)> = sqlx::query_as(
+        "SELECT approval_id FROM timesheet_approvals
+        WHERE project_id = $1 AND party_id = $2 AND week_start = $3",
+    )
+    .bind(project_id)
+    .bind(party_id)
+    .bind(day_timestamp(week_of(date)))
+    .fetch_optional(db)
+    .await?;
+    Ok(approval.is_some())
+}
+
+async fn fetch_entry(db: &Pool<Any>, project_id: i32, entry_id: i32) -> Result<TimeEntry> {
+    let entry = sqlx::query_as::<_, TimeEntryRow>(&format!(
+        "SELECT {} FROM time_entries WHERE entry_id = $1 AND project_id = $2",
+        TIME_ENTRY_COLUMNS
+    ))
+    .bind(entry_id)
+    .bind(project_id)
+    .fetch_one(db)
+    .await?;
+    Ok(entry.into())
+}
+
+async fn entry_resource(db: &Pool<Any>, project: &ProjectResource, entry: &TimeEntry) -> Result<TimeEntryResource> {
+    Ok(TimeEntryResource {
+        id: entry.entry_id,
+        project: project.clone(),
+        party: entry.party_id,
+        locked: is_locked(db, entry.project_id, entry.party_id, entry.date).await?,
+    })
+}
+
+/// Validates the body: a duration within a day, a task of the project, a week not approved yet.
+async fn validate(db: &Pool<Any>, project_id: i32, party_id: i32, body: &TimeEntryBody) -> Result<()> {
+    if body.minutes <= 0 || body.minutes > MAX_MINUTES {
+        return Err(ProjectError::InvalidInput(format!(
+            "a time entry lasts 1 to {} minutes",
+            MAX_MINUTES
+        )));
+    }
+    if let Some(task_id) = body.task_id {
+        let task: Option<(i32,)> = sqlx::query_as(
+            "SELECT task_id FROM tasks WHERE task_id = $1 AND project_id = $2 AND deleted_at IS NULL",
+        )
+        .bind(task_id)
+        .bind(project_id)
+        .fetch_optional(db)
+        .await?;
+        if task.is_none() {
+            return Err(ProjectError::InvalidInput(format!("unknown task {}", task_id)));
+        }
+    }
+    if is_locked(db, project_id, party_id, day_timestamp(body.date)).await? {
+        return Err(ProjectError::InvalidInput(format!(
+            "the timesheet of the week of {} is approved",
+            body.date
+        )));
+    }
+    Ok(())
+}
+
+/// The entries of the project the caller may view.
+#[get("/api/projects/{id}/time-entries")]
+async fn list_time_entries(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let project = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            let entries = sqlx::query_as::<_, TimeEntryRow>(&format!(
+                "SELECT {} FROM time_entries WHERE project_id = $1 ORDER BY date, entry_id",
+                TIME_ENTRY_COLUMNS
+            ))
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let mut visible = vec![];
+            for entry in entries.into_iter().map(TimeEntry::from) {
+                let resource = entry_resource(&state.db, &project, &entry).await?;
+                if permission.is_authorized(&tk, Action::ViewTimeEntry, &resource, &context)? {
+                    visible.push(entry);
+                }
+            }
+
+            let json = serde_json::to_string(&visible)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Logs time of the caller on the project.
+#[post("/api/projects/{id}/time-entries")]
+async fn create_time_entry(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<TimeEntryBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::LogTime, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            validate(&state.db, id, tk.id, &body).await?;
+
+            let entry: TimeEntry = sqlx::query_as::<_, TimeEntryRow>(&format!(
+                "INSERT INTO time_entries (project_id, task_id, party_id, date, minutes, note, billable)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING {}",
+                TIME_ENTRY_COLUMNS
+            ))
+            .bind(id)
+            .bind(body.task_id)
+            .bind(tk.id)
+            .bind(day_timestamp(body.date))
+            .bind(body.minutes)
+            .bind(&body.note)
+            .bind(body.billable as i32)
+            .fetch_one(&state.db)
+            .await?
+            .into();
+
+            let detail = format!("time entry {} {} minutes on {}", entry.entry_id, entry.minutes, body.date);
+            audit::record(&state.db, tk.id, "LogTime", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&entry)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[put("/api/projects/{id}/time-entries/{entry_id}")]
+async fn update_time_entry(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+    body: Json<TimeEntryBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, entry_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let entry_id = entry_id.parse::<i32>()?;
+            let body = body.into_inner();
+
+            let project = project_resource(&state.db, id).await?;
+            let entry = fetch_entry(&state.db, id, entry_id).await?;
+            let resource = entry_resource(&state.db, &project, &entry).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::UpdateTimeEntry, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            validate(&state.db, id, entry.party_id, &body).await?;
+
+            let entry: TimeEntry = sqlx::query_as::<_, TimeEntryRow>(&format!(
+                "UPDATE time_entries SET task_id = $1, date = $2, minutes = $3, note = $4, billable = $5,
+                updated_at = CURRENT_TIMESTAMP
+                WHERE entry_id = $6
+                RETURNING {}",
+                TIME_ENTRY_COLUMNS
+            ))
+            .bind(body.task_id)
+            .bind(day_timestamp(body.date))
+            .bind(body.minutes)
+            .bind(&body.note)
+            .bind(body.billable as i32)
+            .bind(entry_id)
+            .fetch_one(&state.db)
+            .await?
+            .into();
+
+            let detail = format!("time entry {} {} minutes on {}", entry.entry_id, entry.minutes, body.date);
+            audit::record(&state.db, tk.id, "UpdateTimeEntry", &project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&entry)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[delete("/api/projects/{id}/time-entries/{entry_id}")]
+async fn delete_time_entry(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<(String, String)>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let (id, entry_id) = path.into_inner();
+            let id = id.parse::<i32>()?;
+            let entry_id = entry_id.parse::<i32>()?;
+
+            let project = project_resource(&state.db, id).await?;
+            let entry = fetch_entry(&state.db, id, entry_id).await?;
+            let resource = entry_resource(&state.db, &project, &entry).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::DeleteTimeEntry, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            sqlx::query("DELETE FROM time_entries WHERE entry_id = $1")
+                .bind(entry_id)
+                .execute(&state.db)
+                .await?;
+
+            let detail = format!("time entry {} {} minutes", entry.entry_id, entry.minutes);
+            audit::record(&state.db, tk.id, "DeleteTimeEntry", &project.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&entry)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Hours of the party per week, over the entries the caller may view: all of them for the party
+/// itself, those of their projects for the project owners.
+#[get("/api/parties/{party_id}/timesheet")]
+async fn get_timesheet(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let party_id = path.into_inner().parse::<i32>()?;
+
+            let permission = delegations::permission_for(&state, &tk).await?;
+            let entries = sqlx::query_as::<_, TimeEntryRow>(&format!(
+                "SELECT {} FROM time_entries WHERE party_id = $1 ORDER BY date, entry_id",
+                TIME_ENTRY_COLUMNS
+            ))
+            .bind(party_id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let mut projects: HashMap<i32, ProjectResource> = HashMap::new();
+            let mut weeks: Vec<TimesheetWeek> = vec![];
+            for entry in entries.into_iter().map(TimeEntry::from) {
+                if !projects.contains_key(&entry.project_id) {
+                    let project = project_resource(&state.db, entry.project_id).await?;
+                    projects.insert(entry.project_id, project);
+                }
+                let resource = entry_resource(&state.db, &projects[&entry.project_id], &entry).await?;
+                if !permission.is_authorized(&tk, Action::ViewTimeEntry, &resource, &context)? {
+                    continue;
+                }
+
+                let week_start = week_of(entry.date);
+                match weeks.iter_mut().find(|week| week.week_start == week_start) {
+                    Some(week) => week.hours.add(&entry),
+                    None => {
+                        let mut hours = Hours::default();
+                        hours.add(&entry);
+                        weeks.push(TimesheetWeek { week_start, hours });
+                    }
+                }
+            }
+
+            let json = serde_json::to_string(&weeks)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Hours logged on the project, in total and per party.
+#[get("/api/projects/{id}/time-totals")]
+async fn get_project_time(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewProjectTime, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let entries = sqlx::query_as::<_, TimeEntryRow>(&format!(
+                "SELECT {} FROM time_entries WHERE project_id = $1 ORDER BY party_id, entry_id",
+                TIME_ENTRY_COLUMNS
+            ))
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+
+            let mut total = ProjectTime {
+                hours: Hours::default(),
+                parties: vec![],
+            };
+            for entry in entries.into_iter().map(TimeEntry::from) {
+                total.hours.add(&entry);
+                match total.parties.iter_mut().find(|party| party.party_id == entry.party_id) {
+                    Some(party) => party.hours.add(&entry),
+                    None => {
+                        let mut hours = Hours::default();
+                        hours.add(&entry);
+                        total.parties.push(PartyHours {
+                            party_id: entry.party_id,
+                            hours,
+                        });
+                    }
+                }
+            }
+
+            let json = serde_json::to_string(&total)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Approves the timesheet of a party on the project for a week, locking its entries.
+#[post("/api/projects/{id}/timesheets/approve")]
+async fn approve_timesheet(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<ApprovalBody>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ApproveTimesheet, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+            let week_start = week_start(body.week);
+            if is_locked(&state.db, id, body.party_id, day_timestamp(week_start)).await? {
+                return Err(ProjectError::InvalidInput(format!(
+                    "the timesheet of the week of {} is approved already",
+                    week_start
+                )));
+            }
+
+            sqlx::query(
+                "INSERT INTO timesheet_approvals (project_id, party_id, week_start, approved_by)
+                VALUES ($1, $2, $3, $4)",
+            )
+            .bind(id)
+            .bind(body.party_id)
+            .bind(day_timestamp(week_start))
+            .bind(tk.id)
+            .execute(&state.db)
+            .await?;
+
+            let detail = format!("timesheet of party {} for the week of {}", body.party_id, week_start);
+            audit::record(&state.db, tk.id, "ApproveTimesheet", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&serde_json::json!({
+                "party_id": body.party_id,
+                "week_start": week_start,
+            }))?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn weeks_start_on_monday() {
+        let monday = NaiveDate::from_ymd_opt(2030, 1, 7).unwrap();
+        let sunday = NaiveDate::from_ymd_opt(2030, 1, 13).unwrap();
+        assert_eq!(week_start(monday), monday);
+        assert_eq!(week_start(sunday), monday);
+        assert_eq!(week_of(day_timestamp(sunday) + 23 * 60 * 60), monday);
+        assert_eq!(week_of(day_timestamp(sunday) + 24 * 60 * 60), sunday + Duration::days(1));
+    }
+
+    #[actix_web::test]
+    async fn time_entries_lock_once_the_timesheet_is_approved() {
+        let app = create_test_app().await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/time-entries")
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "date": "2030-01-09", "minutes": 90, "note": "review" }))
+            .to_request();
+        let entry: Value = test::call_and_read_body_json(&app, req).await;
+        let uri = format!("/api/projects/2/time-entries/{}", entry["entry_id"]);
+        let update_req = |token_claims: TokenClaims| {
+            test::TestRequest::put()
+                .uri(&uri)
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({ "date": "2030-01-09", "minutes": 120, "billable": false }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, update_req(projectlead())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = test::call_service(&app, update_req(developer())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let totals_req = |token_claims: TokenClaims| {
+            test::TestRequest::get()
+                .uri("/api/projects/2/time-totals")
+                .insert_header(bearer(token_claims))
+                .to_request()
+        };
+        let resp = test::call_service(&app, totals_req(developer())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let totals: Value = test::call_and_read_body_json(&app, totals_req(projectlead())).await;
+        assert_eq!(totals["hours"], 2.0);
+        assert_eq!(totals["billable_hours"], 0.0);
+
+        let req = test::TestRequest::get()
+            .uri("/api/parties/4/timesheet")
+            .insert_header(bearer(developer()))
+            .to_request();
+        let timesheet: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(timesheet, serde_json::json!([{ "week_start": "2030-01-07", "hours": 2.0, "billable_hours": 0.0 }]));
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/timesheets/approve")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "party_id": 4, "week": "2030-01-11" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(&app, update_req(developer())).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/time-entries")
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "date": "2030-01-10", "minutes": 30 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,364 @@
+use std::sync::Arc;
+
+use crate::AppState;
+use actix_web::{
+    get, put,
+    web::{self, Data, Json, ReqData},
+};
+
+use crate::services::*;
+
+use super::audit;
+use super::delegations;
+use super::projects::{project_resource, ProjectError, Result};
+
+use cedar_policy::{ParseErrors, Policy};
+use serde::{Deserialize, Serialize};
+use sqlx::{self, Any, FromRow, Pool, Transaction};
+
+/// States of the workflow seeded on project creation, the first one is the state of new tasks.
+pub const DEFAULT_STATES: [&str; 3] = ["todo", "in_progress", "done"];
+
+/// (from, to, project role) transitions of the default workflow.
+pub const DEFAULT_TRANSITIONS: [(&str, &str, &str); 4] = [
+    ("todo", "in_progress", "Contributor"),
+    ("in_progress", "todo", "Contributor"),
+    ("in_progress", "done", "Reviewer"),
+    ("done", "in_progress", "Reviewer"),
+];
+
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Transition {
+    from_state: String,
+    to_state: String,
+    /// Project role of the assignees allowed to perform the transition
+    project_role: String,
+}
+
+#[derive(FromRow)]
+struct WorkflowTransition {
+    transition_id: i32,
+    project_id: i32,
+    #[sqlx(flatten)]
+    transition: Transition,
+}
+
+impl WorkflowTransition {
+    /// The policy granting the transition to the assignees of its project role.
+    fn policy(&self) -> std::result::Result<Policy, ParseErrors> {
+        let id = format!("Workflow.{}", self.transition_id);
+        let src = format!(
+            r#"@id("{id}")
+permit(
+  principal,
+  action == Action::"TransitionTask",
+  resource in Project::"{project}"
+)
+when {{
+  context has transition &&
+  context.transition.from == "{from}" && context.transition.to == "{to}" &&
+  principal in resource.project.{project_role}
+}};"#,
+            id = id,
+            project = self.project_id,
+            from = self.transition.from_state,
+            to = self.transition.to_state,
+            project_role = project_role_attr(&self.transition.project_role),
+        );
+        Policy::parse(Some(id), src)
+    }
+}
+
+/// The workflow of a project: its states in order and the transitions between them.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub(crate) struct Workflow {
+    pub states: Vec<String>,
+    pub transitions: Vec<Transition>,
+}
+
+impl Workflow {
+    /// The state of the new tasks.
+    pub fn initial_state(&self) -> Option<&str> {
+        self.states.first().map(String::as_str)
+    }
+
+    pub fn has_state(&self, state: &str) -> bool {
+        self.states.iter().any(|s| s == state)
+    }
+
+    pub fn allows(&self, from: &str, to: &str) -> bool {
+        self.transitions
+            .iter()
+            .any(|transition| transition.from_state == from && transition.to_state == to)
+    }
+
+    /// States are policy literals: lowercase letters, digits and underscores only.
+    fn validate(&self) -> Result<()> {
+        if self.states.is_empty() {
+            return Err(ProjectError::InvalidInput("a workflow needs a state".to_string()));
+        }
+        for (i, state) in self.states.iter().enumerate() {
+            let valid = !state.is_empty()
+                && state
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !valid {
+                return Err(ProjectError::InvalidInput(format!("invalid state {}", state)));
+            }
+            if self.states[..i].contains(state) {
+                return Err(ProjectError::InvalidInput(format!("duplicate state {}", state)));
+            }
+        }
+        for transition in &self.transitions {
+            if !self.has_state(&transition.from_state) || !self.has_state(&transition.to_state) {
+                return Err(ProjectError::InvalidInput(format!(
+                    "unknown state in transition {} -> {}",
+                    transition.from_state, transition.to_state
+                )));
+            }
+            if !PROJECT_ROLES.contains(&transition.project_role.as_str()) {
+                return Err(ProjectError::InvalidInput(format!(
+                    "unknown project role {}",
+                    transition.project_role
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Seeds the default workflow of a new project.
+pub(crate) async fn seed_default_workflow(tx: &mut Transaction<'_, Any>, project_id: i32) -> Result<()> {
+    let workflow = Workflow {
+        states: DEFAULT_STATES.iter().map(|state| state.to_string()).collect(),
+        transitions: DEFAULT_TRANSITIONS
+            .iter()
+            .map(|(from, to, project_role)| Transition {
+                from_state: from.to_string(),
+                to_state: to.to_string(),
+                project_role: project_role.to_string(),
+            })
+            .collect(),
+    };
+    save_workflow(tx, project_id, &workflow).await
+}
+
+/// Replaces the states and transitions of the workflow of the project, committed by the caller.
+async fn save_workflow(tx: &mut Transaction<'_, Any>, project_id: i32, workflow: &Workflow) -> Result<()> {
+    sqlx::query("DELETE FROM workflow_transitions WHERE project_id = $1")
+        .bind(project_id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM workflow_states WHERE project_id = $1")
+        .bind(project_id)
+        .execute(&mut **tx)
+        .await?;
+    for (position, state) in workflow.states.iter().enumerate() {
+        sqlx::query("INSERT INTO workflow_states (project_id, state, position) VALUES ($1, $2, $3)")
+            .bind(project_id)
+            .bind(state)
+            .bind(position as i32)
+            .execute(&mut **tx)
+            .await?;
+    }
+    for transition in &workflow.transitions {
+        sqlx::query(
+            "INSERT INTO workflow_transitions (project_id, from_state, to_state, project_role)
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(project_id)
+        .bind(&transition.from_state)
+        .bind(&transition.to_state)
+        .bind(&transition.project_role)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn workflow_transitions(db: &Pool<Any>, project_id: i32) -> Result<Vec<WorkflowTransition>> {
+    let transitions = sqlx::query_as::<_, WorkflowTransition>(
+        "SELECT transition_id, project_id, from_state, to_state, project_role
+        FROM workflow_transitions
+        WHERE project_id = $1
+        ORDER BY transition_id",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+    Ok(transitions)
+}
+
+pub(crate) async fn workflow(db: &Pool<Any>, project_id: i32) -> Result<Workflow> {
+    let states: Vec<(String,)> = sqlx::query_as(
+        "SELECT state FROM workflow_states WHERE project_id = $1 ORDER BY position",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+    let transitions = workflow_transitions(db, project_id).await?;
+    Ok(Workflow {
+        states: states.into_iter().map(|(state,)| state).collect(),
+        transitions: transitions
+            .into_iter()
+            .map(|transition| transition.transition)
+            .collect(),
+    })
+}
+
+/// The permission of a party on the tasks of a project: its permission and the policies
+/// granting the transitions of the workflow of the project.
+pub(crate) async fn permission_for_workflow(
+    state: &AppState,
+    token_claims: &TokenClaims,
+    project_id: i32,
+) -> Result<Permission> {
+    let policies = workflow_transitions(&state.db, project_id)
+        .await?
+        .iter()
+        .map(WorkflowTransition::policy)
+        .collect::<std::result::Result<Vec<Policy>, ParseErrors>>()
+        .map_err(AuthorizerError::from)?;
+    Ok(delegations::permission_for(state, token_claims)
+        .await?
+        .with_policies(policies)?)
+}
+
+#[get("/api/projects/{id}/workflow")]
+async fn get_workflow(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ViewProject, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let json = serde_json::to_string(&workflow(&state.db, id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+/// Replaces the workflow of the project, no task may be left in a removed state.
+#[put("/api/projects/{id}/workflow")]
+async fn update_workflow(
+    state: Data<Arc<AppState>>,
+    token_claims: Option<ReqData<TokenClaims>>,
+    context: Option<ReqData<RequestContext>>,
+    path: web::Path<String>,
+    body: Json<Workflow>,
+) -> Result<String> {
+    match (token_claims, context) {
+        (Some(token_claims), Some(context)) => {
+            let tk = token_claims.into_inner();
+            let id = path.into_inner().parse::<i32>()?;
+            let body = body.into_inner();
+            body.validate()?;
+
+            let resource = project_resource(&state.db, id).await?;
+            let permission = delegations::permission_for(&state, &tk).await?;
+            if !permission.is_authorized(&tk, Action::ManageWorkflow, &resource, &context)? {
+                return Err(ProjectError::AuthFailed);
+            }
+
+            let statuses: Vec<(String,)> = sqlx::query_as(
+                "SELECT DISTINCT status FROM tasks WHERE project_id = $1 AND deleted_at IS NULL",
+            )
+            .bind(id)
+            .fetch_all(&state.db)
+            .await?;
+            if let Some((status,)) = statuses.iter().find(|(status,)| !body.has_state(status)) {
+                return Err(ProjectError::InvalidInput(format!("tasks are still in state {}", status)));
+            }
+
+            let mut tx = state.db.begin().await?;
+            save_workflow(&mut tx, id, &body).await?;
+            tx.commit().await?;
+
+            let detail = format!(
+                "workflow {} with {} transitions",
+                body.states.join(" "),
+                body.transitions.len()
+            );
+            audit::record(&state.db, tk.id, "UpdateWorkflow", &resource.uid(), &[], &detail).await?;
+
+            let json = serde_json::to_string(&workflow(&state.db, id).await?)?;
+            Ok(json)
+        }
+        _ => Err(ProjectError::AuthFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_support::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn task_follows_the_project_workflow() {
+        let app = create_test_app().await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/projects/2/tasks")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({ "title": "write the specs" }))
+            .to_request();
+        let task: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(task["status"], "todo");
+        let uri = format!("/api/projects/2/tasks/{}", task["task_id"]);
+        let transition_req = |token_claims: TokenClaims, to: &str| {
+            test::TestRequest::post()
+                .uri(&format!("{}/transitions", uri))
+                .insert_header(bearer(token_claims))
+                .set_json(serde_json::json!({ "to": to }))
+                .to_request()
+        };
+
+        let req = test::TestRequest::put()
+            .uri(&uri)
+            .insert_header(bearer(developer()))
+            .set_json(serde_json::json!({ "title": "write the specs", "status": "done" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = test::call_service(&app, transition_req(developer(), "done")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let task: Value = test::call_and_read_body_json(&app, transition_req(developer(), "in_progress")).await;
+        assert_eq!(task["status"], "in_progress");
+
+        // in the default workflow, only reviewers move tasks to done
+        let resp = test::call_service(&app, transition_req(developer(), "done")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::put()
+            .uri("/api/projects/2/workflow")
+            .insert_header(bearer(projectlead()))
+            .set_json(serde_json::json!({
+                "states": ["todo", "in_progress", "done"],
+                "transitions": [
+                    { "from_state": "todo", "to_state": "in_progress", "project_role": "Contributor" },
+                    { "from_state": "in_progress", "to_state": "done", "project_role": "Contributor" },
+                ],
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let task: Value = test::call_and_read_body_json(&app, transition_req(developer(), "done")).await;
+        assert_eq!(task["status"], "done");
+    }
+}